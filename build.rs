@@ -5,6 +5,10 @@ fn main() {
     cc::Build::new()
         .file("src/cextern/fits_hcompress.c")
         .file("src/cextern/fits_hdecompress.c")
-        .include("src/cextern/fitsio2.h")
+        .file("src/cextern/ricecomp.c")
+        .file("src/cextern/pliocomp.c")
+        .file("src/cextern/quantize.c")
+        .file("src/cextern/fits_randoms.c")
+        .include("src/cextern")
         .compile("cfitsio");
 }
\ No newline at end of file
@@ -0,0 +1,60 @@
+/*
+  The table of random numbers used to dither quantized floating point pixels,
+  adapted from imcompress.c of CFITSIO. Compression and decompression must
+  draw from the same sequence, so it is generated here once and shared.
+*/
+
+#include <stdlib.h>
+#include "fitsio2.h"
+
+float *fits_rand_value = 0;
+
+int fits_init_randoms(void) {
+
+/* initialize an array of random numbers */
+
+    int ii;
+    double a = 16807.0;
+    double m = 2147483647.0;
+    double temp, seed;
+    float *values;
+
+    if (fits_rand_value)
+        return(0);   /* array is already initialized */
+
+    /* allocate array for the random number sequence */
+    values = calloc(N_RANDOM, sizeof(float));
+
+    if (!values)
+        return(MEMORY_ALLOCATION);
+
+    /*  We need a portable algorithm that anyone can use to generate this
+        exact same sequence of random number.  The C 'rand' function is not
+        suitable because it is not available to Fortran or Java programmers.
+        Instead, use a well known simple algorithm published here:
+        "Random number generators: good ones are hard to find", Communications of the ACM,
+        Volume 31 ,  Issue 10  (October 1988) Pages: 1192 - 1201
+    */
+
+    /* initialize the random numbers */
+    seed = 1;
+    for (ii = 0; ii < N_RANDOM; ii++) {
+        temp = a * seed;
+        seed = temp -m * ((int) (temp / m) );
+        values[ii] = (float) (seed / m);
+    }
+
+    /*
+      IMPORTANT NOTE: the 10000th seed value must have the value 1043618065 if the
+      algorithm has been implemented correctly
+    */
+
+    if ( (int) seed != 1043618065) {
+        ffpmsg("fits_init_randoms generated incorrect random number sequence");
+        free(values);
+        return(1);
+    }
+
+    fits_rand_value = values;
+    return(0);
+}
@@ -0,0 +1,130 @@
+//! Error handling for reading and writing FITS files.
+//!
+//! Every public read/write path in `astrors` returns a `FitsError` instead of
+//! panicking, so a single malformed file can be reported and skipped by the caller.
+
+use std::fmt;
+use std::io;
+
+/// Represents the errors that can occur while reading or writing a FITS file.
+///
+/// # Variants
+/// - `MissingKeyword`: A mandatory header keyword is missing or has the wrong type.
+/// - `InvalidHeader`: The header is not conforming (e.g. mandatory keywords out of order).
+/// - `InvalidBitpix`: The `BITPIX` value is not one of the values allowed by the standard.
+/// - `InvalidTform`: A `TFORMn` value could not be parsed.
+/// - `UnsupportedDataType`: A data type that cannot be represented in a FITS column or image.
+/// - `InvalidValue`: A field of an ASCII table could not be parsed.
+/// - `TruncatedData`: The file ended before the expected number of bytes could be read.
+/// - `UnsupportedXtension`: The `XTENSION` value is not supported by this crate.
+/// - `Io`: An underlying I/O error.
+/// - `InHdu`: Wraps another error with the HDU index and the byte offset where the HDU starts.
+#[derive(Debug)]
+pub enum FitsError {
+    MissingKeyword(String),
+    InvalidHeader(String),
+    InvalidBitpix(i64),
+    InvalidTform(String),
+    UnsupportedDataType(String),
+    InvalidValue(String),
+    TruncatedData { expected: usize, found: usize },
+    UnsupportedXtension(String),
+    Io(io::Error),
+    InHdu { index: usize, offset: u64, source: Box<FitsError> },
+}
+
+impl FitsError {
+    /// Attaches the HDU index and byte offset to the error.
+    ///
+    /// # Arguments
+    /// - `index` (usize): The index of the HDU in the file (0 is the primary HDU).
+    /// - `offset` (u64): The byte offset of the start of the HDU in the file.
+    ///
+    /// # Returns
+    /// - `FitsError`: The error wrapped in `FitsError::InHdu`. Errors that already
+    ///   carry a location are returned unchanged.
+    pub fn at(self, index: usize, offset: u64) -> Self {
+        match self {
+            FitsError::InHdu { .. } => self,
+            _ => FitsError::InHdu { index, offset, source: Box::new(self) },
+        }
+    }
+
+    /// Returns the index of the HDU where the error occurred, if known.
+    pub fn hdu_index(&self) -> Option<usize> {
+        match self {
+            FitsError::InHdu { index, .. } => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte offset of the HDU where the error occurred, if known.
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            FitsError::InHdu { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error, without the HDU location.
+    pub fn kind(&self) -> &FitsError {
+        match self {
+            FitsError::InHdu { source, .. } => source.kind(),
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for FitsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FitsError::MissingKeyword(keyword) => write!(f, "missing or invalid keyword {}", keyword),
+            FitsError::InvalidHeader(msg) => write!(f, "invalid header: {}", msg),
+            FitsError::InvalidBitpix(bitpix) => write!(f, "invalid BITPIX value {}", bitpix),
+            FitsError::InvalidTform(tform) => write!(f, "invalid TFORM value '{}'", tform),
+            FitsError::UnsupportedDataType(dtype) => write!(f, "unsupported data type {}", dtype),
+            FitsError::InvalidValue(value) => write!(f, "invalid value '{}'", value),
+            FitsError::TruncatedData { expected, found } => {
+                write!(f, "truncated data: expected {} bytes, found {}", expected, found)
+            },
+            FitsError::UnsupportedXtension(xtension) => write!(f, "unsupported XTENSION '{}'", xtension),
+            FitsError::Io(err) => write!(f, "I/O error: {}", err),
+            FitsError::InHdu { index, offset, source } => {
+                write!(f, "HDU {} (byte offset {}): {}", index, offset, source)
+            },
+        }
+    }
+}
+
+impl std::error::Error for FitsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FitsError::Io(err) => Some(err),
+            FitsError::InHdu { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FitsError {
+    fn from(err: io::Error) -> Self {
+        FitsError::Io(err)
+    }
+}
+
+/// Reports a worker pool that could not be started as an I/O error.
+impl From<rayon::ThreadPoolBuildError> for FitsError {
+    fn from(err: rayon::ThreadPoolBuildError) -> Self {
+        FitsError::Io(io::Error::other(err))
+    }
+}
+
+/// Allows `FitsError` to be propagated with `?` from functions returning `std::io::Result`.
+impl From<FitsError> for io::Error {
+    fn from(err: FitsError) -> Self {
+        match err {
+            FitsError::Io(err) => err,
+            _ => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
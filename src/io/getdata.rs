@@ -1,27 +1,23 @@
+use crate::io::errors::FitsError;
 use crate::io::hdulist::HDUList;
 use crate::io::hdulist::HDU;
 
 use super::hdus::image::ImageData;
 
 /// Returns data from the first non-empty imagehdu
-pub fn get_data(filename: &str) -> Option<ImageData> {
-    let hdulist = HDUList::fromfile(filename).unwrap();
+///
+/// Errors opening or parsing the file are returned instead of panicking, which changed the
+/// return type from `Option<ImageData>`.
+pub fn get_data(filename: &str) -> Result<Option<ImageData>, FitsError> {
+    let hdulist = HDUList::fromfile(filename)?;
 
     for hdu in hdulist.hdus {
         match hdu {
-            HDU::Primary(hdu) => {
-                if !hdu.data.is_empty() {
-                    return Some(hdu.data);
-                }
-            },
-            HDU::Image(hdu) => {
-                if !hdu.data.is_empty() {
-                    return Some(hdu.data);
-                }
-            },
+            HDU::Primary(hdu) if !hdu.data.is_empty() => return Ok(Some(hdu.data)),
+            HDU::Image(hdu) if !hdu.data.is_empty() => return Ok(Some(hdu.data)),
             _ => {}
         }
     }
 
-    None
+    Ok(None)
 }
\ No newline at end of file
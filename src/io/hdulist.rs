@@ -1,17 +1,50 @@
-use core::panic;
 use std::fmt::{self, Debug, Formatter};
-use std::io::{self, Seek, SeekFrom};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
 use std::fs::File;
 
+use crate::io::errors::FitsError;
 use crate::io::header::Header;
+use crate::io::lazyhdulist::LazyHDUList;
 
 use crate::io::hdus::primaryhdu::PrimaryHDU;
 use crate::io::hdus::image::imagehdu::ImageHDU;
 use crate::io::hdus::bintable::bintablehdu::BinTableHDU;
 use crate::io::hdus::table::tablehdu::TableHDU;
+use crate::io::hdus::compressed::compressed::is_compressed_image;
 
-use crate::io::hdus::utils::buffer_has_more_data;
+use crate::io::hdus::utils::{buffer_has_more_data, data_size_from_header};
+use crate::io::utils::{gunzip_stream, is_gzip_stream};
 
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+
+/// Defines how `HDUList::fromfile_with_mode` handles HDUs that cannot be read.
+///
+/// # Variants
+/// - `Strict`: Fails on the first non-conforming HDU.
+/// - `Lenient`: Skips the non-conforming HDU using the data size given by its header
+///   (`BITPIX`, `NAXISn`, `PCOUNT`, `GCOUNT`), records a warning and keeps reading.
+///   Reading still fails if the header of the bad HDU itself cannot be parsed, since
+///   the start of the next HDU cannot be located.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadMode {
+    #[default]
+    Strict,
+    Lenient,
+}
+
+/// Options of `HDUList::fromfile_with_options`.
+///
+/// # Fields
+/// - `mode` (ReadMode): How HDUs that cannot be read are handled.
+/// - `raw` (bool): Whether to keep image pixels as they are stored, instead of applying
+///   `BSCALE`, `BZERO` and `BLANK`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReadOptions {
+    pub mode: ReadMode,
+    pub raw: bool,
+}
 
 pub struct HDUList {
     pub hdus: Vec<HDU>,
@@ -47,7 +80,10 @@ impl HDUList {
         }
     }
 
-    /// Reads an HDUList from a file.
+    /// Reads an HDUList from a file, failing on the first HDU that cannot be read.
+    ///
+    /// Gzip-compressed files (`.fits.gz`) are recognized by their magic bytes and decompressed
+    /// in memory before being parsed.
     /// 
     /// # Arguments
     /// 
@@ -55,42 +91,205 @@ impl HDUList {
     /// 
     /// # Returns
     /// 
-    /// Returns a Result containing the HDUList if successful, or an std::io::Error if an error occurred.
-    pub fn fromfile(filename : &str) -> Result<Self, std::io::Error> {
+    /// Returns a Result containing the HDUList if successful, or a `FitsError` carrying the
+    /// index and byte offset of the HDU that could not be read.
+    pub fn fromfile(filename : &str) -> Result<Self, FitsError> {
+        let (hdulist, _) = Self::fromfile_with_mode(filename, ReadMode::Strict)?;
+        Ok(hdulist)
+    }
+
+    /// Reads an HDUList from a file with the given reading policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the file to read from.
+    /// * `mode` - The `ReadMode` deciding whether a bad HDU is an error or is skipped.
+    ///
+    /// # Returns
+    ///
+    /// Returns the HDUList together with the warnings collected while reading. Each warning is a
+    /// `FitsError` carrying the index and byte offset of the skipped HDU. In `ReadMode::Strict`
+    /// the warnings are always empty.
+    pub fn fromfile_with_mode(filename: &str, mode: ReadMode) -> Result<(Self, Vec<FitsError>), FitsError> {
+        Self::fromfile_with_options(filename, ReadOptions { mode, ..Default::default() })
+    }
+
+    /// Reads an HDUList from a file with the given options.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the file to read from.
+    /// * `options` - The `ReadOptions`, e.g. to read the stored pixels of scaled images.
+    ///
+    /// # Returns
+    ///
+    /// Returns the HDUList together with the warnings collected while reading, as
+    /// `HDUList::fromfile_with_mode` does.
+    pub fn fromfile_with_options(filename: &str, options: ReadOptions) -> Result<(Self, Vec<FitsError>), FitsError> {
         let mut f = File::open(filename)?;
+        Self::from_reader_with_options(&mut f, options)
+    }
+
+    /// Reads an HDUList from an in-memory FITS payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The content of a FITS file.
+    ///
+    /// # Returns
+    ///
+    /// Returns a Result containing the HDUList if successful, or a `FitsError` carrying the
+    /// index and byte offset of the HDU that could not be read.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FitsError> {
+        let (hdulist, _) = Self::from_reader_with_mode(&mut Cursor::new(bytes), ReadMode::Strict)?;
+        Ok(hdulist)
+    }
+
+    /// Reads an HDUList from any `Read + Seek` source, starting at its current position.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The source to read from.
+    /// * `mode` - The `ReadMode` deciding whether a bad HDU is an error or is skipped.
+    ///
+    /// # Returns
+    ///
+    /// Returns the HDUList together with the warnings collected while reading, as
+    /// `HDUList::fromfile_with_mode` does. A gzip stream is decompressed first, and the offsets
+    /// of the errors then refer to the decompressed bytes.
+    pub fn from_reader_with_mode<R: Read + Seek + ?Sized>(f: &mut R, mode: ReadMode) -> Result<(Self, Vec<FitsError>), FitsError> {
+        Self::from_reader_with_options(f, ReadOptions { mode, ..Default::default() })
+    }
+
+    /// Reads an HDUList from any `Read + Seek` source with the given options, starting at its
+    /// current position.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The source to read from.
+    /// * `options` - The `ReadOptions`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the HDUList together with the warnings collected while reading, as
+    /// `HDUList::from_reader_with_mode` does.
+    pub fn from_reader_with_options<R: Read + Seek + ?Sized>(f: &mut R, options: ReadOptions) -> Result<(Self, Vec<FitsError>), FitsError> {
+        if is_gzip_stream(f)? {
+            let bytes = gunzip_stream(f)?;
+            return Self::from_reader_with_options(&mut Cursor::new(bytes), options);
+        }
+        let mode = options.mode;
+
         let mut hdulist = HDUList::new();
-        let mut primary_hdu = true;
+        let mut warnings = Vec::new();
+        let mut index = 0;
         loop {
-            
-            let hdu = HDU::read_from_file(&mut f, Some(primary_hdu));
-            //TODO: implement own notimplemented error
-            let hdu = match hdu {
-                Ok(hdu) => hdu,
+            let offset = f.stream_position()?;
+
+            let result = if options.raw {
+                HDU::read_from_file_raw(f, Some(index == 0))
+            } else {
+                HDU::read_from_file(f, Some(index == 0))
+            };
+            match result {
+                Ok(hdu) => hdulist.add_hdu(hdu),
                 Err(e) => {
-                    println!("Error reading HDU: {}", e);
-                    continue;
+                    let e = e.at(index, offset);
+                    if mode == ReadMode::Strict {
+                        return Err(e);
+                    }
+                    skip_hdu(f, offset).map_err(|skip_err| skip_err.at(index, offset))?;
+                    warnings.push(e);
                 }
-            };
-            primary_hdu = false;
-            hdulist.add_hdu(hdu);
+            }
+            index += 1;
 
-            if !buffer_has_more_data(&mut f)? {
+            if !buffer_has_more_data(f)? {
                 break;
             }
         }
-        Ok(hdulist)
+        Ok((hdulist, warnings))
     }
 
-    pub fn write_to(&mut self, filename: &str) -> Result<(), std::io::Error> {
+    /// Opens a file without decoding its data.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the file to read from.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `LazyHDUList` holding the parsed header, data offset and data size of each HDU.
+    /// The image or table data of an HDU is decoded the first time `LazyHDU::data` is called.
+    pub fn open(filename: &str) -> Result<LazyHDUList, FitsError> {
+        LazyHDUList::open(filename)
+    }
+
+    /// Writes the HDUList to a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the file to write to.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `FitsError` carrying the index of the HDU that could not be written.
+    pub fn write_to(&mut self, filename: &str) -> Result<(), FitsError> {
         let mut f = File::create(filename)?;
-        for hdu in &mut self.hdus {
-            match hdu {
-                HDU::Primary(hdu)   => hdu.write_to_file(&mut f)?,
-                HDU::Image(hdu)       => hdu.write_to_file(&mut f)?,
-                HDU::Table(hdu)       => hdu.write_to_file(&mut f)?,
-                HDU::BinTable(hdu) => hdu.write_to_file(&mut f)?,
-            }
+        self.write_to_writer(&mut f)
+    }
+
+    /// Writes the HDUList to a gzip-compressed file (`.fits.gz`).
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path to the file to write to.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `FitsError` carrying the index of the HDU that could not be written.
+    pub fn write_to_gzip(&mut self, filename: &str) -> Result<(), FitsError> {
+        let f = File::create(filename)?;
+        let mut encoder = GzEncoder::new(io::BufWriter::new(f), Compression::default());
+        self.write_to_writer(&mut encoder)?;
+        encoder.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Serializes the HDUList into an in-memory FITS payload.
+    ///
+    /// # Returns
+    ///
+    /// Returns the bytes of the FITS file, or a `FitsError` carrying the index of the HDU
+    /// that could not be written.
+    pub fn to_bytes(&mut self) -> Result<Vec<u8>, FitsError> {
+        let mut bytes = Vec::new();
+        self.write_to_writer(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Writes the HDUList to any `Write` sink.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The sink to write to.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `FitsError` carrying the index and byte offset of the HDU that could not be written.
+    pub fn write_to_writer<W: Write + ?Sized>(&mut self, writer: &mut W) -> Result<(), FitsError> {
+        let mut f = CountingWriter { inner: writer, count: 0 };
+        for (index, hdu) in self.hdus.iter_mut().enumerate() {
+            let offset = f.count;
+            let result = match hdu {
+                HDU::Primary(hdu)   => hdu.write_to_file(&mut f),
+                HDU::Image(hdu)       => hdu.write_to_file(&mut f),
+                HDU::Table(hdu)       => hdu.write_to_file(&mut f),
+                HDU::BinTable(hdu) => hdu.write_to_file(&mut f),
+            };
+            result.map_err(|e| e.at(index, offset))?;
         }
+        f.flush()?;
         Ok(())
     }
 
@@ -104,6 +303,36 @@ impl HDUList {
     }
 }
 
+/// Wraps a writer to keep track of the byte offset of each HDU written.
+struct CountingWriter<'a, W: Write + ?Sized> {
+    inner: &'a mut W,
+    count: u64,
+}
+
+impl<W: Write + ?Sized> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Moves the file cursor past the HDU starting at `offset`, using only its header.
+fn skip_hdu<R: Read + Seek + ?Sized>(f: &mut R, offset: u64) -> Result<(), FitsError> {
+    f.seek(SeekFrom::Start(offset))?;
+    let mut header = Header::new();
+    header.read_from_file(f)?;
+
+    let data_size = data_size_from_header(&header)?;
+    let padded_size = data_size.div_ceil(2880) * 2880;
+    f.seek(SeekFrom::Current(padded_size as i64))?;
+    Ok(())
+}
+
 pub enum HDU {
     Primary(PrimaryHDU),
     Image(ImageHDU),
@@ -128,48 +357,68 @@ impl HDU {
     ///
     /// # Arguments
     ///
-    /// * `f` - A mutable reference to a `File` object representing the FITS file.
+    /// * `f` - A mutable reference to the FITS file or any other `Read + Seek` source.
     /// * `primary_hdu` - An optional boolean value indicating whether the HDU is the primary HDU.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing the parsed HDU if successful, or an `std::io::Error` if an I/O error occurs.
-    pub fn read_from_file(f: &mut File, primary_hdu: Option<bool>) -> Result<Self, std::io::Error> {
+    /// Returns a `Result` containing the parsed HDU if successful, or a `FitsError`.
+    /// A BINTABLE with `ZIMAGE = T` is returned as a decompressed `HDU::Image`.
+    /// A non-primary HDU without `XTENSION` is reported as `FitsError::MissingKeyword`,
+    /// and an unknown `XTENSION` as `FitsError::UnsupportedXtension`.
+    /// The pixels of scaled images are converted to their physical values.
+    pub fn read_from_file<R: Read + Seek + ?Sized>(f: &mut R, primary_hdu: Option<bool>) -> Result<Self, FitsError> {
+        Self::read_hdu(f, primary_hdu, false)
+    }
+
+    /// Reads an HDU from a file, keeping the pixels of images as they are stored.
+    ///
+    /// See `HDU::read_from_file`; `BSCALE`, `BZERO` and `BLANK` are not applied.
+    pub fn read_from_file_raw<R: Read + Seek + ?Sized>(f: &mut R, primary_hdu: Option<bool>) -> Result<Self, FitsError> {
+        Self::read_hdu(f, primary_hdu, true)
+    }
+
+    fn read_hdu<R: Read + Seek + ?Sized>(f: &mut R, primary_hdu: Option<bool>, raw: bool) -> Result<Self, FitsError> {
         let current_pos = f.stream_position()?;
         let mut header = Header::new();
         header.read_from_file(f)?;
         
         if primary_hdu.unwrap_or(false) {
             f.seek(SeekFrom::Start(current_pos))?;
-            let primaryhdu = PrimaryHDU::read_from_file(f)?;
+            let primaryhdu = if raw { PrimaryHDU::read_from_file_raw(f)? } else { PrimaryHDU::read_from_file(f)? };
             Ok(HDU::Primary(primaryhdu))
 
         } else if header.contains_key("XTENSION") {
-            let mut hdu_type = header["XTENSION"].value.to_string();
+            let mut hdu_type = header.get_value("XTENSION")?.to_string();
             hdu_type.retain(|c| !c.is_whitespace());
             match hdu_type.as_str() {
                 "IMAGE" => {
                     f.seek(SeekFrom::Start(current_pos))?;
-                    let imagehdu = ImageHDU::read_from_file(f)?;
-                    return Ok(HDU::Image(imagehdu))
+                    let imagehdu = if raw { ImageHDU::read_from_file_raw(f)? } else { ImageHDU::read_from_file(f)? };
+                    Ok(HDU::Image(imagehdu))
                 },
                 "TABLE" => {
                     f.seek(SeekFrom::Start(current_pos))?;
                     let tablehdu = TableHDU::read_from_file(f)?;
-                    return Ok(HDU::Table(tablehdu))
+                    Ok(HDU::Table(tablehdu))
+                },
+                "BINTABLE" if is_compressed_image(&header) => {
+                    f.seek(SeekFrom::Start(current_pos))?;
+                    let imagehdu = if raw { ImageHDU::read_from_file_raw(f)? } else { ImageHDU::read_from_file(f)? };
+                    Ok(HDU::Image(imagehdu))
                 },
                 "BINTABLE" => {
                     f.seek(SeekFrom::Start(current_pos))?;
                     let bintablehdu = BinTableHDU::read_from_file(f)?;
-                    return Ok(HDU::BinTable(bintablehdu))
+                    Ok(HDU::BinTable(bintablehdu))
                 },
                 _ => {
-                    Err(io::Error::new(io::ErrorKind::Other, "Not implemented HDU type"))
+                    Err(FitsError::UnsupportedXtension(hdu_type))
                 }
                 
             }
         } else {
-            panic!("Not implemented");
+            Err(FitsError::MissingKeyword("XTENSION".to_string()))
         }
     }
 }
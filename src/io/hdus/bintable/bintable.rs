@@ -1,7 +1,9 @@
-use core::panic;
-use std::io::Write;
-use std::{fs::File, io::Read};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 
+use crate::io::errors::FitsError;
+use crate::io::utils::read_exact_or_truncated;
+use crate::io::hdus::utils::{get_size, get_size_or};
 use crate::io::{hdus::bintable::buffer::Buffer, header::card::Card, utils::pad_buffer_to_fits_block, utils::pad_read_buffer_to_fits_block, Header};
 use crate::io::hdus::bintable::*;
 
@@ -22,7 +24,10 @@ extern crate num_cpus;
 /// - `start_address` (usize): The starting byte offset for the column's data in each row.
 /// - `type_bytes` (usize): The number of bytes required to store the column's data.
 /// - `type_letter` (String): The first letter of the column's format, indicating the data type.
-#[derive(Debug)]
+/// - `tscal` (Option<f64>): The scale of the stored values (`TSCALn`), if any.
+/// - `tzero` (Option<f64>): The offset of the stored values (`TZEROn`), if any.
+/// - `tnull` (Option<i64>): The stored value marking undefined integers (`TNULLn`), if any.
+#[derive(Debug, Clone)]
 pub struct Column {
     pub ttype: String, 
     pub tform: String,
@@ -31,6 +36,9 @@ pub struct Column {
     pub start_address: usize,
     pub type_bytes : usize,
     pub type_letter: String,
+    pub tscal: Option<f64>,
+    pub tzero: Option<f64>,
+    pub tnull: Option<i64>,
 }
 
 impl Column {
@@ -44,19 +52,23 @@ impl Column {
     /// - `start_address` (usize): The starting byte offset for the column's data in each row.
     ///
     /// # Returns
-    /// A new `Column` instance with calculated `type_bytes` and `type_letter`.
-    pub fn new(ttype: String, tform: String, tunit: Option<String>, tdisp: Option<String>, start_address: usize) -> Self {
-        let tform2 = tform.clone();
-        let column = Column {
+    /// A new `Column` instance with calculated `type_bytes` and `type_letter` and no scaling,
+    /// or `FitsError::InvalidTform` if `tform` cannot be parsed.
+    pub fn new(ttype: String, tform: String, tunit: Option<String>, tdisp: Option<String>, start_address: usize) -> Result<Self, FitsError> {
+        let type_bytes = get_data_bytes_size(&tform)?;
+        let type_letter = get_first_letter(&tform).to_string();
+        Ok(Column {
             ttype,
             tform,
             tunit,
             tdisp,
             start_address, 
-            type_bytes: get_data_bytes_size(&tform2),
-            type_letter: get_first_letter(&tform2).to_string(),
-        };
-        column
+            type_bytes,
+            type_letter,
+            tscal: None,
+            tzero: None,
+            tnull: None,
+        })
     }
 }
 
@@ -66,15 +78,17 @@ impl Column {
 /// - `header` (&Header): The FITS header containing column metadata.
 ///
 /// # Returns
-/// - `Result<Vec<Column>, String>`: A vector of `Column` instances or an error string.
+/// - `Result<Vec<Column>, FitsError>`: A vector of `Column` instances, `FitsError::MissingKeyword`
+///   if `TFIELDS` or a `TFORMn` is missing, or `FitsError::InvalidTform` if a `TFORMn` cannot be parsed.
 ///
 /// # Behavior
-/// - Extracts column properties like `TTYPE`, `TFORM`, `TUNIT`, and `TDISP` from the header.
+/// - Extracts column properties like `TTYPE`, `TFORM`, `TUNIT`, `TDISP`, `TSCAL`, `TZERO` and `TNULL` from the header.
 /// - Computes the starting address and byte size for each column.
-pub fn read_tableinfo_from_header(header: &Header) -> Result<Vec<Column>, String> {
+/// - Fields without a `TTYPEn` are named `col{n}`.
+pub fn read_tableinfo_from_header(header: &Header) -> Result<Vec<Column>, FitsError> {
     let mut columns: Vec<Column> = Vec::new();
 
-    let tfields = header["TFIELDS"].value.as_int().unwrap_or(0);
+    let tfields = header.get_int("TFIELDS")?;
     let mut start_address = 0;
 
     for i in 1..=tfields {
@@ -83,19 +97,19 @@ pub fn read_tableinfo_from_header(header: &Header) -> Result<Vec<Column>, String
         let tunit: Option<&Card> = header.get_card(&format!("TUNIT{}", i));
         let tdisp: Option<&Card> = header.get_card(&format!("TDISP{}", i));
 
-        if ttype.is_none() {
-            break;
-        }
-
-        let ttype: String = ttype.unwrap().value.to_string().trim_end().to_string();
-        let tform: String = tform.unwrap().value.to_string();
+        let ttype: String = ttype.map_or_else(|| format!("col{}", i), |c| c.value.to_string().trim_end().to_string());
+        let tform: String = tform
+            .ok_or_else(|| FitsError::MissingKeyword(format!("TFORM{}", i)))?
+            .value.to_string();
         let tunit: Option<String> = tunit.map(|c| c.value.to_string());
         let tdisp: Option<String> = tdisp.map(|c| c.value.to_string());
 
-        let size = get_data_bytes_size(&tform);
-        let column = Column::new(ttype, tform, tunit, tdisp, start_address);
+        let mut column = Column::new(ttype, tform, tunit, tdisp, start_address)?;
+        column.tscal = header.get_float(&format!("TSCAL{}", i)).ok();
+        column.tzero = header.get_float(&format!("TZERO{}", i)).ok();
+        column.tnull = header.get_int(&format!("TNULL{}", i)).ok();
 
-        start_address += size;
+        start_address += column.type_bytes;
 
         columns.push(column);
     }
@@ -108,20 +122,23 @@ pub fn read_tableinfo_from_header(header: &Header) -> Result<Vec<Column>, String
 /// # Arguments
 /// - `columns` (&mut Vec<Column>): A mutable reference to a vector of `Column` instances.
 /// - `header` (&Header): The FITS header containing metadata for the table.
-/// - `file` (&mut File): The file from which to read the data.
+/// - `file` (&mut R): The file or reader from which to read the data.
 ///
 /// # Returns
-/// - `Result<DataFrame, std::io::Error>`: The resulting `DataFrame` or an I/O error.
+/// - `Result<DataFrame, FitsError>`: The resulting `DataFrame`, or `FitsError::TruncatedData`
+///   if the file ends before the table data.
 ///
 /// # Behavior
 /// - Reads the table's binary data in chunks using multiple threads.
 /// - Constructs a `DataFrame` by iterating over the data rows and columns.
-pub fn read_table_bytes_to_df(columns : &mut Vec<Column>, header: &Header, file: &mut File) -> Result<DataFrame, std::io::Error> {
-    let nrows = header["NAXIS2"].value.as_int().unwrap_or(0);
+/// - Variable-length array columns are read from the heap, which starts `THEAP` bytes after
+///   the start of the data.
+pub fn read_table_bytes_to_df<R: Read + Seek + ?Sized>(columns : &mut [Column], header: &Header, file: &mut R) -> Result<DataFrame, FitsError> {
+    let nrows = get_size(header, "NAXIS2")?;
     let mut n_chunks: u16 = 1;
     let mut n_threads: u16 = num_cpus::get() as u16;
 
-    if nrows > n_threads as i64 * 10 {
+    if nrows > n_threads as usize * 10 {
         n_chunks = n_threads;
     }
     else {
@@ -129,228 +146,1003 @@ pub fn read_table_bytes_to_df(columns : &mut Vec<Column>, header: &Header, file:
     }
 
     let bytes_per_row = calculate_number_of_bytes_of_row(columns);
-    let buffer_size = nrows as usize * bytes_per_row;
+    let buffer_size = nrows.checked_mul(bytes_per_row)
+        .ok_or_else(|| FitsError::InvalidHeader(format!("{} rows of {} bytes", nrows, bytes_per_row)))?;
     let limits = split_buffer(buffer_size, n_chunks, bytes_per_row as u16);
 
     let mut buffer = vec![0; buffer_size];
-    file.read_exact(&mut buffer)?;
+    read_exact_or_truncated(file, &mut buffer)?;
     
-    //TODO read suplemental data here
-    if header.get_card("THEAP").is_some(){
-        // Skip theap bytes
-        let theap = header.get_card("THEAP").unwrap().value.as_int().unwrap();
-        let mut theap_buffer = vec![0; theap as usize];
-        file.read_exact(&mut theap_buffer)?;
-        drop(theap_buffer);
-    }
-    let mut pcount_buffer;
-    if header.get_card("PCOUNT").is_some(){
-        // Skip pcount bytes
-        let pcount = header.get_card("PCOUNT").unwrap().value.as_int().unwrap();
-        pcount_buffer = vec![0; pcount as usize];
-        file.read_exact(&mut pcount_buffer)?;
-    }
-
+    // The supplemental data area holds the gap up to THEAP and the heap of the array descriptors.
+    let pcount = get_size_or(header, "PCOUNT", 0)?;
+    let mut supplemental = vec![0; pcount];
+    read_exact_or_truncated(file, &mut supplemental)?;
+    let heap = &supplemental[heap_gap(header, buffer_size)?.min(pcount)..];
 
     //use rayon pool install
     //let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
-    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads as usize).build().unwrap();
-    let results : Vec<Result<DataFrame, std::io::Error>> = pool.install(|| {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads as usize).build()?;
+    let results : Vec<Result<DataFrame, FitsError>> = pool.install(|| {
         limits.into_par_iter().map(|(start, end)| {
-            let local_buffer: &[u8] = &buffer[start..end];
-            let nbuffer_rows = (end - start) / bytes_per_row;
-
-            let mut local_buf_cols : Vec<Buffer> = Vec::new();
-            columns.iter().for_each(|column: &Column| {
-                local_buf_cols.push(Buffer::new(&column.tform, nbuffer_rows as i32));
-            });
-            
-            (0..nbuffer_rows).for_each(|i| {
-                let row_start_idx = i * bytes_per_row;
-                let row = &local_buffer[row_start_idx..row_start_idx + bytes_per_row];
-                columns.iter().enumerate().for_each(|(j, column)| {
-                    let buf_col = &mut local_buf_cols[j];
-                    let col_bytes = &row[column.start_address..column.start_address + column.type_bytes];
-                    buf_col.write_on_idx(col_bytes, i as i64);                });
-            });
-
-            let df_cols = columns.iter().enumerate().map(|(i, column)| {
-                if (get_first_letter(&column.tform) == "P") | (get_first_letter(&column.tform) == "Q") {
-                    local_buf_cols[i].read_var_len_cols();
-                }
-                
-                let buf_col = &local_buf_cols[i];
-                let series = buf_col.to_series(&column.ttype);
-                local_buf_cols[i].clear();
-                series
-            }).collect();
-
-            let local_df = unsafe { DataFrame::new_no_checks(df_cols) };
-            Ok(local_df)
+            decode_table_rows(columns, &buffer[start..end], bytes_per_row, heap, 0)
         }).collect()
     });
     drop(buffer);
+    drop(supplemental);
 
-    let mut final_df = results[0].as_ref().unwrap().clone();
-    for i in 1..results.len() {
-        final_df.vstack_mut(results[i].as_ref().unwrap()).unwrap();
+    let mut results = results.into_iter();
+    let mut final_df = match results.next() {
+        Some(df) => df?,
+        None => DataFrame::empty(),
+    };
+    for df in results {
+        final_df.vstack_mut(&df?)
+            .map_err(|e| FitsError::InvalidValue(format!("table chunks could not be stacked ({})", e)))?;
     }
-    pad_read_buffer_to_fits_block(file, buffer_size)?;
+    pad_read_buffer_to_fits_block(file, buffer_size + pcount)?;
 
     Ok(final_df)
 }
 
+/// Returns the number of bytes between the end of the rows and the start of the heap.
+///
+/// # Arguments
+/// - `header` (&Header): The header of the binary table.
+/// - `rows_size` (usize): The size of the rows, `NAXIS1 * NAXIS2`.
+///
+/// # Returns
+/// - `Result<usize, FitsError>`: `THEAP - rows_size`, 0 without `THEAP`, or `FitsError::InvalidHeader`
+///   if `THEAP` points inside the rows.
+fn heap_gap(header: &Header, rows_size: usize) -> Result<usize, FitsError> {
+    let theap = get_size_or(header, "THEAP", rows_size)?;
+    theap.checked_sub(rows_size).ok_or_else(|| FitsError::InvalidHeader(
+        format!("THEAP = {} is before the end of the {} bytes of rows", theap, rows_size)))
+}
+
+/// Returns the range of heap bytes used by the variable-length arrays of some rows.
+///
+/// # Arguments
+/// - `columns` (&[Column]): The columns to decode.
+/// - `buffer` (&[u8]): The bytes of whole rows.
+/// - `bytes_per_row` (usize): The size of a row, `NAXIS1`.
+///
+/// # Returns
+/// - `Result<Option<Range<usize>>, FitsError>`: The offsets in the heap from the first to past the
+///   last byte of the arrays, or `None` if the columns have no array in these rows.
+fn heap_extent(columns: &[Column], buffer: &[u8], bytes_per_row: usize) -> Result<Option<Range<usize>>, FitsError> {
+    let mut extent: Option<Range<usize>> = None;
+    for column in columns.iter().filter(|c| (c.type_letter == "P") | (c.type_letter == "Q")) {
+        let letter = get_var_len_type(&column.tform)?;
+        for row in buffer.chunks_exact(bytes_per_row) {
+            let (count, offset) = read_descriptor(&row[column.start_address..column.start_address + column.type_bytes]);
+            if count == 0 {
+                continue;
+            }
+            let end = offset.checked_add(var_len_array_bytes(letter, count)?).ok_or_else(|| FitsError::InvalidValue(format!(
+                "array of {} elements at heap offset {} of column {} is outside the heap", count, offset, column.ttype
+            )))?;
+            extent = Some(match extent {
+                Some(range) => range.start.min(offset)..range.end.max(end),
+                None => offset..end,
+            });
+        }
+    }
+    Ok(extent)
+}
+
+/// Decodes consecutive rows of a binary table into a Polars `DataFrame`.
+///
+/// # Arguments
+/// - `columns` (&[Column]): The columns to decode, any subset of the columns of the table.
+/// - `buffer` (&[u8]): The bytes of whole rows.
+/// - `bytes_per_row` (usize): The size of a row, `NAXIS1`.
+/// - `heap` (&[u8]): The heap of the table, or the part of it holding the arrays of these rows.
+/// - `heap_offset` (usize): The offset in the heap of the first byte of `heap`.
+///
+/// # Returns
+/// - `Result<DataFrame, FitsError>`: One series per column, or `FitsError::InvalidTform` if a
+///   column format cannot be decoded.
+///
+/// # Behavior
+/// - Variable-length array columns (`P` and `Q`) are resolved against `heap`, see
+///   `Buffer::read_var_len_cols`.
+/// - Integers equal to `TNULLn` and NaN floats are read as nulls, see `mask_nulls`.
+/// - Fixed-width arrays whose elements are all null are read as null rows, see `mask_null_rows`.
+/// - Columns with `TSCALn` or `TZEROn` are converted to their physical values, unsigned integers
+///   for the standard offsets, see `scale_series`.
+pub fn decode_table_rows(columns: &[Column], buffer: &[u8], bytes_per_row: usize, heap: &[u8], heap_offset: usize) -> Result<DataFrame, FitsError> {
+    let nbuffer_rows = buffer.len().checked_div(bytes_per_row).unwrap_or(0);
+
+    let mut local_buf_cols : Vec<Buffer> = Vec::new();
+    for column in columns.iter() {
+        local_buf_cols.push(Buffer::new(&column.tform, nbuffer_rows as i32)?);
+    }
+
+    for i in 0..nbuffer_rows {
+        let row_start_idx = i * bytes_per_row;
+        let row = &buffer[row_start_idx..row_start_idx + bytes_per_row];
+        for (buf_col, column) in local_buf_cols.iter_mut().zip(columns) {
+            let col_bytes = &row[column.start_address..column.start_address + column.type_bytes];
+            buf_col.write_on_idx(col_bytes, i as i64)?;
+        }
+    }
+
+    let df_cols = columns.iter().enumerate().map(|(i, column)| {
+        let mut series = local_buf_cols[i].read_var_len_cols(&column.ttype, heap, heap_offset)?;
+        local_buf_cols[i].clear();
+        series = mask_nulls(series, column.tnull)?;
+        if !matches!(column.type_letter.as_str(), "P" | "Q" | "X") {
+            series = mask_null_rows(series)?;
+        }
+        if column.tscal.is_some() || column.tzero.is_some() {
+            series = scale_series(series, column.tscal.unwrap_or(1.0), column.tzero.unwrap_or(0.0))?;
+        }
+        Ok(series)
+    }).collect::<Result<Vec<Series>, FitsError>>()?;
+
+    Ok(unsafe { DataFrame::new_no_checks(df_cols) })
+}
+
+/// A column of a binary table, by name (`TTYPEn`) or by 0-based index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnRef {
+    Name(String),
+    Index(usize),
+}
+
+impl From<&str> for ColumnRef {
+    fn from(name: &str) -> Self {
+        ColumnRef::Name(name.to_string())
+    }
+}
+
+impl From<usize> for ColumnRef {
+    fn from(index: usize) -> Self {
+        ColumnRef::Index(index)
+    }
+}
+
+/// The columns and rows to read from a binary table.
+///
+/// # Fields
+/// - `columns` (Option<Vec<ColumnRef>>): The columns to decode, in the order of the resulting
+///   `DataFrame`. All columns when `None`.
+/// - `rows` (Option<Range<usize>>): The 0-based range of rows to read. All rows when `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableProjection {
+    pub columns: Option<Vec<ColumnRef>>,
+    pub rows: Option<Range<usize>>,
+}
+
+impl TableProjection {
+    /// Creates a projection on the given columns, reading all rows.
+    pub fn columns<C: Into<ColumnRef>>(columns: impl IntoIterator<Item = C>) -> Self {
+        Self { columns: Some(columns.into_iter().map(Into::into).collect()), rows: None }
+    }
+
+    /// Restricts the projection to a range of rows.
+    pub fn with_rows(mut self, rows: Range<usize>) -> Self {
+        self.rows = Some(rows);
+        self
+    }
+
+    /// Returns the selected columns of a table, in the requested order.
+    ///
+    /// # Returns
+    /// - `Result<Vec<Column>, FitsError>`: The selected columns, or `FitsError::InvalidValue` for
+    ///   an unknown or repeated column.
+    fn select(&self, columns: &[Column]) -> Result<Vec<Column>, FitsError> {
+        let Some(refs) = &self.columns else {
+            return Ok(columns.to_vec());
+        };
+        let mut selected: Vec<Column> = Vec::with_capacity(refs.len());
+        for column_ref in refs {
+            let column = match column_ref {
+                ColumnRef::Name(name) => columns.iter().find(|c| &c.ttype == name),
+                ColumnRef::Index(index) => columns.get(*index),
+            }.ok_or_else(|| FitsError::InvalidValue(format!("no column {:?} in the table", column_ref)))?;
+            if selected.iter().any(|c| c.ttype == column.ttype) {
+                return Err(FitsError::InvalidValue(format!("column {} selected twice", column.ttype)));
+            }
+            selected.push(column.clone());
+        }
+        Ok(selected)
+    }
+}
+
+/// Iterator over the rows of a binary table, decoded into `DataFrame` batches.
+///
+/// Only one batch of rows is held in memory at a time, and only the selected columns are
+/// decoded. The arrays of variable-length columns are read from the part of the heap they use.
+/// Once the last batch is read, the cursor is moved past the heap to the end of the HDU.
+pub struct TableBatches<'a, R: Read + Seek + ?Sized> {
+    file: &'a mut R,
+    header: Header,
+    columns: Vec<Column>,
+    row_size: usize,
+    data_start: u64,
+    nrows: usize,
+    end_row: usize,
+    batch_rows: usize,
+    next_row: usize,
+    buffer: Vec<u8>,
+    heap_start: u64,
+    heap_size: usize,
+    heap: Vec<u8>,
+}
+
+impl<'a, R: Read + Seek + ?Sized> TableBatches<'a, R> {
+    /// Prepares to read all the rows and columns of the table whose data unit starts at the cursor.
+    ///
+    /// # Arguments
+    /// - `file` (&mut R): The file or reader, positioned after the header of the table.
+    /// - `header` (Header): The header of the binary table.
+    /// - `batch_rows` (usize): The maximum number of rows per batch.
+    ///
+    /// # Returns
+    /// - `Result<TableBatches<R>, FitsError>`: The iterator, or `FitsError::InvalidValue` if
+    ///   `batch_rows` is 0.
+    pub fn new(file: &'a mut R, header: Header, batch_rows: usize) -> Result<Self, FitsError> {
+        Self::with_projection(file, header, batch_rows, &TableProjection::default())
+    }
+
+    /// Prepares to read some rows and columns of the table whose data unit starts at the cursor.
+    ///
+    /// # Arguments
+    /// - `file` (&mut R): The file or reader, positioned after the header of the table.
+    /// - `header` (Header): The header of the binary table.
+    /// - `batch_rows` (usize): The maximum number of rows per batch.
+    /// - `projection` (&TableProjection): The columns to decode and the rows to read.
+    ///
+    /// # Returns
+    /// - `Result<TableBatches<R>, FitsError>`: The iterator, positioned on the first requested row
+    ///   with a seek of `NAXIS1` bytes per skipped row. `FitsError::InvalidValue` if `batch_rows` is
+    ///   0, a column is unknown or the row range is not within the table.
+    pub fn with_projection(file: &'a mut R, header: Header, batch_rows: usize, projection: &TableProjection) -> Result<Self, FitsError> {
+        if batch_rows == 0 {
+            return Err(FitsError::InvalidValue("batches of 0 rows".to_string()));
+        }
+        let columns = projection.select(&read_tableinfo_from_header(&header)?)?;
+        let row_size = get_size(&header, "NAXIS1")?;
+        let nrows = get_size(&header, "NAXIS2")?;
+        let rows = projection.rows.clone().unwrap_or(0..nrows);
+        if rows.start > rows.end || rows.end > nrows {
+            return Err(FitsError::InvalidValue(format!("rows {:?} of a table of {} rows", rows, nrows)));
+        }
+        if let Some(column) = columns.iter().find(|c| c.start_address + c.type_bytes > row_size) {
+            return Err(FitsError::InvalidValue(format!("column {} does not fit in NAXIS1 = {}", column.ttype, row_size)));
+        }
+
+        let pcount = get_size_or(&header, "PCOUNT", 0)?;
+        let rows_size = nrows.checked_mul(row_size)
+            .ok_or_else(|| FitsError::InvalidHeader(format!("{} rows of {} bytes", nrows, row_size)))?;
+        let heap_gap = heap_gap(&header, rows_size)?;
+
+        let data_start = file.stream_position()?;
+        file.seek(SeekFrom::Start(data_start + (rows.start * row_size) as u64))?;
+
+        let mut batches = Self {
+            file,
+            header,
+            columns,
+            row_size,
+            data_start,
+            nrows,
+            end_row: rows.end,
+            batch_rows,
+            next_row: rows.start,
+            buffer: vec![0; batch_rows.min(rows.len()) * row_size],
+            heap_start: data_start + (rows_size + heap_gap) as u64,
+            heap_size: pcount.saturating_sub(heap_gap),
+            heap: Vec::new(),
+        };
+        if rows.is_empty() {
+            batches.skip_to_end()?;
+        }
+        Ok(batches)
+    }
+
+    /// Returns the header of the table.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the selected columns of the table.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the number of rows of the table, `NAXIS2`.
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    /// Returns the number of requested rows that are still to be read.
+    pub fn remaining_rows(&self) -> usize {
+        self.end_row - self.next_row
+    }
+
+    /// Decodes the selected columns of no rows, giving the schema of the batches.
+    pub fn empty_frame(&self) -> Result<DataFrame, FitsError> {
+        decode_table_rows(&self.columns, &[], self.row_size, &[], 0)
+    }
+
+    /// Moves the cursor past the rows, the heap and the padding of the table.
+    fn skip_to_end(&mut self) -> Result<(), FitsError> {
+        let pcount = get_size_or(&self.header, "PCOUNT", 0)?;
+        let end = self.nrows.checked_mul(self.row_size)
+            .and_then(|size| size.checked_add(pcount))
+            .and_then(|size| size.checked_next_multiple_of(2880))
+            .and_then(|size| self.data_start.checked_add(size as u64))
+            .ok_or_else(|| FitsError::InvalidHeader(format!(
+                "{} rows of {} bytes and a heap of {} bytes overflow the data size", self.nrows, self.row_size, pcount
+            )))?;
+        self.file.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+
+    fn read_batch(&mut self) -> Result<DataFrame, FitsError> {
+        let rows = self.batch_rows.min(self.remaining_rows());
+        let buffer = &mut self.buffer[..rows * self.row_size];
+        read_exact_or_truncated(self.file, buffer)?;
+
+        // Only the part of the heap holding the arrays of the batch is read.
+        let heap_offset = match heap_extent(&self.columns, buffer, self.row_size)? {
+            Some(extent) => {
+                if extent.end > self.heap_size {
+                    return Err(FitsError::InvalidValue(format!(
+                        "arrays up to heap offset {} in a heap of {} bytes", extent.end, self.heap_size
+                    )));
+                }
+                let position = self.file.stream_position()?;
+                self.heap.resize(extent.len(), 0);
+                self.file.seek(SeekFrom::Start(self.heap_start + extent.start as u64))?;
+                read_exact_or_truncated(self.file, &mut self.heap)?;
+                self.file.seek(SeekFrom::Start(position))?;
+                extent.start
+            },
+            None => {
+                self.heap.clear();
+                0
+            },
+        };
+        let df = decode_table_rows(&self.columns, buffer, self.row_size, &self.heap, heap_offset)?;
+        self.next_row += rows;
+        if self.next_row == self.end_row {
+            self.skip_to_end()?;
+        }
+        Ok(df)
+    }
+}
+
+impl<R: Read + Seek + ?Sized> Iterator for TableBatches<'_, R> {
+    type Item = Result<DataFrame, FitsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_row >= self.end_row {
+            return None;
+        }
+        let batch = self.read_batch();
+        if batch.is_err() {
+            // Stop after an error rather than decoding misaligned rows.
+            self.next_row = self.end_row;
+        }
+        Some(batch)
+    }
+}
+
+/// The longest strings written in fixed-width `A` columns; columns with longer strings are written
+/// as variable-length `PA` columns, so that a few long values do not widen every row.
+pub const MAX_FIXED_STRING_LENGTH: usize = 1024;
+
 /// Converts a Polars `DataFrame` into a vector of `Column` instances.
 ///
 /// # Arguments
 /// - `df` (&DataFrame): The input `DataFrame`.
 ///
 /// # Returns
-/// - `Result<Vec<Column>, std::io::Error>`: A vector of `Column` instances or an I/O error.
+/// - `Result<Vec<Column>, FitsError>`: A vector of `Column` instances, or `FitsError::UnsupportedDataType`
+///   for a column type that has no FITS equivalent.
 ///
 /// # Behavior
 /// - Maps each column in the `DataFrame` to a `Column` with the corresponding FITS-compatible format.
 /// - Handles primitive types, strings, and list types with appropriate `TFORM` and byte size calculations.
-pub fn polars_to_columns(df: &DataFrame) -> Result<Vec<Column>, std::io::Error> {
+/// - `List` columns whose rows all have the same length become fixed-width repeat columns (`nD`),
+///   ragged or null lists become variable-length columns (`1PD(max)`) stored in the heap.
+/// - `String` columns longer than `MAX_FIXED_STRING_LENGTH` become variable-length `1PA(max)` columns.
+/// - `Q` descriptors are used instead of `P` once the heap grows past the 32-bit offsets of `P`.
+/// - Structs of two `Float32` or `Float64` fields, the real and imaginary parts, become complex
+///   `C` or `M` columns.
+/// - `UInt8` columns become `B` columns, `Int8` columns become `B` columns with `TZERO = -128`.
+/// - `UInt16`, `UInt32` and `UInt64` columns become `I`, `J` and `K` columns with `TZERO` = 2^15,
+///   2^31 and 2^63.
+/// - Integer columns holding nulls get a `TNULL`, a stored value not taken by the column, see
+///   `choose_tnull`. Null floats are written as NaN and null logicals as a null byte.
+pub fn polars_to_columns(df: &DataFrame) -> Result<Vec<Column>, FitsError> {
+    polars_to_columns_with_bit_arrays(df, &[])
+}
+
+/// Converts a Polars `DataFrame` into a vector of `Column` instances, writing some `List(Boolean)`
+/// columns as bit arrays.
+///
+/// # Arguments
+/// - `df` (&DataFrame): The input `DataFrame`.
+/// - `bit_columns` (&[String]): The names of the `List(Boolean)` columns to write as `X` bit arrays
+///   instead of `L` logical arrays.
+///
+/// # Returns
+/// - `Result<Vec<Column>, FitsError>`: A vector of `Column` instances, see `polars_to_columns`.
+///
+/// # Behavior
+/// - A bit array column with rows of `n` booleans becomes `nX`, packed in `ceil(n / 8)` bytes;
+///   ragged bit arrays become `1PX(max)`.
+pub fn polars_to_columns_with_bit_arrays(df: &DataFrame, bit_columns: &[String]) -> Result<Vec<Column>, FitsError> {
     let mut start_address = 0;
-    let mut sum_to_address = 0;
-    
-    let columns : Vec<Column> = df.get_columns().iter().map(|series| {
+    let mut heap_size = 0;
+    let mut columns : Vec<Column> = Vec::new();
+
+    for series in df.get_columns() {
         let ttype = series.name();
+        let mut tzero = None;
         let tform = match series.dtype() {
-            DataType::Boolean => {
-                sum_to_address = byte_value_from_str("L");
-                "L".to_string()
-            },
-            DataType::UInt8 => {
-                sum_to_address = byte_value_from_str("X");
-                "X".to_string()
-            },
+            DataType::Boolean => "L".to_string(),
+            DataType::UInt8 => "B".to_string(),
             DataType::Int8 => {
-                sum_to_address = byte_value_from_str("B");
+                tzero = Some(SIGNED_BYTE_ZERO);
                 "B".to_string()
             },
-            DataType::Int16 => {
-                sum_to_address = byte_value_from_str("I");
+            DataType::Int16 => "I".to_string(),
+            DataType::Int32 => "J".to_string(),
+            DataType::Int64 => "K".to_string(),
+            DataType::UInt16 => {
+                tzero = Some(UNSIGNED_ZERO_16);
                 "I".to_string()
             },
-            DataType::Int32 => {
-                sum_to_address = byte_value_from_str("J");
+            DataType::UInt32 => {
+                tzero = Some(UNSIGNED_ZERO_32);
                 "J".to_string()
             },
-            DataType::Int64 => {
-                sum_to_address = byte_value_from_str("K");
+            DataType::UInt64 => {
+                tzero = Some(UNSIGNED_ZERO_64);
                 "K".to_string()
             },
-            DataType::Float32 => {
-                sum_to_address = byte_value_from_str("E");
-                "E".to_string()
-            },
-            DataType::Float64 => {
-                sum_to_address = byte_value_from_str("D");
-                "D".to_string()
+            DataType::Float32 => "E".to_string(),
+            DataType::Float64 => "D".to_string(),
+            DataType::Struct(_) => match complex_letter(series.dtype()) {
+                Some(letter) => letter.to_string(),
+                None => return Err(FitsError::UnsupportedDataType(format!("{} in column {}", series.dtype(), ttype))),
             },
             DataType::String => {
                 let data = &series.str().unwrap();
-                let mut max_length = data.iter().map(|item| item.unwrap_or("").len()).max().unwrap();
+                let mut max_length = data.iter().map(|item| item.unwrap_or("").len()).max().unwrap_or(0);
 
-                //Max length should be even number
-                if max_length % 2 != 0 {
-                    max_length += 1_usize;
+                if max_length > MAX_FIXED_STRING_LENGTH {
+                    let nbytes = data.iter().map(|item| item.unwrap_or("").len()).sum();
+                    var_len_tform("A", max_length, nbytes, &mut heap_size)
+                } else {
+                    //Max length should be even number
+                    if max_length % 2 != 0 {
+                        max_length += 1_usize;
+                    }
+                    format!("{}A", max_length)
                 }
-                sum_to_address = max_length;
-                format!("{}A", max_length)
             },
             DataType::List(dtype ) => {
-                // Get list shape 
-                let list_shape = series.list().unwrap().get(0).unwrap().len();
-                
-                match dtype.to_physical() {
-                    DataType::Float64 => {
-                        sum_to_address = list_shape * byte_value_from_str("D");
-                        format!("{}D", list_shape)
-                    },
-                    DataType::Float32 => {
-                        sum_to_address = list_shape * byte_value_from_str("E");
-                        format!("{}E", list_shape)
+                let letter = match complex_letter(dtype) {
+                    Some(letter) => letter,
+                    None => match dtype.to_physical() {
+                    DataType::Float64 => "D",
+                    DataType::Float32 => "E",
+                    DataType::Int64 => "K",
+                    DataType::Int32 => "J",
+                    DataType::Int16 => "I",
+                    DataType::UInt64 => {
+                        tzero = Some(UNSIGNED_ZERO_64);
+                        "K"
                     },
-                    DataType::Int64 => {
-                        sum_to_address = list_shape * byte_value_from_str("K");
-                        format!("{}K", list_shape)
-                    },
-                    DataType::Int32 => {
-                        sum_to_address = list_shape * byte_value_from_str("J");
-                        format!("{}J", list_shape)
+                    DataType::UInt32 => {
+                        tzero = Some(UNSIGNED_ZERO_32);
+                        "J"
                     },
-                    DataType::Int16 => {
-                        sum_to_address = list_shape * byte_value_from_str("I");
-                        format!("{}I", list_shape)
+                    DataType::UInt16 => {
+                        tzero = Some(UNSIGNED_ZERO_16);
+                        "I"
                     },
                     DataType::Int8 => {
-                        sum_to_address = list_shape * byte_value_from_str("B");
-                        format!("{}B", list_shape)
-                    },
-                    DataType::UInt8 => {
-                        sum_to_address = list_shape * byte_value_from_str("X");
-                        format!("{}X", list_shape)
+                        tzero = Some(SIGNED_BYTE_ZERO);
+                        "B"
                     },
-                    DataType::Boolean => {
-                        sum_to_address = list_shape * byte_value_from_str("L");
-                        format!("{}L", list_shape)
-                    },
-                    _ => {
-                        panic!("Unsupported data type for array column");
+                    DataType::UInt8 => "B",
+                    DataType::Boolean if bit_columns.iter().any(|name| name == ttype) => "X",
+                    DataType::Boolean => "L",
+                    other => {
+                        return Err(FitsError::UnsupportedDataType(format!("List({}) in column {}", other, ttype)));
                     }
-                }
+                    },
+                };
 
+                // Get list shape 
+                // Null rows keep the column fixed-width, they are written as arrays of null elements.
+                let lengths: Vec<Option<usize>> = series.list().unwrap().into_iter().map(|s| s.map(|s| s.len())).collect();
+                let list_shape = lengths.iter().flatten().next().copied();
+                if let Some(list_shape) = list_shape.filter(|&shape| lengths.iter().flatten().all(|&length| length == shape)) {
+                    format!("{}{}", list_shape, letter)
+                } else {
+                    let max_count = lengths.iter().flatten().copied().max().unwrap_or(0);
+                    let nbytes = lengths.iter().flatten().map(|&length| var_len_array_bytes(letter, length)).sum::<Result<usize, FitsError>>()?;
+                    var_len_tform(letter, max_count, nbytes, &mut heap_size)
+                }
             },
-            _ => {
-                panic!("Unsupported data type");
+            other => {
+                return Err(FitsError::UnsupportedDataType(format!("{} in column {}", other, ttype)));
             }
         };
         
-        let column: Column = Column::new(
+        let mut column: Column = Column::new(
             ttype.to_string(), 
             tform, 
             None, 
             None, 
             start_address
-        );
+        )?;
+        column.tzero = tzero;
+        column.tnull = choose_tnull(series, !matches!(column.type_letter.as_str(), "P" | "Q"))?;
 
-        start_address += sum_to_address;
-        column
-    }).collect();
+        start_address += column.type_bytes;
+        columns.push(column);
+    }
 
     Ok(columns)
 }
 
+/// Returns the `TFORM` letter of complex values stored as a struct of real and imaginary parts:
+/// `C` for `Float32` parts, `M` for `Float64` parts, `None` for other types.
+fn complex_letter(dtype: &DataType) -> Option<&'static str> {
+    match dtype {
+        DataType::Struct(fields) if fields.len() == 2 => match (fields[0].data_type(), fields[1].data_type()) {
+            (DataType::Float32, DataType::Float32) => Some("C"),
+            (DataType::Float64, DataType::Float64) => Some("M"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the real and imaginary parts of a complex series, as `f64` with NaN for null values.
+///
+/// # Returns
+/// - `Result<(Vec<f64>, Vec<f64>), FitsError>`: The parts, or `FitsError::UnsupportedDataType` if the
+///   series is not a struct of two `Float32` or two `Float64` fields.
+fn complex_parts(series: &Series) -> Result<(Vec<f64>, Vec<f64>), FitsError> {
+    let unsupported = || FitsError::UnsupportedDataType(format!("{} in complex column {}", series.dtype(), series.name()));
+    if complex_letter(series.dtype()).is_none() {
+        return Err(unsupported());
+    }
+    let fields = series.struct_().map_err(|_| unsupported())?.fields();
+    let part = |field: &Series| -> Result<Vec<f64>, FitsError> {
+        let values = field.cast(&DataType::Float64).map_err(|_| unsupported())?;
+        Ok(values.f64().map_err(|_| unsupported())?.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect())
+    };
+    Ok((part(&fields[0])?, part(&fields[1])?))
+}
+
+/// Writes a complex value big-endian, as two `f32` (8 bytes) for `C` columns or two `f64`
+/// (16 bytes) for `M` columns.
+fn write_complex(field: &mut [u8], letter: &str, re: f64, im: f64) {
+    if letter == "M" {
+        field[0..8].copy_from_slice(&re.to_be_bytes());
+        field[8..16].copy_from_slice(&im.to_be_bytes());
+    } else {
+        field[0..4].copy_from_slice(&(re as f32).to_be_bytes());
+        field[4..8].copy_from_slice(&(im as f32).to_be_bytes());
+    }
+}
+
+/// Reports a series that could not be read as the type of its column.
+fn column_error(column: &Column, err: PolarsError) -> FitsError {
+    FitsError::UnsupportedDataType(format!("column {} ({})", column.ttype, err))
+}
+
+/// Checks that the values of a series fit in the fixed-width field of its column.
+///
+/// # Arguments
+/// - `column` (&Column): The column the series is written to.
+/// - `series` (&Series): The values of the column.
+///
+/// # Returns
+/// - `Result<(), FitsError>`: `FitsError::UnsupportedDataType` if the series has no binary table
+///   representation, or `FitsError::InvalidValue` if its values are not the width of the column.
+///
+/// # Behavior
+/// - Scalars must be exactly the width of the field, strings and arrays at most that width, arrays
+///   of `X` columns counting one bit per element.
+fn check_column_width(column: &Column, series: &Series) -> Result<(), FitsError> {
+    let unsupported = |dtype: &DataType| FitsError::UnsupportedDataType(format!("{} in column {}", dtype, column.ttype));
+    let width = |dtype: &DataType| -> Result<usize, FitsError> {
+        match dtype {
+            DataType::Boolean | DataType::UInt8 | DataType::Int8 => Ok(1),
+            DataType::Int16 | DataType::UInt16 => Ok(2),
+            DataType::Int32 | DataType::UInt32 | DataType::Float32 => Ok(4),
+            DataType::Int64 | DataType::UInt64 | DataType::Float64 => Ok(8),
+            DataType::Struct(_) => match complex_letter(dtype) {
+                Some("C") => Ok(8),
+                Some(_) => Ok(16),
+                None => Err(unsupported(dtype)),
+            },
+            other => Err(unsupported(other)),
+        }
+    };
+
+    let (size, exact) = match series.dtype() {
+        DataType::String => (series.str().map_err(|e| column_error(column, e))?
+            .into_iter().map(|item| item.map_or(0, str::len)).max().unwrap_or(0), false),
+        DataType::List(dtype) => {
+            let dtype = dtype.to_physical();
+            let count = series.list().map_err(|e| column_error(column, e))?
+                .into_iter().map(|item| item.map_or(0, |array| array.len())).max().unwrap_or(0);
+            if column.type_letter == "X" && dtype == DataType::Boolean {
+                (count.div_ceil(8), false)
+            } else {
+                (count * width(&dtype)?, false)
+            }
+        },
+        dtype => (width(dtype)?, true),
+    };
+    if size > column.type_bytes || (exact && size != column.type_bytes) {
+        return Err(FitsError::InvalidValue(format!(
+            "{} bytes of {} in the {} bytes of column {}", size, series.dtype(), column.type_bytes, column.ttype
+        )));
+    }
+    Ok(())
+}
+
+/// Replaces the null rows of a fixed-width `List` column by arrays of `repeat` null elements,
+/// which are written as `TNULLn`, NaN or undefined logicals.
+fn fill_null_rows(series: &Series, repeat: usize) -> Result<Series, FitsError> {
+    let DataType::List(dtype) = series.dtype() else {
+        return Ok(series.clone());
+    };
+    let nulls = Series::full_null("", repeat, dtype);
+    let filled: ListChunked = series.list().map_err(|e| FitsError::InvalidValue(e.to_string()))?
+        .into_iter()
+        .map(|array| Some(array.unwrap_or_else(|| nulls.clone())))
+        .collect();
+    filled.with_name(series.name()).cast(series.dtype()).map_err(|e| FitsError::InvalidValue(e.to_string()))
+}
+
+/// Reads the rows of a fixed-width `List` column whose elements are all null back as null rows.
+///
+/// Complex elements are null when both their parts are NaN. Bit arrays have no null elements.
+fn mask_null_rows(series: Series) -> Result<Series, FitsError> {
+    let DataType::List(dtype) = series.dtype() else {
+        return Ok(series);
+    };
+    let is_complex = complex_letter(dtype).is_some();
+    let masked: ListChunked = series.list().map_err(|e| FitsError::InvalidValue(e.to_string()))?
+        .into_iter()
+        .map(|array| array.filter(|array| {
+            let all_null = if is_complex {
+                complex_parts(array).is_ok_and(|(re, im)| re.iter().chain(&im).all(|v| v.is_nan()))
+            } else {
+                array.null_count() == array.len()
+            };
+            array.is_empty() || !all_null
+        }))
+        .collect();
+    masked.with_name(series.name()).cast(series.dtype()).map_err(|e| FitsError::InvalidValue(e.to_string()))
+}
+
+/// Returns the `TFORM` of a variable-length column and adds its arrays to the size of the heap.
+fn var_len_tform(letter: &str, max_count: usize, nbytes: usize, heap_size: &mut usize) -> String {
+    let descriptor = if *heap_size + nbytes > i32::MAX as usize { "Q" } else { "P" };
+    *heap_size += nbytes;
+    format!("1{}{}({})", descriptor, letter, max_count)
+}
+
+/// The heap of a binary table being written, with the array descriptors of its rows.
+///
+/// # Fields
+/// - `bytes` (Vec<u8>): The arrays of the variable-length columns, column after column.
+/// - `descriptors` (Vec<Vec<(usize, usize)>>): For each column, the element count and heap offset
+///   of the array of each row, empty for fixed-width columns.
+pub struct TableHeap {
+    pub bytes: Vec<u8>,
+    descriptors: Vec<Vec<(usize, usize)>>,
+}
+
+impl TableHeap {
+    /// Lays out the arrays of the variable-length columns of a `DataFrame` in a heap.
+    ///
+    /// # Arguments
+    /// - `columns` (&[Column]): The columns of the table, as returned by `polars_to_columns`.
+    /// - `df` (&DataFrame): The `DataFrame` containing the table's data.
+    ///
+    /// # Returns
+    /// - `Result<TableHeap, FitsError>`: The heap, or `FitsError::UnsupportedDataType` for a
+    ///   variable-length column that is neither a `List` nor a `String` column.
+    ///
+    /// # Behavior
+    /// - Null rows are written as empty arrays.
+    pub fn from_df(columns: &[Column], df: &DataFrame) -> Result<Self, FitsError> {
+        let mut bytes = Vec::new();
+        let mut descriptors = Vec::with_capacity(columns.len());
+        for column in columns {
+            if (column.type_letter != "P") & (column.type_letter != "Q") {
+                descriptors.push(Vec::new());
+                continue;
+            }
+
+            let series = df.column(&column.ttype)
+                .map_err(|_| FitsError::InvalidValue(format!("column {} is not in the DataFrame", column.ttype)))?;
+            let mut column_descriptors = Vec::with_capacity(series.len());
+            match series.dtype() {
+                DataType::String => {
+                    for item in series.str().unwrap() {
+                        let string = item.unwrap_or("");
+                        column_descriptors.push((string.len(), bytes.len()));
+                        bytes.extend_from_slice(string.as_bytes());
+                    }
+                },
+                DataType::List(_) => {
+                    let letter = get_var_len_type(&column.tform)?;
+                    let null = column.tnull.unwrap_or(0);
+                    for item in series.list().unwrap() {
+                        let offset = bytes.len();
+                        let count = match item {
+                            Some(array) => append_heap_array(&array, letter, null, &mut bytes)?,
+                            None => 0,
+                        };
+                        column_descriptors.push((count, offset));
+                    }
+                },
+                other => {
+                    return Err(FitsError::UnsupportedDataType(format!("{} in variable-length column {}", other, column.ttype)));
+                }
+            }
+            descriptors.push(column_descriptors);
+        }
+        Ok(Self { bytes, descriptors })
+    }
+
+    /// Returns the size of the heap in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Checks if the heap holds no array.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+/// Appends the big-endian elements of the array of one row to the heap.
+///
+/// # Arguments
+/// - `array` (&Series): The array of the row.
+/// - `letter` (&str): The type letter of the elements, after `P` or `Q` in the `TFORM`.
+/// - `null` (i64): The stored value of null integers (`TNULLn`), NaN being used for null floats.
+///
+/// # Returns
+/// - `Result<usize, FitsError>`: The number of elements of the array, in bits for `X` arrays.
+fn append_heap_array(array: &Series, letter: &str, null: i64, bytes: &mut Vec<u8>) -> Result<usize, FitsError> {
+    let array = array.to_physical_repr();
+    match array.dtype() {
+        DataType::Boolean if letter == "X" => {
+            let start = bytes.len();
+            bytes.resize(start + array.len().div_ceil(8), 0);
+            for (k, item) in array.bool().unwrap().into_iter().enumerate() {
+                if item == Some(true) {
+                    bytes[start + k / 8] |= 0x80 >> (k % 8);
+                }
+            }
+        },
+        DataType::Boolean => bytes.extend(array.bool().unwrap().into_iter().map(logical_to_byte)),
+        DataType::UInt8 => bytes.extend(array.u8().unwrap().into_iter().map(|item| item.unwrap_or(null as u8))),
+        DataType::Int8 => bytes.extend(array.i8().unwrap().into_iter().map(|item| item.map_or(null as u8, signed_byte_to_stored))),
+        DataType::Int16 => bytes.extend(array.i16().unwrap().into_iter().flat_map(|item| item.unwrap_or(null as i16).to_be_bytes())),
+        DataType::Int32 => bytes.extend(array.i32().unwrap().into_iter().flat_map(|item| item.unwrap_or(null as i32).to_be_bytes())),
+        DataType::Int64 => bytes.extend(array.i64().unwrap().into_iter().flat_map(|item| item.unwrap_or(null).to_be_bytes())),
+        DataType::UInt16 => bytes.extend(array.u16().unwrap().into_iter().flat_map(|item| item.map_or(null as u16, |v| v ^ 0x8000).to_be_bytes())),
+        DataType::UInt32 => bytes.extend(array.u32().unwrap().into_iter().flat_map(|item| item.map_or(null as u32, |v| v ^ 0x8000_0000).to_be_bytes())),
+        DataType::UInt64 => bytes.extend(array.u64().unwrap().into_iter().flat_map(|item| item.map_or(null as u64, |v| v ^ (1 << 63)).to_be_bytes())),
+        DataType::Float32 => bytes.extend(array.f32().unwrap().into_iter().flat_map(|item| item.unwrap_or(f32::NAN).to_be_bytes())),
+        DataType::Float64 => bytes.extend(array.f64().unwrap().into_iter().flat_map(|item| item.unwrap_or(f64::NAN).to_be_bytes())),
+        DataType::Struct(_) => {
+            let (letter, size) = match complex_letter(array.dtype()) {
+                Some("C") => ("C", 8),
+                Some(_) => ("M", 16),
+                None => return Err(FitsError::UnsupportedDataType(format!("List({}) in a variable-length column", array.dtype()))),
+            };
+            let (re, im) = complex_parts(&array)?;
+            for (re, im) in re.into_iter().zip(im) {
+                let start = bytes.len();
+                bytes.resize(start + size, 0);
+                write_complex(&mut bytes[start..], letter, re, im);
+            }
+        },
+        other => return Err(FitsError::UnsupportedDataType(format!("List({}) in a variable-length column", other))),
+    }
+    Ok(array.len())
+}
+
+/// The offset (`TZEROn`) of `Int8` values stored as unsigned `B` bytes.
+const SIGNED_BYTE_ZERO: f64 = -128.0;
+/// The offsets (`TZEROn`) of `UInt16`, `UInt32` and `UInt64` values stored as signed `I`, `J`
+/// and `K` integers.
+const UNSIGNED_ZERO_16: f64 = 32768.0;
+const UNSIGNED_ZERO_32: f64 = 2147483648.0;
+const UNSIGNED_ZERO_64: f64 = 9223372036854775808.0;
+
+/// Stores a logical value as `T` or `F`, undefined values as a null byte.
+fn logical_to_byte(value: Option<bool>) -> u8 {
+    match value {
+        Some(true) => b'T',
+        Some(false) => b'F',
+        None => 0,
+    }
+}
+
+/// Chooses the stored value marking the null integers of a column (`TNULLn`).
+///
+/// # Arguments
+/// - `series` (&Series): The integer column, or the `List` column of its arrays.
+/// - `null_rows` (bool): Whether null rows of a `List` column are written as null elements, as in
+///   fixed-width columns. Variable-length columns write them as empty arrays instead.
+///
+/// # Returns
+/// - `Result<Option<i64>, FitsError>`: `None` if the column has no null value or is not an integer
+///   column, otherwise the smallest stored value not taken by the column. `FitsError::InvalidValue`
+///   if all the values are taken.
+fn choose_tnull(series: &Series, null_rows: bool) -> Result<Option<i64>, FitsError> {
+    let (values, has_nulls) = match series.dtype() {
+        DataType::List(_) => {
+            let arrays = series.list().unwrap();
+            let values = arrays.explode()
+                .map_err(|e| FitsError::InvalidValue(format!("column {} could not be read ({})", series.name(), e)))?;
+            let has_nulls = arrays.into_iter().any(|array| array.map_or(null_rows, |array| array.null_count() > 0));
+            (values, has_nulls)
+        },
+        _ => (series.clone(), series.null_count() > 0),
+    };
+    if !has_nulls {
+        return Ok(None);
+    }
+
+    let values = values.drop_nulls();
+    let (mut stored, min, max): (Vec<i64>, i64, i64) = match values.dtype() {
+        DataType::UInt8 => (values.u8().unwrap().into_no_null_iter().map(|v| v as i64).collect(), 0, 255),
+        DataType::Int8 => (values.i8().unwrap().into_no_null_iter().map(|v| signed_byte_to_stored(v) as i64).collect(), 0, 255),
+        DataType::Int16 => (values.i16().unwrap().into_no_null_iter().map(|v| v as i64).collect(), i16::MIN as i64, i16::MAX as i64),
+        DataType::UInt16 => (values.u16().unwrap().into_no_null_iter().map(|v| (v ^ 0x8000) as i16 as i64).collect(), i16::MIN as i64, i16::MAX as i64),
+        DataType::Int32 => (values.i32().unwrap().into_no_null_iter().map(|v| v as i64).collect(), i32::MIN as i64, i32::MAX as i64),
+        DataType::UInt32 => (values.u32().unwrap().into_no_null_iter().map(|v| (v ^ 0x8000_0000) as i32 as i64).collect(), i32::MIN as i64, i32::MAX as i64),
+        DataType::Int64 => (values.i64().unwrap().into_no_null_iter().collect(), i64::MIN, i64::MAX),
+        DataType::UInt64 => (values.u64().unwrap().into_no_null_iter().map(|v| (v ^ (1 << 63)) as i64).collect(), i64::MIN, i64::MAX),
+        _ => return Ok(None),
+    };
+    stored.sort_unstable();
+    stored.dedup();
+    let mut candidate = min;
+    for value in stored {
+        if value > candidate {
+            break;
+        }
+        if candidate == max {
+            return Err(FitsError::InvalidValue(format!("column {} takes all the values of its type, none is left for nulls", series.name())));
+        }
+        candidate += 1;
+    }
+    Ok(Some(candidate))
+}
+
+/// Stores a signed byte as the unsigned `B` byte `value + 128`.
+fn signed_byte_to_stored(value: i8) -> u8 {
+    (value as u8) ^ 0x80
+}
+
+/// Replaces the undefined values of a decoded column by nulls.
+///
+/// # Arguments
+/// - `series` (Series): The decoded column, or the `List` column of its arrays.
+/// - `tnull` (Option<i64>): The stored value of undefined integers (`TNULLn`), if any.
+///
+/// # Returns
+/// - `Result<Series, FitsError>`: The column with nulls for the integers equal to `tnull` and for
+///   the NaN floats.
+fn mask_nulls(series: Series, tnull: Option<i64>) -> Result<Series, FitsError> {
+    let name = series.name().to_string();
+    let masked = match (series.dtype(), tnull) {
+        (DataType::List(_), _) => {
+            let masked = series.list().unwrap()
+                .apply_to_inner(&|inner| mask_nulls(inner, tnull).map_err(|e| polars_err!(ComputeError: "{}", e)))
+                .map_err(|e| FitsError::InvalidValue(format!("column {} could not be read ({})", name, e)))?;
+            return Ok(masked.into_series());
+        },
+        (DataType::UInt8, Some(tnull)) => series.u8().unwrap().into_iter().map(|item| item.filter(|&v| v as i64 != tnull)).collect::<UInt8Chunked>().into_series(),
+        (DataType::Int16, Some(tnull)) => series.i16().unwrap().into_iter().map(|item| item.filter(|&v| v as i64 != tnull)).collect::<Int16Chunked>().into_series(),
+        (DataType::Int32, Some(tnull)) => series.i32().unwrap().into_iter().map(|item| item.filter(|&v| v as i64 != tnull)).collect::<Int32Chunked>().into_series(),
+        (DataType::Int64, Some(tnull)) => series.i64().unwrap().into_iter().map(|item| item.filter(|&v| v != tnull)).collect::<Int64Chunked>().into_series(),
+        (DataType::Float32, _) if series.f32().unwrap().into_iter().any(|item| item.is_some_and(f32::is_nan)) => {
+            series.f32().unwrap().into_iter().map(|item| item.filter(|v| !v.is_nan())).collect::<Float32Chunked>().into_series()
+        },
+        (DataType::Float64, _) if series.f64().unwrap().into_iter().any(|item| item.is_some_and(f64::is_nan)) => {
+            series.f64().unwrap().into_iter().map(|item| item.filter(|v| !v.is_nan())).collect::<Float64Chunked>().into_series()
+        },
+        _ => return Ok(series),
+    };
+    Ok(masked.with_name(&name))
+}
+
+/// Converts the stored values of a column to their physical values, `TSCALn * stored + TZEROn`.
+///
+/// # Arguments
+/// - `series` (Series): The decoded column, or the `List` column of its arrays.
+/// - `tscal` (f64): The scale of the column, 1 if `TSCALn` is missing.
+/// - `tzero` (f64): The offset of the column, 0 if `TZEROn` is missing.
+///
+/// # Returns
+/// - `Result<Series, FitsError>`: The physical values. `B` columns with `TZERO = -128` become `Int8`,
+///   `I`, `J` and `K` columns with `TZERO` = 2^15, 2^31 and 2^63 become `UInt16`, `UInt32` and
+///   `UInt64`, and other scaled numeric columns become `Float64`.
+///
+/// # Behavior
+/// - Logical, bit, string and complex columns are left as they are.
+fn scale_series(series: Series, tscal: f64, tzero: f64) -> Result<Series, FitsError> {
+    if tscal == 1.0 && tzero == 0.0 {
+        return Ok(series);
+    }
+    let polars_err = |e: PolarsError| FitsError::InvalidValue(format!("column {} could not be scaled ({})", series.name(), e));
+    let unsigned = tscal == 1.0;
+    let scaled = match series.dtype() {
+        DataType::List(_) => {
+            let scaled = series.list().unwrap()
+                .apply_to_inner(&|inner| scale_series(inner, tscal, tzero).map_err(|e| polars_err!(ComputeError: "{}", e)))
+                .map_err(polars_err)?;
+            return Ok(scaled.into_series());
+        },
+        DataType::UInt8 if unsigned && tzero == SIGNED_BYTE_ZERO => {
+            series.u8().unwrap().into_iter().map(|item| item.map(|v| (v ^ 0x80) as i8)).collect::<Int8Chunked>().into_series()
+        },
+        DataType::Int16 if unsigned && tzero == UNSIGNED_ZERO_16 => {
+            series.i16().unwrap().into_iter().map(|item| item.map(|v| (v as u16) ^ 0x8000)).collect::<UInt16Chunked>().into_series()
+        },
+        DataType::Int32 if unsigned && tzero == UNSIGNED_ZERO_32 => {
+            series.i32().unwrap().into_iter().map(|item| item.map(|v| (v as u32) ^ 0x8000_0000)).collect::<UInt32Chunked>().into_series()
+        },
+        DataType::Int64 if unsigned && tzero == UNSIGNED_ZERO_64 => {
+            series.i64().unwrap().into_iter().map(|item| item.map(|v| (v as u64) ^ (1 << 63))).collect::<UInt64Chunked>().into_series()
+        },
+        DataType::UInt8 | DataType::Int16 | DataType::Int32 | DataType::Int64 | DataType::Float32 | DataType::Float64 => {
+            let values = series.cast(&DataType::Float64).map_err(polars_err)?;
+            values.f64().unwrap().apply_values(|v| v * tscal + tzero).into_series()
+        },
+        _ => return Ok(series),
+    };
+    Ok(scaled.with_name(series.name()))
+}
+
 /// Calculates the total number of bytes required to store one row of the binary table.
 ///
 /// # Arguments
-/// - `columns` (&Vec<Column>): The vector of `Column` instances representing the table's structure.
+/// - `columns` (&[Column]): The `Column` instances representing the table's structure.
 ///
 /// # Returns
 /// - `usize`: The total number of bytes per row.
-pub fn calculate_number_of_bytes_of_row(columns: &Vec<Column>) -> usize {
-    let mut bytes = 0;
-    for column in columns.iter() {
-        let size = get_data_bytes_size(&column.tform);
-        bytes += size;
-    }
-    bytes
+pub fn calculate_number_of_bytes_of_row(columns: &[Column]) -> usize {
+    columns.iter().map(|column| column.type_bytes).sum()
 }
 
 /// Creates or updates the FITS header with metadata for a binary table.
 ///
 /// # Arguments
 /// - `header` (&mut Header): The FITS header to update.
-/// - `columns` (&Vec<Column>): The vector of `Column` instances representing the table's structure.
+/// - `columns` (&[Column]): The `Column` instances representing the table's structure.
 /// - `nrows` (i64): The number of rows in the table.
 ///
 /// # Behavior
 /// - Adds mandatory header keywords like `XTENSION`, `NAXIS1`, and `NAXIS2`.
-/// - Populates column-specific keywords (`TTYPE`, `TFORM`, `TUNIT`, etc.) for each column.
-pub fn create_table_on_header(header: &mut Header, columns: &Vec<Column>, nrows: i64) {
+/// - Populates column-specific keywords (`TTYPE`, `TFORM`, `TUNIT`, `TSCAL`, `TZERO`, `TNULL`, etc.) for each column.
+pub fn create_table_on_header(header: &mut Header, columns: &[Column], nrows: i64) {
     clear_table_on_header(header);
     let tfields = columns.len();
     let num_bytes = calculate_number_of_bytes_of_row(columns);
@@ -371,7 +1163,38 @@ pub fn create_table_on_header(header: &mut Header, columns: &Vec<Column>, nrows:
         if let Some(tdisp) = &column.tdisp {
             header.add_card(&Card::new(format!("TDISP{}", i + 1), tdisp.clone(), Some("Display format of field".to_string())));
         }
+        if let Some(tscal) = column.tscal {
+            header.add_card(&Card::new(format!("TSCAL{}", i + 1), tscal.to_string(), Some("Scale of field".to_string())));
+        }
+        if let Some(tzero) = column.tzero {
+            header.add_card(&Card::new(format!("TZERO{}", i + 1), tzero.to_string(), Some("Offset of field".to_string())));
+        }
+        if let Some(tnull) = column.tnull {
+            header.add_card(&Card::new(format!("TNULL{}", i + 1), tnull.to_string(), Some("Undefined value of field".to_string())));
+        }
+    }
+}
+
+/// Sets the size and position of the heap in the header of a binary table.
+///
+/// # Arguments
+/// - `header` (&mut Header): The FITS header, already filled by `create_table_on_header`.
+/// - `columns` (&[Column]): The `Column` instances representing the table's structure.
+/// - `nrows` (i64): The number of rows in the table.
+/// - `heap` (&TableHeap): The heap of the table.
+///
+/// # Behavior
+/// - Without variable-length columns the header is left as it is, with `PCOUNT = 0`.
+/// - Otherwise `PCOUNT` is set to the size of the heap and `THEAP` to the size of the rows.
+pub fn create_heap_on_header(header: &mut Header, columns: &[Column], nrows: i64, heap: &TableHeap) {
+    if !columns.iter().any(|c| (c.type_letter == "P") | (c.type_letter == "Q")) {
+        return;
     }
+    let theap = calculate_number_of_bytes_of_row(columns) * nrows as usize;
+    header.remove("PCOUNT");
+    header.remove("THEAP");
+    header.add_card_after(&Card::new("PCOUNT".to_string(), heap.len().to_string(), Some("Size of the heap".to_string())), "NAXIS2");
+    header.add_card_after(&Card::new("THEAP".to_string(), theap.to_string(), Some("Byte offset of the heap".to_string())), "TFIELDS");
 }
 
 /// Converts a Polars `DataFrame` into a binary buffer and writes it to a file.
@@ -379,16 +1202,36 @@ pub fn create_table_on_header(header: &mut Header, columns: &Vec<Column>, nrows:
 /// # Arguments
 /// - `columns` (Vec<Column>): The vector of `Column` instances representing the table's structure.
 /// - `df` (&DataFrame): The `DataFrame` containing the table's data.
-/// - `file` (&mut File): The file to which the binary buffer is written.
+/// - `file` (&mut W): The file or writer to which the binary buffer is written.
+///
+/// # Returns
+/// - `Result<(), FitsError>`: Returns `Ok(())` on success or an I/O error.
+///
+/// # Behavior
+/// - Lays out the heap of the variable-length columns with `TableHeap::from_df` and writes the
+///   rows followed by the heap, see `df_to_buffer_with_heap`.
+pub fn df_to_buffer<W: Write + ?Sized>(columns: Vec<Column>, df: &DataFrame, file: &mut W) -> Result<(), FitsError> {
+    let heap = TableHeap::from_df(&columns, df)?;
+    df_to_buffer_with_heap(columns, df, &heap, file)
+}
+
+/// Converts a Polars `DataFrame` into a binary buffer and writes it to a file, followed by its heap.
+///
+/// # Arguments
+/// - `columns` (Vec<Column>): The vector of `Column` instances representing the table's structure.
+/// - `df` (&DataFrame): The `DataFrame` containing the table's data.
+/// - `heap` (&TableHeap): The heap of the table, whose size is written to the header as `PCOUNT`.
+/// - `file` (&mut W): The file or writer to which the binary buffer is written.
 ///
 /// # Returns
-/// - `Result<(), std::io::Error>`: Returns `Ok(())` on success or an I/O error.
+/// - `Result<(), FitsError>`: Returns `Ok(())` on success or an I/O error.
 ///
 /// # Behavior
 /// - Iterates over rows and columns of the `DataFrame`, converting data into binary format.
 /// - Supports parallel processing for efficiency.
-/// - Ensures the final buffer size is padded to the nearest FITS block.
-pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Result<(), std::io::Error> {
+/// - Variable-length columns are written as descriptors pointing into the heap.
+/// - Ensures the final buffer size, rows and heap, is padded to the nearest FITS block.
+pub fn df_to_buffer_with_heap<W: Write + ?Sized>(columns: Vec<Column>, df: &DataFrame, heap: &TableHeap, file: &mut W) -> Result<(), FitsError> {
     let nrows = df.height();
     let bytes_per_row = calculate_number_of_bytes_of_row(&columns);
     
@@ -403,24 +1246,41 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
     }
 
     let limits = split_buffer(nrows, n_chunks, 1);
-    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads as usize).build().unwrap();
-    let bufs : Vec<Vec<u8>> = pool.install(|| {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads as usize).build()?;
+    let bufs = pool.install(|| {
         limits.into_par_iter().map(|(start, end)| {
             let nbuffer_rows: usize = end - start;
             let local_df = &df.slice(start as i64, nbuffer_rows);
             let mut local_buffer : Vec<u8> = vec![0x00; nbuffer_rows * bytes_per_row];
 
-            columns.iter().for_each(|column| {
-                let series = &local_df.column(&column.ttype).unwrap();
+            for (c, column) in columns.iter().enumerate() {
+                if (column.type_letter == "P") | (column.type_letter == "Q") {
+                    heap.descriptors[c][start..end].iter().enumerate().for_each(|(j, &(count, offset))| {
+                        let col_start_add = j * bytes_per_row + column.start_address;
+                        write_descriptor(&mut local_buffer[col_start_add..col_start_add + column.type_bytes], count, offset);
+                    });
+                    continue;
+                }
+                let series = local_df.column(&column.ttype)
+                    .map_err(|_| FitsError::InvalidValue(format!("column {} is not in the DataFrame", column.ttype)))?;
+                let filled;
+                let series = if matches!(series.dtype(), DataType::List(_)) && series.null_count() > 0 {
+                    filled = fill_null_rows(series, get_repeat_count(&column.tform)?)?;
+                    &filled
+                } else {
+                    series
+                };
+                check_column_width(column, series)?;
+                let null = column.tnull.unwrap_or(0);
 
                 match series.dtype() {
                     DataType::Boolean => {
                         series.bool()
-                            .expect("series was not an bool dtype")
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let bytes = item.unwrap() as u8;
+                                let bytes = logical_to_byte(item);
                                 let row_start_add = j * bytes_per_row;
                                 let col_start_add = row_start_add + column.start_address;
                                 local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&[bytes]);
@@ -428,11 +1288,11 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
                     },
                     DataType::UInt8 => {
                         series.u8()
-                            .expect("series was not an u8 dtype")
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let bytes = item.unwrap();
+                                let bytes = item.unwrap_or(null as u8);
                                 let row_start_add = j * bytes_per_row;
                                 let col_start_add = row_start_add + column.start_address;
                                 local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&[bytes]);
@@ -440,23 +1300,23 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
                     },
                     DataType::Int8 => {
                         series.i8()
-                            .expect("series was not an i8 dtype")
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let bytes = item.unwrap();
+                                let bytes = item.map_or(null as u8, signed_byte_to_stored);
                                 let row_start_add = j * bytes_per_row;
                                 let col_start_add = row_start_add + column.start_address;
-                                local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
+                                local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&[bytes]);
                             });
                     },
                     DataType::Int16 => {
                         series.i16()
-                            .expect("series was not an i16 dtype")
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let bytes = item.unwrap();
+                                let bytes = item.unwrap_or(null as i16);
                                 let row_start_add = j * bytes_per_row;
                                 let col_start_add = row_start_add + column.start_address;
                                 local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
@@ -464,11 +1324,11 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
                     },
                     DataType::Int32 => {
                         series.i32()
-                            .expect("series was not an i32 dtype")
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let bytes = item.unwrap();
+                                let bytes = item.unwrap_or(null as i32);
                                 let row_start_add = j * bytes_per_row;
                                 let col_start_add = row_start_add + column.start_address;
                                 local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
@@ -476,11 +1336,47 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
                     },
                     DataType::Int64 => {
                         series.i64()
-                            .expect("series was not an i64 dtype")
+                            .map_err(|e| column_error(column, e))?
+                            .into_iter()
+                            .enumerate()
+                            .for_each(|(j, item)| {
+                                let bytes = item.unwrap_or(null);
+                                let row_start_add = j * bytes_per_row;
+                                let col_start_add = row_start_add + column.start_address;
+                                local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
+                            });
+                    },
+                    DataType::UInt16 => {
+                        series.u16()
+                            .map_err(|e| column_error(column, e))?
+                            .into_iter()
+                            .enumerate()
+                            .for_each(|(j, item)| {
+                                let bytes = item.map_or(null as u16, |item| item ^ 0x8000);
+                                let row_start_add = j * bytes_per_row;
+                                let col_start_add = row_start_add + column.start_address;
+                                local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
+                            });
+                    },
+                    DataType::UInt32 => {
+                        series.u32()
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let bytes = item.unwrap();
+                                let bytes = item.map_or(null as u32, |item| item ^ 0x8000_0000);
+                                let row_start_add = j * bytes_per_row;
+                                let col_start_add = row_start_add + column.start_address;
+                                local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
+                            });
+                    },
+                    DataType::UInt64 => {
+                        series.u64()
+                            .map_err(|e| column_error(column, e))?
+                            .into_iter()
+                            .enumerate()
+                            .for_each(|(j, item)| {
+                                let bytes = item.map_or(null as u64, |item| item ^ (1 << 63));
                                 let row_start_add = j * bytes_per_row;
                                 let col_start_add = row_start_add + column.start_address;
                                 local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
@@ -488,11 +1384,11 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
                     },
                     DataType::Float32 => {
                         series.f32()
-                            .expect("series was not an f32 dtype")
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let bytes = item.unwrap();
+                                let bytes = item.unwrap_or(f32::NAN);
                                 let row_start_add = j * bytes_per_row;
                                 let col_start_add = row_start_add + column.start_address;
                                 local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
@@ -500,172 +1396,284 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
                     },
                     DataType::Float64 => {
                         series.f64()
-                            .expect("series was not an f64 dtype")
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let bytes = item.unwrap();
+                                let bytes = item.unwrap_or(f64::NAN);
                                 let row_start_add = j * bytes_per_row;
                                 let col_start_add = row_start_add + column.start_address;
                                 local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&bytes.to_be_bytes());
                             });
                     },
+                    DataType::Struct(_) => {
+                        let (re, im) = complex_parts(series)?;
+                        re.into_iter().zip(im).enumerate().for_each(|(j, (re, im))| {
+                            let col_start_add = j * bytes_per_row + column.start_address;
+                            write_complex(&mut local_buffer[col_start_add..col_start_add + column.type_bytes], &column.type_letter, re, im);
+                        });
+                    },
                     DataType::List(dtype) => {
                         match dtype.to_physical() {
                             DataType::Boolean => {
                                 series.list()
-                                    .expect("series was not an list dtype")
+                                    .map_err(|e| column_error(column, e))?
                                     .into_iter()
                                     .enumerate()
                                     .for_each(|(j, item)| {
-                                        let array = item.unwrap();
+                                        let Some(array) = item else {
+                                            return;
+                                        };
                                         let row_start_add = j * bytes_per_row;
                                         let col_start_add = row_start_add + column.start_address;
                                         
                                         array.iter().enumerate().for_each(|(k, it )| {
                                             if let AnyValue::Boolean(item) = it {
-                                                let bytes = item as u8;
-                                                local_buffer[col_start_add + k..col_start_add + k + 1].copy_from_slice(&[bytes]);
+                                                if column.type_letter == "X" {
+                                                    // Bits are packed eight to a byte, most significant bit first.
+                                                    if item {
+                                                        local_buffer[col_start_add + k / 8] |= 0x80 >> (k % 8);
+                                                    }
+                                                } else {
+                                                    local_buffer[col_start_add + k] = logical_to_byte(Some(item));
+                                                }
                                             }
                                         });
                                     });
                             },
                             DataType::UInt8 => {
                                 series.list()
-                                    .expect("series was not an list dtype")
+                                    .map_err(|e| column_error(column, e))?
                                     .into_iter()
                                     .enumerate()
                                     .for_each(|(j, item)| {
-                                        let array = item.unwrap();
+                                        let Some(array) = item else {
+                                            return;
+                                        };
                                         let row_start_add = j * bytes_per_row;
                                         let col_start_add = row_start_add + column.start_address;
                                         
                                         array.iter().enumerate().for_each(|(k, it )| {
-                                            if let AnyValue::UInt8(item) = it {
-                                                let bytes = item;
-                                                local_buffer[col_start_add + k..col_start_add + k + 1].copy_from_slice(&[bytes]);
-                                            }
+                                            let bytes = match it {
+                                                AnyValue::UInt8(item) => item,
+                                                _ => null as u8,
+                                            };
+                                            local_buffer[col_start_add + k..col_start_add + k + 1].copy_from_slice(&[bytes]);
                                         });
                                     });
                             },
                             DataType::Int8 => {
                                 series.list()
-                                    .expect("series was not an list dtype")
+                                    .map_err(|e| column_error(column, e))?
                                     .into_iter()
                                     .enumerate()
                                     .for_each(|(j, item)| {
-                                        let array = item.unwrap();
+                                        let Some(array) = item else {
+                                            return;
+                                        };
                                         let row_start_add = j * bytes_per_row;
                                         let col_start_add = row_start_add + column.start_address;
                                         
                                         array.iter().enumerate().for_each(|(k, it )| {
-                                            if let AnyValue::Int8(item) = it {
-                                                let bytes = item;
-                                                local_buffer[col_start_add + k..col_start_add + k + 1].copy_from_slice(&bytes.to_be_bytes());
-                                            }
+                                            local_buffer[col_start_add + k] = match it {
+                                                AnyValue::Int8(item) => signed_byte_to_stored(item),
+                                                _ => null as u8,
+                                            };
                                         });
                                     });
                             },
                             DataType::Int16 => {
                                 series.list()
-                                    .expect("series was not an list dtype")
+                                    .map_err(|e| column_error(column, e))?
                                     .into_iter()
                                     .enumerate()
                                     .for_each(|(j, item)| {
-                                        let array = item.unwrap();
+                                        let Some(array) = item else {
+                                            return;
+                                        };
                                         let row_start_add = j * bytes_per_row;
                                         let col_start_add = row_start_add + column.start_address;
                                         
                                         array.iter().enumerate().for_each(|(k, it )| {
-                                            if let AnyValue::Int16(item) = it {
-                                                let bytes = item;
-                                                local_buffer[col_start_add + k * 2..col_start_add + k * 2 + 2].copy_from_slice(&bytes.to_be_bytes());
-                                            }
+                                            let bytes = match it {
+                                                AnyValue::Int16(item) => item,
+                                                _ => null as i16,
+                                            };
+                                            local_buffer[col_start_add + k * 2..col_start_add + k * 2 + 2].copy_from_slice(&bytes.to_be_bytes());
                                         });
                                     });
                             },
                             DataType::Int32 => {
                                 series.list()
-                                    .expect("series was not an list dtype")
+                                    .map_err(|e| column_error(column, e))?
                                     .into_iter()
                                     .enumerate()
                                     .for_each(|(j, item)| {
-                                        let array = item.unwrap();
+                                        let Some(array) = item else {
+                                            return;
+                                        };
                                         let row_start_add = j * bytes_per_row;
                                         let col_start_add = row_start_add + column.start_address;
                                         
                                         array.iter().enumerate().for_each(|(k, it )| {
-                                            if let AnyValue::Int32(item) = it {
-                                                let bytes = item;
-                                                local_buffer[col_start_add + k * 4..col_start_add + k * 4 + 4].copy_from_slice(&bytes.to_be_bytes());
-                                            }
+                                            let bytes = match it {
+                                                AnyValue::Int32(item) => item,
+                                                _ => null as i32,
+                                            };
+                                            local_buffer[col_start_add + k * 4..col_start_add + k * 4 + 4].copy_from_slice(&bytes.to_be_bytes());
                                         });
                                     });
                             },
                             DataType::Int64 => {
                                 series.list()
-                                    .expect("series was not an list dtype")
+                                    .map_err(|e| column_error(column, e))?
                                     .into_iter()
                                     .enumerate()
                                     .for_each(|(j, item)| {
-                                        let array = item.unwrap();
+                                        let Some(array) = item else {
+                                            return;
+                                        };
                                         let row_start_add = j * bytes_per_row;
                                         let col_start_add = row_start_add + column.start_address;
                                         
                                         array.iter().enumerate().for_each(|(k, it )| {
-                                            if let AnyValue::Int64(item) = it {
-                                                let bytes = item;
-                                                local_buffer[col_start_add + k * 8..col_start_add + k * 8 + 8].copy_from_slice(&bytes.to_be_bytes());
-                                            }
+                                            let bytes = match it {
+                                                AnyValue::Int64(item) => item,
+                                                _ => null,
+                                            };
+                                            local_buffer[col_start_add + k * 8..col_start_add + k * 8 + 8].copy_from_slice(&bytes.to_be_bytes());
+                                        });
+                                    });
+                            },
+                            DataType::UInt16 => {
+                                series.list()
+                                    .map_err(|e| column_error(column, e))?
+                                    .into_iter()
+                                    .enumerate()
+                                    .for_each(|(j, item)| {
+                                        let Some(array) = item else {
+                                            return;
+                                        };
+                                        let row_start_add = j * bytes_per_row;
+                                        let col_start_add = row_start_add + column.start_address;
+                                        
+                                        array.iter().enumerate().for_each(|(k, it )| {
+                                            let bytes = match it {
+                                                AnyValue::UInt16(item) => item ^ 0x8000,
+                                                _ => null as u16,
+                                            };
+                                            local_buffer[col_start_add + k * 2..col_start_add + k * 2 + 2].copy_from_slice(&bytes.to_be_bytes());
+                                        });
+                                    });
+                            },
+                            DataType::UInt32 => {
+                                series.list()
+                                    .map_err(|e| column_error(column, e))?
+                                    .into_iter()
+                                    .enumerate()
+                                    .for_each(|(j, item)| {
+                                        let Some(array) = item else {
+                                            return;
+                                        };
+                                        let row_start_add = j * bytes_per_row;
+                                        let col_start_add = row_start_add + column.start_address;
+                                        
+                                        array.iter().enumerate().for_each(|(k, it )| {
+                                            let bytes = match it {
+                                                AnyValue::UInt32(item) => item ^ 0x8000_0000,
+                                                _ => null as u32,
+                                            };
+                                            local_buffer[col_start_add + k * 4..col_start_add + k * 4 + 4].copy_from_slice(&bytes.to_be_bytes());
+                                        });
+                                    });
+                            },
+                            DataType::UInt64 => {
+                                series.list()
+                                    .map_err(|e| column_error(column, e))?
+                                    .into_iter()
+                                    .enumerate()
+                                    .for_each(|(j, item)| {
+                                        let Some(array) = item else {
+                                            return;
+                                        };
+                                        let row_start_add = j * bytes_per_row;
+                                        let col_start_add = row_start_add + column.start_address;
+                                        
+                                        array.iter().enumerate().for_each(|(k, it )| {
+                                            let bytes = match it {
+                                                AnyValue::UInt64(item) => item ^ (1 << 63),
+                                                _ => null as u64,
+                                            };
+                                            local_buffer[col_start_add + k * 8..col_start_add + k * 8 + 8].copy_from_slice(&bytes.to_be_bytes());
                                         });
                                     });
                             },
                             DataType::Float32 => {
                                 series.list()
-                                    .expect("series was not an list dtype")
+                                    .map_err(|e| column_error(column, e))?
                                     .into_iter()
                                     .enumerate()
                                     .for_each(|(j, item)| {
-                                        let array = item.unwrap();
+                                        let Some(array) = item else {
+                                            return;
+                                        };
                                         let row_start_add = j * bytes_per_row;
                                         let col_start_add = row_start_add + column.start_address;
                                         
                                         array.iter().enumerate().for_each(|(k, it )| {
-                                            if let AnyValue::Float32(item) = it {
-                                                let bytes = item;
-                                                local_buffer[col_start_add + k * 4..col_start_add + k * 4 + 4].copy_from_slice(&bytes.to_be_bytes());
-                                            }
+                                            let bytes = match it {
+                                                AnyValue::Float32(item) => item,
+                                                _ => f32::NAN,
+                                            };
+                                            local_buffer[col_start_add + k * 4..col_start_add + k * 4 + 4].copy_from_slice(&bytes.to_be_bytes());
                                         });
                                     });
                             },
                             DataType::Float64 => {
                                 series.list()
-                                    .expect("series was not an list dtype")
+                                    .map_err(|e| column_error(column, e))?
                                     .into_iter()
                                     .enumerate()
                                     .for_each(|(j, item)| {
-                                        let array = item.unwrap();
+                                        let Some(array) = item else {
+                                            return;
+                                        };
                                         let row_start_add = j * bytes_per_row;
                                         let col_start_add = row_start_add + column.start_address;
                                         
                                         array.iter().enumerate().for_each(|(k, it )| {
-                                            if let AnyValue::Float64(item) = it {
-                                                let bytes = item;
-                                                local_buffer[col_start_add + k * 8..col_start_add + k * 8 + 8].copy_from_slice(&bytes.to_be_bytes());
-                                            }
+                                            let bytes = match it {
+                                                AnyValue::Float64(item) => item,
+                                                _ => f64::NAN,
+                                            };
+                                            local_buffer[col_start_add + k * 8..col_start_add + k * 8 + 8].copy_from_slice(&bytes.to_be_bytes());
                                         });
                                     });
                             },
-                            _ => panic!("Unsupported data type for array column"),
+                            DataType::Struct(_) => {
+                                let size = if column.type_letter == "M" { 16 } else { 8 };
+                                let arrays = series.list().map_err(|e| column_error(column, e))?;
+                                for (j, item) in arrays.into_iter().enumerate() {
+                                    let Some(array) = item else {
+                                        continue;
+                                    };
+                                    let (re, im) = complex_parts(&array)?;
+                                    let col_start_add = j * bytes_per_row + column.start_address;
+                                    re.into_iter().zip(im).enumerate().for_each(|(k, (re, im))| {
+                                        write_complex(&mut local_buffer[col_start_add + k * size..col_start_add + (k + 1) * size], &column.type_letter, re, im);
+                                    });
+                                }
+                            },
+                            other => return Err(FitsError::UnsupportedDataType(format!("List({}) in column {}", other, column.ttype))),
                         }
                     }
                     DataType::String => {
                         series.str()
-                            .expect("series was not an string dtype")
+                            .map_err(|e| column_error(column, e))?
                             .into_iter()
                             .enumerate()
                             .for_each(|(j, item)| {
-                                let string = item.unwrap();
+                                let string = item.unwrap_or("");
                                 let mut string = string.to_string();
                                 string.push_str(&" ".repeat(column.type_bytes - string.len()));
                                 let row_start_add = j * bytes_per_row;
@@ -673,38 +1681,20 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
                                 local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&string.into_bytes());
                             });
                     },
-                    _ => {
-                        series.str()
-                            .expect("series was not an string dtype")
-                            .into_iter()
-                            .enumerate()
-                            .for_each(|(j, item)| {
-                                let string = item.unwrap();
-                                let mut string = string.to_string();
-                                string.push_str(&" ".repeat(column.type_bytes - string.len()));
-                                let row_start_add = j * bytes_per_row;
-                                let col_start_add = row_start_add + column.start_address;
-                                local_buffer[col_start_add..col_start_add + column.type_bytes].copy_from_slice(&string.into_bytes());
-                            });
-                    }
-                    
+                    other => return Err(FitsError::UnsupportedDataType(format!("{} in column {}", other, column.ttype))),
                 }
-                
-            });
-            
-            
-            local_buffer.to_owned()
-        }).collect()
-    });
+            }
+            Ok(local_buffer)
+        }).collect::<Result<Vec<Vec<u8>>, FitsError>>()
+    })?;
 
     let mut bytes_written = 0;
-    bufs.into_iter().for_each(|buffer| {
-        bytes_written += file.write(&buffer).unwrap();
-    });
-    
-    if bytes_written < nrows * bytes_per_row {
-        panic!("Error writing to file");
+    for buffer in bufs {
+        file.write_all(&buffer)?;
+        bytes_written += buffer.len();
     }
+    file.write_all(&heap.bytes)?;
+    bytes_written += heap.len();
 
     pad_buffer_to_fits_block(file, bytes_written)?;
     Ok(())
@@ -1,8 +1,9 @@
-use std::fs::File;
-use std::io::Result;
+use std::io::{Read, Seek, Write};
 
 use polars::frame::DataFrame;
+use crate::io::errors::FitsError;
 use crate::io::hdus::bintable::bintable::*;
+use crate::io::hdus::bintable::{get_first_letter, get_var_len_type};
 
 use crate::io::header::card::Card;
 use crate::io::Header;
@@ -60,45 +61,129 @@ impl BinTableHDU {
     /// Reads a Binary Table HDU from a FITS file.
     ///
     /// # Arguments
-    /// - `f` (&mut File): The file from which to read the HDU.
+    /// - `f` (&mut R): The file or reader from which to read the HDU.
     ///
     /// # Returns
-    /// - `Result<BinTableHDU>`: The `BinTableHDU` containing the header and data or an I/O error.
+    /// - `Result<BinTableHDU, FitsError>`: The `BinTableHDU` containing the header and data, or a `FitsError`.
     ///
     /// # Behavior
-    /// - Reads the header to extract metadata.
+    /// - Reads the header and checks that the mandatory keywords come first.
     /// - Reads and parses the binary table data into a Polars `DataFrame`.
     /// - Extracts column metadata from the header for data interpretation.
-    pub fn read_from_file(f: &mut File) -> Result<Self>  {
-        //TODO: Check for mandatory words
+    pub fn read_from_file<R: Read + Seek + ?Sized>(f: &mut R) -> Result<Self, FitsError>  {
         let mut header = Header::new();
         header.read_from_file(f)?;
-        let mut columns = read_tableinfo_from_header(&header).unwrap();
-        let df = read_table_bytes_to_df(&mut columns, &header, f);
-        Ok(Self::new(header, df?))
+        if !header.are_mandatory_keywords_first(&MANDATORY_KEYWORDS) {
+            return Err(FitsError::InvalidHeader(
+                format!("binary table header must start with {}", MANDATORY_KEYWORDS.join(", "))
+            ));
+        }
+        let mut columns = read_tableinfo_from_header(&header)?;
+        let df = read_table_bytes_to_df(&mut columns, &header, f)?;
+        Ok(Self::new(header, df))
+    }
+
+    /// Reads some columns and rows of a Binary Table HDU.
+    ///
+    /// # Arguments
+    /// - `f` (&mut R): The file or reader, positioned at the start of the HDU.
+    /// - `projection` (&TableProjection): The columns to decode and the rows to read.
+    ///
+    /// # Returns
+    /// - `Result<BinTableHDU, FitsError>`: The HDU, whose `DataFrame` holds the selected columns
+    ///   and rows. The header still describes the whole table, and is rebuilt from the data on write.
+    ///
+    /// # Behavior
+    /// - Seeks directly to the first requested row and only decodes the selected columns.
+    /// - Returns `FitsError::InvalidValue` for an unknown column or a row range past the end.
+    /// - The cursor is moved to the end of the HDU.
+    pub fn read_projected<R: Read + Seek + ?Sized>(f: &mut R, projection: &TableProjection) -> Result<Self, FitsError> {
+        let mut batches = Self::read_batches_projected(f, usize::MAX, projection)?;
+        let df = match batches.next() {
+            Some(df) => df?,
+            None => batches.empty_frame()?,
+        };
+        Ok(Self::new(batches.header().clone(), df))
+    }
+
+    /// Reads the header of a Binary Table HDU and iterates over its rows in batches.
+    ///
+    /// # Arguments
+    /// - `f` (&mut R): The file or reader, positioned at the start of the HDU.
+    /// - `batch_rows` (usize): The maximum number of rows per `DataFrame` batch.
+    ///
+    /// # Returns
+    /// - `Result<TableBatches<R>, FitsError>`: An iterator of `Result<DataFrame, FitsError>` batches.
+    ///
+    /// # Behavior
+    /// - Only one batch of rows is held in memory at a time, so tables larger than memory can be
+    ///   processed.
+    /// - The rows are decoded as in `read_from_file`. Once the iterator is exhausted, the cursor
+    ///   is at the end of the HDU.
+    pub fn read_batches<R: Read + Seek + ?Sized>(f: &mut R, batch_rows: usize) -> Result<TableBatches<'_, R>, FitsError> {
+        Self::read_batches_projected(f, batch_rows, &TableProjection::default())
+    }
+
+    /// Iterates over some columns and rows of a Binary Table HDU in batches.
+    ///
+    /// # Arguments
+    /// - `f` (&mut R): The file or reader, positioned at the start of the HDU.
+    /// - `batch_rows` (usize): The maximum number of rows per `DataFrame` batch.
+    /// - `projection` (&TableProjection): The columns to decode and the rows to read.
+    ///
+    /// # Returns
+    /// - `Result<TableBatches<R>, FitsError>`: An iterator of `Result<DataFrame, FitsError>` batches.
+    pub fn read_batches_projected<'a, R: Read + Seek + ?Sized>(f: &'a mut R, batch_rows: usize, projection: &TableProjection) -> Result<TableBatches<'a, R>, FitsError> {
+        let mut header = Header::new();
+        header.read_from_file(f)?;
+        if !header.are_mandatory_keywords_first(&MANDATORY_KEYWORDS) {
+            return Err(FitsError::InvalidHeader(
+                format!("binary table header must start with {}", MANDATORY_KEYWORDS.join(", "))
+            ));
+        }
+        TableBatches::with_projection(f, header, batch_rows, projection)
+    }
+
+    /// Returns the names of the columns declared as bit arrays (`X`, `PX` or `QX`) in the header.
+    fn bit_columns(&self) -> Vec<String> {
+        let tfields = self.header.get_int("TFIELDS").unwrap_or(0);
+        (1..=tfields).filter_map(|i| {
+            let tform = self.header.get_string(&format!("TFORM{}", i)).ok()?;
+            let letter = get_var_len_type(&tform).unwrap_or_else(|_| get_first_letter(&tform));
+            if letter == "X" {
+                self.header.get_string(&format!("TTYPE{}", i)).ok()
+            } else {
+                None
+            }
+        }).collect()
     }
 
     /// Writes the Binary Table HDU to a FITS file.
     ///
     /// # Arguments
-    /// - `f` (&mut File): The file to which the HDU will be written.
+    /// - `f` (&mut W): The file or writer to which the HDU will be written.
     ///
     /// # Returns
-    /// - `Result<()>`: Returns `Ok(())` on success or an I/O error.
+    /// - `Result<(), FitsError>`: Returns `Ok(())` on success or a `FitsError`.
     ///
     /// # Behavior
     /// - Ensures the header contains mandatory keywords in the correct order.
     /// - Converts the Polars `DataFrame` into a binary buffer and writes it to the file.
     /// - Updates the header with column metadata and writes it to the file.
-    pub fn write_to_file(&mut self, mut f: &mut File) -> Result<()> {
+    /// - Ragged `List` and long `String` columns are written to the heap, with `PCOUNT` and `THEAP`.
+    /// - `List(Boolean)` columns whose `TFORMn` in the header is a bit array (`X`, `PX` or `QX`), such as
+    ///   columns read from a file, are written back as bit arrays.
+    pub fn write_to_file<W: Write + ?Sized>(&mut self, mut f: &mut W) -> Result<(), FitsError> {
         //TODO: This function should not repeat here and in primary hdu
         self.header.fix_header_w_mandatory_order(&MANDATORY_KEYWORDS);
-        let columns = polars_to_columns(&self.data).unwrap();
+        let columns = polars_to_columns_with_bit_arrays(&self.data, &self.bit_columns())?;
+        let heap = TableHeap::from_df(&columns, &self.data)?;
         create_table_on_header(&mut self.header, &columns, self.data.height() as i64);
+        create_heap_on_header(&mut self.header, &columns, self.data.height() as i64, &heap);
         
         self.header.fix_header_w_mandatory_order(&MANDATORY_KEYWORDS);
         self.header.write_to_buffer(&mut f)?;
-        df_to_buffer(columns, &self.data, f)?;
+        df_to_buffer_with_heap(columns, &self.data, &heap, f)?;
         Ok(())
     }
 }
\ No newline at end of file
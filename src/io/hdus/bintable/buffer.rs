@@ -1,38 +1,37 @@
-use rayon::prelude::*;
-use polars::{prelude::NamedFrom, series::Series};
+use polars::prelude::{DataType, Field, IntoSeries, NamedFrom, StructChunked};
+use polars::series::Series;
 
+use crate::io::errors::FitsError;
 use crate::io::hdus::bintable::*;
 
 
 /// Represents a data buffer for a column in a FITS binary table.
 ///
 /// # Variants
-/// - `L(Vec<bool>)`: Logical data (boolean values).
-/// - `X(Vec<u8>)`: Bit data.
-/// - `B(Vec<i8>)`: Byte data (8-bit signed integers).
+/// - `L(Vec<Option<bool>>)`: Logical data (boolean values, `None` for undefined values).
+/// - `B(Vec<u8>)`: Byte data (8-bit unsigned integers).
 /// - `I(Vec<i16>)`: Short data (16-bit signed integers).
 /// - `J(Vec<i32>)`: Integer data (32-bit signed integers).
 /// - `K(Vec<i64>)`: Long data (64-bit signed integers).
 /// - `A(Vec<String>)`: Character data (ASCII strings).
 /// - `E(Vec<f32>)`: Float data (32-bit floating-point values).
 /// - `D(Vec<f64>)`: Double data (64-bit floating-point values).
-/// - `C(Vec<String>)`: Complex data (stored as strings for simplicity).
-/// - `M(Vec<String>)`: Double complex data (stored as strings for simplicity).
+/// - `C(Vec<(f32, f32)>)`: Complex data (real and imaginary 32-bit floating-point parts).
+/// - `M(Vec<(f64, f64)>)`: Double complex data (real and imaginary 64-bit floating-point parts).
 /// - `P(Vec<Vec<i32>>)` and `Q(Vec<Vec<i64>>)`:
 ///   - Array descriptors for variable-length columns.
 #[derive(Debug, PartialEq)]
 pub enum ColumnDataBuffer {
-    L(Vec<bool>), // Logical
-    X(Vec<u8>), // Bit
-    B(Vec<i8>), // Byte
+    L(Vec<Option<bool>>), // Logical
+    B(Vec<u8>), // Byte
     I(Vec<i16>), // Short
     J(Vec<i32>), // Int
     K(Vec<i64>), // Long
     A(Vec<String>), // Char
     E(Vec<f32>), // Float
     D(Vec<f64>), // Double
-    C(Vec<String>), // Complex
-    M(Vec<String>), // Double complex
+    C(Vec<(f32, f32)>), // Complex
+    M(Vec<(f64, f64)>), // Double complex
     P(Vec<Vec<i32>>), // Array descriptor
     Q(Vec<Vec<i64>>), // Array descriptor
 }
@@ -40,24 +39,28 @@ pub enum ColumnDataBuffer {
 /// Represents an array buffer for columns with vector data in a FITS binary table.
 ///
 /// # Variants
-/// - `L(Vec<Vec<bool>>)`
-/// - `X(Vec<Vec<u8>>)`
-/// - `B(Vec<Vec<i8>>)`
+/// - `L(Vec<Vec<Option<bool>>>)`
+/// - `X(Vec<Vec<bool>>)`: Bit arrays, one `bool` per bit.
+/// - `B(Vec<Vec<u8>>)`
 /// - `I(Vec<Vec<i16>>)`
 /// - `J(Vec<Vec<i32>>)`
 /// - `K(Vec<Vec<i64>>)`
 /// - `E(Vec<Vec<f32>>)`
 /// - `D(Vec<Vec<f64>>)`
+/// - `C(Vec<Vec<(f32, f32)>>)`
+/// - `M(Vec<Vec<(f64, f64)>>)`
 #[derive(Debug, PartialEq)]
 pub enum ColumnArrayBuffer {
-    L(Vec<Vec<bool>>), // Logical
-    X(Vec<Vec<u8>>), // Bit
-    B(Vec<Vec<i8>>), // Byte
+    L(Vec<Vec<Option<bool>>>), // Logical
+    X(Vec<Vec<bool>>), // Bit
+    B(Vec<Vec<u8>>), // Byte
     I(Vec<Vec<i16>>), // Short
     J(Vec<Vec<i32>>), // Int
     K(Vec<Vec<i64>>), // Long
     E(Vec<Vec<f32>>), // Float
     D(Vec<Vec<f64>>), // Double
+    C(Vec<Vec<(f32, f32)>>), // Complex
+    M(Vec<Vec<(f64, f64)>>), // Double complex
 }
 
 enum BufferTypes
@@ -69,17 +72,15 @@ enum BufferTypes
 /// Represents a generalized buffer for a FITS binary table column.
 ///
 /// # Fields
-/// - `tform` (String): The format string of the column (e.g., "J", "D").
-/// - `size` (i32): The number of rows in the column.
 /// - `buffer` (BufferTypes): The actual data buffer (scalar or vector).
 /// - `sub_size` (i32): The size of sub-elements in case of vector columns.
 /// - `data_letter` (String): The first letter of the `TFORM` format string, indicating the data type.
+/// - `array_letter` (String): The element type of variable-length array columns, empty otherwise.
 pub struct Buffer {
-    tform : String,
-    size : i32,
     buffer : BufferTypes,
     sub_size : i32,
     data_letter : String,
+    array_letter : String,
 }
 
 impl Buffer {
@@ -90,38 +91,45 @@ impl Buffer {
     /// - `size` (i32): The number of rows in the column.
     ///
     /// # Returns
-    /// - `Buffer`: A new buffer initialized based on the column's format and size.
+    /// - `Result<Buffer, FitsError>`: A new buffer initialized based on the column's format and size,
+    ///   or `FitsError::InvalidTform` if the format string cannot be parsed.
     ///
     /// # Behavior
     /// - Initializes the buffer as either scalar or vector, depending on the format string.
     /// - Calculates the `sub_size` for vector columns.
-    pub fn new(tform : &str, size : i32) -> Self {
-        let tform = tform.to_string();
-        let data_letter = get_first_letter(&tform).to_string();
+    pub fn new(tform : &str, size : i32) -> Result<Self, FitsError> {
+        let data_letter = get_first_letter(tform).to_string();
+        let data_bytes = get_data_bytes_size(tform)?;
+        let type_bytes = byte_value_from_str(tform)?;
 
+        // Bit arrays are always vectors, of one `bool` per bit.
         let mut sub_size = 1;
         let mut vec_column = false;
-        if (get_data_bytes_size(&tform) !=  byte_value_from_str(&tform))
-            & (data_letter != "A") & (data_letter != "C") & (data_letter != "M")  & (data_letter != "P") & (data_letter != "Q")
+        if (data_letter == "X") | ((data_bytes != type_bytes)
+            & (data_letter != "A") & (data_letter != "P") & (data_letter != "Q"))
         {
             vec_column = true;
-            sub_size = (get_data_bytes_size(&tform)/byte_value_from_str(&tform)) as i32;
+            sub_size = get_repeat_count(tform)? as i32;
         }
 
-        let buffer : BufferTypes;
-        if vec_column {
-            buffer = BufferTypes::Vector(ColumnArrayBuffer::new(&tform, size, sub_size));
+        let array_letter = if (data_letter == "P") | (data_letter == "Q") {
+            get_var_len_type(tform)?.to_string()
+        } else {
+            String::new()
+        };
+
+        let buffer = if vec_column {
+            BufferTypes::Vector(ColumnArrayBuffer::new(tform, size, sub_size)?)
         }else{
-            buffer = BufferTypes::Scalar(ColumnDataBuffer::new(&tform, size));
-        }
+            BufferTypes::Scalar(ColumnDataBuffer::new(tform, size))
+        };
 
-        Buffer{
-            tform,
-            size,
+        Ok(Buffer{
             buffer,
             sub_size,
-            data_letter
-        }
+            data_letter,
+            array_letter,
+        })
     }
 
     /// Converts the buffer into a Polars `Series` for data analysis.
@@ -130,8 +138,8 @@ impl Buffer {
     /// - `col_name` (&str): The name of the column.
     ///
     /// # Returns
-    /// - `Series`: A Polars `Series` containing the column's data.
-    pub fn to_series(&self, col_name : &str) -> Series {
+    /// - `Result<Series, FitsError>`: A Polars `Series` containing the column's data.
+    pub fn to_series(&self, col_name : &str) -> Result<Series, FitsError> {
         match &self.buffer {
             BufferTypes::Scalar(data) => data.to_series(col_name),
             BufferTypes::Vector(data) => data.to_series(col_name),
@@ -151,32 +159,139 @@ impl Buffer {
     /// # Arguments
     /// - `bytes` (&[u8]): The raw bytes to write.
     /// - `idx` (i64): The row index where the data should be written.
-    pub fn write_on_idx(&mut self, bytes : &[u8], idx : i64){
+    ///
+    /// # Returns
+    /// - `Result<(), FitsError>`: `FitsError::InvalidValue` if the format does not match the buffer.
+    pub fn write_on_idx(&mut self, bytes : &[u8], idx : i64) -> Result<(), FitsError> {
         match &mut self.buffer {
             BufferTypes::Scalar(data) => data.write_on_idx(bytes, &self.data_letter, idx),
             BufferTypes::Vector(data) => data.write_on_idx(bytes, &self.data_letter, idx, self.sub_size),
         }
     }
 
-    /// Reads variable-length column data (e.g., `P` and `Q` type columns).
+    /// Resolves the array descriptors of a variable-length column (`P` and `Q`) against the heap.
+    ///
+    /// # Arguments
+    /// - `col_name` (&str): The name of the column.
+    /// - `heap` (&[u8]): The bytes of the heap, or of the part of it holding the arrays of the rows.
+    /// - `heap_offset` (usize): The offset in the heap of the first byte of `heap`.
+    ///
+    /// # Returns
+    /// - `Result<Series, FitsError>`: A `String` series for character arrays (`PA`), a `List` series
+    ///   of the element type otherwise. `FitsError::InvalidValue` if an array is not within `heap`.
     ///
     /// # Behavior
-    /// - Processes the buffer to extract variable-length data, if applicable.
-    pub fn read_var_len_cols(&mut self){
-        //TODO
-        
-        match &mut self.buffer {
-            BufferTypes::Scalar(data) => {
-                println!("buffer: {:?}", data);
-            },
-            BufferTypes::Vector(data) => {
-                
-            },
+    /// - Columns that are not variable-length arrays are converted with `to_series`.
+    pub fn read_var_len_cols(&self, col_name : &str, heap : &[u8], heap_offset : usize) -> Result<Series, FitsError> {
+        let descriptors: Vec<(usize, usize)> = match &self.buffer {
+            BufferTypes::Scalar(ColumnDataBuffer::P(data)) => data.iter().map(|d| (d[0] as u32 as usize, d[1] as u32 as usize)).collect(),
+            BufferTypes::Scalar(ColumnDataBuffer::Q(data)) => data.iter().map(|d| (d[0] as u64 as usize, d[1] as u64 as usize)).collect(),
+            _ => return self.to_series(col_name),
+        };
+
+        let letter = self.array_letter.as_str();
+        let arrays = descriptors.iter().map(|&(count, offset)| {
+            if count == 0 {
+                return Ok(&heap[..0]);
+            }
+            let nbytes = var_len_array_bytes(letter, count)?;
+            offset.checked_sub(heap_offset)
+                .and_then(|start| heap.get(start..start.checked_add(nbytes)?))
+                .ok_or_else(|| FitsError::InvalidValue(format!(
+                    "array of {} bytes at heap offset {} of column {} is outside the heap", nbytes, offset, col_name
+                )))
+        }).collect::<Result<Vec<&[u8]>, FitsError>>()?;
+
+        if letter == "A" {
+            let strings: Vec<String> = arrays.iter()
+                .map(|bytes| String::from_utf8_lossy(bytes).trim_end_matches(['\0', ' ']).to_string())
+                .collect();
+            return Ok(Series::new(col_name, strings));
         }
 
+        let dtype = match letter {
+            "L" => DataType::Boolean,
+            "X" => DataType::Boolean,
+            "B" => DataType::UInt8,
+            "I" => DataType::Int16,
+            "J" => DataType::Int32,
+            "K" => DataType::Int64,
+            "E" => DataType::Float32,
+            "D" => DataType::Float64,
+            "C" => complex_dtype(DataType::Float32),
+            "M" => complex_dtype(DataType::Float64),
+            _ => return Err(FitsError::UnsupportedDataType(format!("variable-length arrays of type {}", letter))),
+        };
+        let rows: Vec<Series> = arrays.iter().zip(&descriptors)
+            .map(|(bytes, &(count, _))| heap_array_to_series(letter, bytes, count))
+            .collect::<Result<_, FitsError>>()?;
+        Series::new(col_name, rows)
+            .cast(&DataType::List(Box::new(dtype)))
+            .map_err(|e| FitsError::InvalidValue(format!("column {} could not be built ({})", col_name, e)))
+    }
+}
+
+/// Decodes a logical value: `T` (or 1) is true, a null byte is undefined and any other byte is false.
+fn logical_from_byte(byte : u8) -> Option<bool> {
+    match byte {
+        b'T' | 1 => Some(true),
+        0 => None,
+        _ => Some(false),
     }
+}
+
+/// Decodes the `count` big-endian elements of a variable-length array read from the heap.
+fn heap_array_to_series(letter : &str, bytes : &[u8], count : usize) -> Result<Series, FitsError> {
+    let series = match letter {
+        "L" => Series::new("", bytes.iter().map(|&b| logical_from_byte(b)).collect::<Vec<Option<bool>>>()),
+        "X" => Series::new("", (0..count).map(|i| bytes[i / 8] & (0x80 >> (i % 8)) != 0).collect::<Vec<bool>>()),
+        "B" => Series::new("", bytes),
+        "I" => Series::new("", bytes.chunks_exact(2).map(|b| i16::from_be_bytes([b[0], b[1]])).collect::<Vec<i16>>()),
+        "J" => Series::new("", bytes.chunks_exact(4).map(|b| i32::from_be_bytes(b.try_into().unwrap())).collect::<Vec<i32>>()),
+        "K" => Series::new("", bytes.chunks_exact(8).map(|b| i64::from_be_bytes(b.try_into().unwrap())).collect::<Vec<i64>>()),
+        "E" => Series::new("", bytes.chunks_exact(4).map(|b| f32::from_be_bytes(b.try_into().unwrap())).collect::<Vec<f32>>()),
+        "D" => Series::new("", bytes.chunks_exact(8).map(|b| f64::from_be_bytes(b.try_into().unwrap())).collect::<Vec<f64>>()),
+        "C" => complex_to_series("", &bytes.chunks_exact(8).map(read_complex_f32).collect::<Vec<_>>())?,
+        "M" => complex_to_series("", &bytes.chunks_exact(16).map(read_complex_f64).collect::<Vec<_>>())?,
+        _ => Series::new("", bytes),
+    };
+    Ok(series)
+}
 
+/// Returns the type of complex columns: a struct of the real (`re`) and imaginary (`im`) parts.
+///
+/// # Arguments
+/// - `part` (DataType): The type of each part, `Float32` for `C` columns and `Float64` for `M` columns.
+pub fn complex_dtype(part : DataType) -> DataType {
+    DataType::Struct(vec![Field::new("re", part.clone()), Field::new("im", part)])
+}
+
+/// Builds a complex series, a struct of the real (`re`) and imaginary (`im`) parts.
+fn complex_to_series<T: Copy>(col_name : &str, values : &[(T, T)]) -> Result<Series, FitsError>
+where
+    Series: NamedFrom<Vec<T>, [T]>,
+{
+    let re = Series::new("re", values.iter().map(|v| v.0).collect::<Vec<T>>());
+    let im = Series::new("im", values.iter().map(|v| v.1).collect::<Vec<T>>());
+    StructChunked::new(col_name, &[re, im])
+        .map(|parts| parts.into_series())
+        .map_err(|e| FitsError::InvalidValue(format!("complex column {} could not be built ({})", col_name, e)))
+}
+
+/// Reads a big-endian single precision complex value (`C`).
+fn read_complex_f32(bytes : &[u8]) -> (f32, f32) {
+    (
+        f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        f32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+    )
+}
 
+/// Reads a big-endian double precision complex value (`M`).
+fn read_complex_f64(bytes : &[u8]) -> (f64, f64) {
+    (
+        f64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+        f64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+    )
 }
 
 impl ColumnArrayBuffer {
@@ -188,29 +303,33 @@ impl ColumnArrayBuffer {
     /// - `sub_size` (i32): The size of sub-elements in the array.
     ///
     /// # Returns
-    /// - `ColumnArrayBuffer`: A new buffer for storing array data.
-    pub fn new(tform : &str, size : i32, sub_size : i32) -> Self {
+    /// - `Result<ColumnArrayBuffer, FitsError>`: A new buffer for storing array data, or
+    ///   `FitsError::UnsupportedDataType` if the format has no array buffer.
+    pub fn new(tform : &str, size : i32, sub_size : i32) -> Result<Self, FitsError> {
         let tform = tform.trim();
         let tform_type = get_first_letter(tform);
 
-        match tform_type {
-            "L" => ColumnArrayBuffer::L(vec![vec![false; sub_size as usize]; size as usize]),
-            "X" => ColumnArrayBuffer::X(vec![vec![0; sub_size as usize]; size as usize]),
+        let buffer = match tform_type {
+            "L" => ColumnArrayBuffer::L(vec![vec![None; sub_size as usize]; size as usize]),
+            "X" => ColumnArrayBuffer::X(vec![vec![false; sub_size as usize]; size as usize]),
             "B" => ColumnArrayBuffer::B(vec![vec![0; sub_size as usize]; size as usize]),
             "I" => ColumnArrayBuffer::I(vec![vec![0; sub_size as usize]; size as usize]),
             "J" => ColumnArrayBuffer::J(vec![vec![0; sub_size as usize]; size as usize]),
             "K" => ColumnArrayBuffer::K(vec![vec![0; sub_size as usize]; size as usize]),
             "E" => ColumnArrayBuffer::E(vec![vec![0.0; sub_size as usize]; size as usize]),
             "D" => ColumnArrayBuffer::D(vec![vec![0.0; sub_size as usize]; size as usize]),
-            _   => panic!("Unsupported data type for array col"),
-        }
+            "C" => ColumnArrayBuffer::C(vec![vec![(0.0, 0.0); sub_size as usize]; size as usize]),
+            "M" => ColumnArrayBuffer::M(vec![vec![(0.0, 0.0); sub_size as usize]; size as usize]),
+            _   => return Err(FitsError::UnsupportedDataType(format!("array column of TFORM {}", tform))),
+        };
+        Ok(buffer)
     }
 
-    pub fn empty(tform : &str) -> Self {
+    pub fn empty(tform : &str) -> Result<Self, FitsError> {
         let tform = tform.trim();
         let tform_type = get_first_letter(tform);
 
-        match tform_type {
+        let buffer = match tform_type {
             "L" => ColumnArrayBuffer::L(vec![vec![]]),
             "X" => ColumnArrayBuffer::X(vec![vec![]]),
             "B" => ColumnArrayBuffer::B(vec![vec![]]),
@@ -219,8 +338,11 @@ impl ColumnArrayBuffer {
             "K" => ColumnArrayBuffer::K(vec![vec![]]),
             "E" => ColumnArrayBuffer::E(vec![vec![]]),
             "D" => ColumnArrayBuffer::D(vec![vec![]]),
-            _   => panic!("Unsupported data type for array col"),
-        }
+            "C" => ColumnArrayBuffer::C(vec![vec![]]),
+            "M" => ColumnArrayBuffer::M(vec![vec![]]),
+            _   => return Err(FitsError::UnsupportedDataType(format!("array column of TFORM {}", tform))),
+        };
+        Ok(buffer)
     }
 
     /// Converts the array buffer into a Polars `Series`.
@@ -229,8 +351,8 @@ impl ColumnArrayBuffer {
     /// - `col_name` (&str): The name of the column.
     ///
     /// # Returns
-    /// - `Series`: A Polars `Series` containing the column's array data.
-    pub fn to_series(&self, col_name : &str) -> Series {
+    /// - `Result<Series, FitsError>`: A Polars `Series` containing the column's array data.
+    pub fn to_series(&self, col_name : &str) -> Result<Series, FitsError> {
         let series = match self {
             ColumnArrayBuffer::L(data)   =>  Series::new(col_name, 
                 data.iter().map(|vec| {
@@ -272,8 +394,18 @@ impl ColumnArrayBuffer {
                     Series::new("", vec)
                 }).collect::<Vec<Series>>()
             ),
+            ColumnArrayBuffer::C(data)    =>  Series::new(col_name, 
+                data.iter().map(|vec| {
+                    complex_to_series("", vec)
+                }).collect::<Result<Vec<Series>, FitsError>>()?
+            ),
+            ColumnArrayBuffer::M(data)    =>  Series::new(col_name, 
+                data.iter().map(|vec| {
+                    complex_to_series("", vec)
+                }).collect::<Result<Vec<Series>, FitsError>>()?
+            ),
         };
-        series
+        Ok(series)
     }
 
     /// Clears the array buffer, removing all stored data.
@@ -287,6 +419,8 @@ impl ColumnArrayBuffer {
             ColumnArrayBuffer::K(data)    => data.clear(),
             ColumnArrayBuffer::E(data)    => data.clear(),
             ColumnArrayBuffer::D(data)    => data.clear(),
+            ColumnArrayBuffer::C(data)    => data.clear(),
+            ColumnArrayBuffer::M(data)    => data.clear(),
         }
     }
 
@@ -297,35 +431,56 @@ impl ColumnArrayBuffer {
     /// - `data_letter` (&str): The format letter indicating the data type.
     /// - `idx` (i64): The row index where the data should be written.
     /// - `sub_size` (i32): The number of sub-elements for vector data.
-    pub fn write_on_idx(&mut self, bytes : &[u8], data_letter : &str, idx : i64, sub_size : i32){
-        match data_letter {
-            "L" => {
-                // parse bytes to bool
-                match self {
-                    ColumnArrayBuffer::L(data) => {
-                        (0..sub_size).for_each(|i| {
-                            data[idx as usize][i as usize] = bytes[i as usize] != 0;
-                        });
-                    }
-                    _ => panic!("Wrong data type"),
-                }
+    ///
+    /// # Returns
+    /// - `Result<(), FitsError>`: `FitsError::InvalidValue` if `data_letter` is not the type of the buffer.
+    pub fn write_on_idx(&mut self, bytes : &[u8], data_letter : &str, idx : i64, sub_size : i32) -> Result<(), FitsError> {
+        let row = idx as usize;
+        let n = sub_size as usize;
+        match (data_letter, self) {
+            // parse bytes to bool
+            ("L", ColumnArrayBuffer::L(data)) => {
+                (0..n).for_each(|i| data[row][i] = logical_from_byte(bytes[i]));
             }
-            "K" => {
-                // parse bytes to i64
-                match self {
-                    ColumnArrayBuffer::K(data) => {
-                        let mut stbyte = 0;
-                        (0..sub_size).for_each(|i| {
-                            data[idx as usize][i as usize] = i64::from_be_bytes([bytes[stbyte], bytes[stbyte+1], bytes[stbyte+2], bytes[stbyte+3], bytes[stbyte+4], bytes[stbyte+5], bytes[stbyte+6], bytes[stbyte+7]]);
-                            stbyte += 8;
-                        });
-                    }
-                    _ => panic!("Wrong data type"),
-                }
+            // unpack bits to bool, most significant bit first
+            ("X", ColumnArrayBuffer::X(data)) => {
+                (0..n).for_each(|i| data[row][i] = bytes[i / 8] & (0x80 >> (i % 8)) != 0);
             }
-            _ => panic!("Wrong data type"),
-
+            // parse bytes to u8
+            ("B", ColumnArrayBuffer::B(data)) => {
+                data[row][..n].copy_from_slice(&bytes[..n]);
+            }
+            // parse bytes to i16
+            ("I", ColumnArrayBuffer::I(data)) => {
+                bytes.chunks_exact(2).take(n).enumerate().for_each(|(i, b)| data[row][i] = i16::from_be_bytes([b[0], b[1]]));
+            }
+            // parse bytes to i32
+            ("J", ColumnArrayBuffer::J(data)) => {
+                bytes.chunks_exact(4).take(n).enumerate().for_each(|(i, b)| data[row][i] = i32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+            }
+            // parse bytes to i64
+            ("K", ColumnArrayBuffer::K(data)) => {
+                bytes.chunks_exact(8).take(n).enumerate().for_each(|(i, b)| data[row][i] = i64::from_be_bytes(b.try_into().unwrap()));
+            }
+            // parse bytes to f32
+            ("E", ColumnArrayBuffer::E(data)) => {
+                bytes.chunks_exact(4).take(n).enumerate().for_each(|(i, b)| data[row][i] = f32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+            }
+            // parse bytes to f64
+            ("D", ColumnArrayBuffer::D(data)) => {
+                bytes.chunks_exact(8).take(n).enumerate().for_each(|(i, b)| data[row][i] = f64::from_be_bytes(b.try_into().unwrap()));
+            }
+            // parse bytes to (f32, f32)
+            ("C", ColumnArrayBuffer::C(data)) => {
+                bytes.chunks_exact(8).take(n).enumerate().for_each(|(i, b)| data[row][i] = read_complex_f32(b));
+            }
+            // parse bytes to (f64, f64)
+            ("M", ColumnArrayBuffer::M(data)) => {
+                bytes.chunks_exact(16).take(n).enumerate().for_each(|(i, b)| data[row][i] = read_complex_f64(b));
+            }
+            (letter, _) => return Err(FitsError::InvalidValue(format!("{} data in an array buffer of another type", letter))),
         }
+        Ok(())
     }
 }
 
@@ -343,8 +498,7 @@ impl ColumnDataBuffer {
         let tform_type = get_first_letter(tform);
 
         match tform_type {
-            "L" => ColumnDataBuffer::L(vec![false; size as usize]),
-            "X" => ColumnDataBuffer::X(vec![0; size as usize]),
+            "L" => ColumnDataBuffer::L(vec![None; size as usize]),
             "B" => ColumnDataBuffer::B(vec![0; size as usize]),
             "I" => ColumnDataBuffer::I(vec![0; size as usize]),
             "J" => ColumnDataBuffer::J(vec![0; size as usize]),
@@ -352,8 +506,8 @@ impl ColumnDataBuffer {
             "A" => ColumnDataBuffer::A(vec![String::new(); size as usize]),
             "E" => ColumnDataBuffer::E(vec![0.0; size as usize]),
             "D" => ColumnDataBuffer::D(vec![0.0; size as usize]),
-            "C" => ColumnDataBuffer::C(vec![String::new(); size as usize]),
-            "M" => ColumnDataBuffer::M(vec![String::new(); size as usize]),
+            "C" => ColumnDataBuffer::C(vec![(0.0, 0.0); size as usize]),
+            "M" => ColumnDataBuffer::M(vec![(0.0, 0.0); size as usize]),
             "P" => ColumnDataBuffer::P(vec![vec![0; 2]; size as usize]),
             "Q" => ColumnDataBuffer::Q(vec![vec![0; 2]; size as usize]),
             _   => ColumnDataBuffer::A(vec![String::new(); size as usize]),
@@ -366,11 +520,10 @@ impl ColumnDataBuffer {
     /// - `col_name` (&str): The name of the column.
     ///
     /// # Returns
-    /// - `Series`: A Polars `Series` containing the column's scalar data.
-    pub fn to_series(&self, col_name : &str) -> Series {
+    /// - `Result<Series, FitsError>`: A Polars `Series` containing the column's scalar data.
+    pub fn to_series(&self, col_name : &str) -> Result<Series, FitsError> {
         let series = match self {
             ColumnDataBuffer::L(data)   =>  Series::new(col_name, data),
-            ColumnDataBuffer::X(data)     =>  Series::new(col_name, data),
             ColumnDataBuffer::B(data)     =>  Series::new(col_name, data),
             ColumnDataBuffer::I(data)    =>  Series::new(col_name, data),
             ColumnDataBuffer::J(data)    =>  Series::new(col_name, data),
@@ -378,8 +531,8 @@ impl ColumnDataBuffer {
             ColumnDataBuffer::A(data) =>  Series::new(col_name, data),
             ColumnDataBuffer::E(data)    =>  Series::new(col_name, data),
             ColumnDataBuffer::D(data)    =>  Series::new(col_name, data),
-            ColumnDataBuffer::C(data) =>  Series::new(col_name, data),
-            ColumnDataBuffer::M(data) =>  Series::new(col_name, data),
+            ColumnDataBuffer::C(data) =>  complex_to_series(col_name, data)?,
+            ColumnDataBuffer::M(data) =>  complex_to_series(col_name, data)?,
             ColumnDataBuffer::P(data) =>  {
                 let series_vec: Vec<Series> = data.iter().map(|vec| {
                     Series::new("", &vec)
@@ -393,14 +546,13 @@ impl ColumnDataBuffer {
                 Series::new(col_name, series_vec)
             }
         };
-        series
+        Ok(series)
     }
 
     /// Clears the scalar data buffer, removing all stored data.
     pub fn clear(&mut self){
         match self {
             ColumnDataBuffer::L(data)   => data.clear(),
-            ColumnDataBuffer::X(data)     => data.clear(),
             ColumnDataBuffer::B(data)     => data.clear(),
             ColumnDataBuffer::I(data)    => data.clear(),
             ColumnDataBuffer::J(data)    => data.clear(),
@@ -421,117 +573,47 @@ impl ColumnDataBuffer {
     /// - `bytes` (&[u8]): The raw bytes to write.
     /// - `data_letter` (&str): The format letter indicating the data type.
     /// - `idx` (i64): The row index where the data should be written.
-    pub fn write_on_idx(&mut self, bytes : &[u8], data_letter : &str, idx : i64){
-        match data_letter {
-            "L" => {
-                // parse bytes to bool
-                match self {
-                    ColumnDataBuffer::L(data) => data[idx as usize] = bytes[0] != 0,
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "X" => {
-                // parse bytes to u8
-                match self {
-                    ColumnDataBuffer::X(data) => data[idx as usize] = bytes[0],
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "B" => {
-                // parse bytes to i8
-                match self {
-                    ColumnDataBuffer::B(data) => data[idx as usize] = bytes[0] as i8,
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "I" => {
-                // parse bytes to i16
-                match self {
-                    ColumnDataBuffer::I(data) => data[idx as usize] = i16::from_be_bytes([bytes[0], bytes[1]]),
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "J" => {
-                // parse bytes to i32
-                match self {
-                    ColumnDataBuffer::J(data) => data[idx as usize] = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "K" => {
-                // parse bytes to i64
-                match self {
-                    ColumnDataBuffer::K(data) => data[idx as usize] = i64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]),
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "A" => {
-                // parse bytes to String
-                match self {
-                    ColumnDataBuffer::A(data) => {
-                        let string = unsafe { String::from_utf8_unchecked(bytes.to_vec()) }.trim_end().to_string();
-                        data[idx as usize] = string;
-                    }
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "E" => {
-                // parse bytes to f32
-                match self {
-                    ColumnDataBuffer::E(data) => data[idx as usize] = f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "D" => {
-                // parse bytes to f64
-                match self {
-                    ColumnDataBuffer::D(data) => data[idx as usize] = f64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]),
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "C" => {
-                // parse bytes to String
-                match self {
-                    ColumnDataBuffer::C(data) => {
-                        let string = unsafe { String::from_utf8_unchecked(bytes.to_vec()) }.trim_end().to_string();
-                        data[idx as usize] = string;
-                    }
-                    _ => panic!("Wrong data type"),
-                }
-            }
-            "M" => {
-                // parse bytes to String
-                match self {
-                    ColumnDataBuffer::M(data) => {
-                        let string = unsafe { String::from_utf8_unchecked(bytes.to_vec()) }.trim_end().to_string();
-                        data[idx as usize] = string;
-                    }
-                    _ => panic!("Wrong data type"),
-                }
+    ///
+    /// # Returns
+    /// - `Result<(), FitsError>`: `FitsError::InvalidValue` if `data_letter` is not the type of the buffer.
+    pub fn write_on_idx(&mut self, bytes : &[u8], data_letter : &str, idx : i64) -> Result<(), FitsError> {
+        let row = idx as usize;
+        match (data_letter, self) {
+            // parse bytes to bool
+            ("L", ColumnDataBuffer::L(data)) => data[row] = logical_from_byte(bytes[0]),
+            // parse bytes to u8
+            ("B", ColumnDataBuffer::B(data)) => data[row] = bytes[0],
+            // parse bytes to i16
+            ("I", ColumnDataBuffer::I(data)) => data[row] = i16::from_be_bytes([bytes[0], bytes[1]]),
+            // parse bytes to i32
+            ("J", ColumnDataBuffer::J(data)) => data[row] = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            // parse bytes to i64
+            ("K", ColumnDataBuffer::K(data)) => data[row] = i64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]),
+            // parse bytes to String
+            ("A", ColumnDataBuffer::A(data)) => {
+                data[row] = unsafe { String::from_utf8_unchecked(bytes.to_vec()) }.trim_end().to_string();
             }
-            "P" => {
-                // parse bytes to String
-                match self {
-                    ColumnDataBuffer::P(data) => {
-                        data[idx as usize][0] = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-                        data[idx as usize][1] = i32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-                    }
-                    _ => panic!("Wrong data type"),
-                }
+            // parse bytes to f32
+            ("E", ColumnDataBuffer::E(data)) => data[row] = f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            // parse bytes to f64
+            ("D", ColumnDataBuffer::D(data)) => data[row] = f64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]),
+            // parse bytes to (f32, f32)
+            ("C", ColumnDataBuffer::C(data)) => data[row] = read_complex_f32(bytes),
+            // parse bytes to (f64, f64)
+            ("M", ColumnDataBuffer::M(data)) => data[row] = read_complex_f64(bytes),
+            // parse bytes to a 32-bit array descriptor
+            ("P", ColumnDataBuffer::P(data)) => {
+                data[row][0] = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                data[row][1] = i32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
             }
-            "Q" => {
-                // parse bytes to String
-                match self {
-                    ColumnDataBuffer::Q(data) => {
-                        data[idx as usize][0] = i64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]);
-                        data[idx as usize][0] = i64::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]]);
-                    }
-                    _ => panic!("Wrong data type"),
-                }
+            // parse bytes to a 64-bit array descriptor
+            ("Q", ColumnDataBuffer::Q(data)) => {
+                data[row][0] = i64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]);
+                data[row][1] = i64::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]]);
             }
-            _ => panic!("Wrong data type"),
-
+            (letter, _) => return Err(FitsError::InvalidValue(format!("{} data in a scalar buffer of another type", letter))),
         }
+        Ok(())
     }
     //no need for max_len on bintable
 }
\ No newline at end of file
@@ -8,6 +8,8 @@ pub mod utils;
 extern crate regex;
 use regex::Regex;
 
+use crate::io::errors::FitsError;
+
 /// Extracts the first uppercase letter from a string.
 ///
 /// # Arguments
@@ -24,31 +26,47 @@ pub fn get_first_letter(string : &str) -> &str {
     ""
 }
 
-/// Calculates the total byte size of a data type based on its format string.
+/// Extracts the repeat count of a format string.
 ///
 /// # Arguments
-/// - `string` (&str): The format string (e.g., "10A", "2E").
+/// - `string` (&str): The format string (e.g., "10A", "16X", "E").
 ///
 /// # Returns
-/// - `usize`: The total byte size for the data type.
-///
-/// # Behavior
-/// - Parses the numeric prefix to determine the number of elements.
-/// - Multiplies the element count by the byte size of the data type (determined by `byte_value_from_str`).
-pub fn get_data_bytes_size(string : &str) -> usize {
+/// - `Result<usize, FitsError>`: The numeric prefix, 1 if there is none, or `FitsError::InvalidTform`.
+pub fn get_repeat_count(string : &str) -> Result<usize, FitsError> {
     let re = Regex::new(r"^(\d*)?").unwrap();
     let size = if let Some(cap) = re.find(string) {
         let number = &string[cap.range()];
         if number.is_empty() {
             1
         } else {
-            number.parse::<usize>().unwrap()
+            number.parse::<usize>().map_err(|_| FitsError::InvalidTform(string.to_string()))?
         }
     }else{
         1
     };
+    Ok(size)
+}
 
-    size * byte_value_from_str(string)
+/// Calculates the total byte size of a data type based on its format string.
+///
+/// # Arguments
+/// - `string` (&str): The format string (e.g., "10A", "2E").
+///
+/// # Returns
+/// - `Result<usize, FitsError>`: The total byte size for the data type, or `FitsError::InvalidTform`.
+///
+/// # Behavior
+/// - Parses the numeric prefix to determine the number of elements.
+/// - Multiplies the element count by the byte size of the data type (determined by `byte_value_from_str`).
+/// - Bit arrays (`X`) are packed eight bits to a byte, so `16X` takes 2 bytes.
+pub fn get_data_bytes_size(string : &str) -> Result<usize, FitsError> {
+    let size = get_repeat_count(string)?;
+    if get_first_letter(string) == "X" {
+        return Ok(size.div_ceil(8));
+    }
+
+    Ok(size * byte_value_from_str(string)?)
 }
 
 /// Determines the byte size of a single element based on its data type format string.
@@ -57,11 +75,12 @@ pub fn get_data_bytes_size(string : &str) -> usize {
 /// - `data_type` (&str): The format string representing the data type.
 ///
 /// # Returns
-/// - `usize`: The byte size of a single element of the specified data type.
+/// - `Result<usize, FitsError>`: The byte size of a single element of the specified data type,
+///   or `FitsError::InvalidTform` for an unknown type letter.
 ///
 /// # Supported Data Types
 /// - `"L"`: Logical (1 byte)
-/// - `"X"`: Bit (1 byte)
+/// - `"X"`: Bit (1 byte holds 8 bits, see `get_data_bytes_size`)
 /// - `"B"`: Byte (1 byte)
 /// - `"I"`: Short integer (2 bytes)
 /// - `"J"`: Integer (4 bytes)
@@ -73,23 +92,94 @@ pub fn get_data_bytes_size(string : &str) -> usize {
 /// - `"M"`: Double complex (16 bytes)
 /// - `"P"`: Array descriptor (8 bytes)
 /// - `"Q"`: Array descriptor (16 bytes)
-pub fn byte_value_from_str(data_type : &str) -> usize {
+pub fn byte_value_from_str(data_type : &str) -> Result<usize, FitsError> {
     match get_first_letter(data_type) {
-        "L" => 1,
-        "X" => 1,
-        "B" => 1,
-        "I" => 2,
-        "J" => 4,
-        "K" => 8,
-        "A" => 1,
-        "E" => 4,
-        "D" => 8,
-        "C" => 8,
-        "M" => 16,
-        "P" => 8,
-        "Q" => 16,
-        _ => panic!("Wrong data type"),
+        "L" => Ok(1),
+        "X" => Ok(1),
+        "B" => Ok(1),
+        "I" => Ok(2),
+        "J" => Ok(4),
+        "K" => Ok(8),
+        "A" => Ok(1),
+        "E" => Ok(4),
+        "D" => Ok(8),
+        "C" => Ok(8),
+        "M" => Ok(16),
+        "P" => Ok(8),
+        "Q" => Ok(16),
+        _ => Err(FitsError::InvalidTform(data_type.to_string())),
+    }
+}
+
+/// Extracts the element type of a variable-length array column.
+///
+/// # Arguments
+/// - `tform` (&str): The format string of a `P` or `Q` column (e.g., "1PE(12)", "QD").
+///
+/// # Returns
+/// - `Result<&str, FitsError>`: The letter of the array elements, or `FitsError::InvalidTform`
+///   if `tform` is not an array descriptor or has no element type.
+pub fn get_var_len_type(tform : &str) -> Result<&str, FitsError> {
+    let re = Regex::new(r"^\s*\d*[PQ]([A-Z])").unwrap();
+    re.captures(tform)
+        .and_then(|cap| cap.get(1))
+        .map(|letter| &tform[letter.range()])
+        .ok_or_else(|| FitsError::InvalidTform(tform.to_string()))
+}
+
+/// Reads the element count and heap offset of a `P` (8 bytes) or `Q` (16 bytes) array descriptor.
+///
+/// # Arguments
+/// - `bytes` (&[u8]): The bytes of the descriptor in the row.
+///
+/// # Returns
+/// - `(usize, usize)`: The number of elements of the array and its byte offset in the heap.
+pub fn read_descriptor(bytes : &[u8]) -> (usize, usize) {
+    if bytes.len() >= 16 {
+        (
+            u64::from_be_bytes(bytes[0..8].try_into().unwrap()) as usize,
+            u64::from_be_bytes(bytes[8..16].try_into().unwrap()) as usize,
+        )
+    } else {
+        (
+            u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize,
+            u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize,
+        )
+    }
+}
+
+/// Writes the element count and heap offset of an array into a `P` (8 bytes) or `Q` (16 bytes) descriptor.
+///
+/// # Arguments
+/// - `field` (&mut [u8]): The bytes of the descriptor in the row.
+/// - `count` (usize): The number of elements of the array.
+/// - `offset` (usize): The byte offset of the array in the heap.
+pub fn write_descriptor(field : &mut [u8], count : usize, offset : usize) {
+    if field.len() >= 16 {
+        field[0..8].copy_from_slice(&(count as u64).to_be_bytes());
+        field[8..16].copy_from_slice(&(offset as u64).to_be_bytes());
+    } else {
+        field[0..4].copy_from_slice(&(count as u32).to_be_bytes());
+        field[4..8].copy_from_slice(&(offset as u32).to_be_bytes());
+    }
+}
+
+/// Calculates the number of heap bytes of a variable-length array.
+///
+/// # Arguments
+/// - `letter` (&str): The element type of the array, as returned by `get_var_len_type`.
+/// - `count` (usize): The number of elements of the array.
+///
+/// # Returns
+/// - `Result<usize, FitsError>`: The size of the array in bytes, bits (`X`) being packed in bytes.
+///   `FitsError::InvalidValue` if the size overflows, as no heap can hold such an array.
+pub fn var_len_array_bytes(letter : &str, count : usize) -> Result<usize, FitsError> {
+    if letter == "X" {
+        return Ok(count.div_ceil(8));
     }
+    count.checked_mul(byte_value_from_str(letter)?).ok_or_else(|| {
+        FitsError::InvalidValue(format!("array of {} elements of type {} is outside the heap", count, letter))
+    })
 }
 
 /// Splits a buffer into evenly distributed chunks, aligned by row size.
@@ -114,7 +204,7 @@ pub fn split_buffer(buffer_size: usize, n: u16, row_size: u16) -> Vec<(usize, us
     for i in 0..n {
         if n - 1 == i {
             end = buffer_size;
-        } else if (start + nbufs) % row_size as usize != 0 {
+        } else if !(start + nbufs).is_multiple_of(row_size as usize) {
             end = start + nbufs + row_size as usize - (start + nbufs) % row_size as usize;
         } else {
             end = start + nbufs;
@@ -0,0 +1,932 @@
+//! Tile-compressed images, stored as a BINTABLE with `ZIMAGE = T`.
+//!
+//! Each row of the table holds one tile of the image. The pixels of a tile are
+//! compressed into the heap and pointed to by the `COMPRESSED_DATA` column.
+
+use std::io::{Read, Seek, Write};
+
+use rayon::prelude::*;
+
+use crate::io::errors::FitsError;
+use crate::io::header::card::{Card, CardValue};
+use crate::io::Header;
+use crate::io::hdus::bintable::bintable::{read_tableinfo_from_header, Column};
+use crate::io::hdus::bintable::read_descriptor;
+use crate::io::hdus::compressed::gzip::{gzip_compress, gzip_compress_pixels, gzip_decompress, gzip_decompress_pixels};
+use crate::io::hdus::compressed::hcompress::{hcompress, hcompress_scale, hdecompress};
+use crate::io::hdus::compressed::plio::{plio_compress, plio_decompress};
+use crate::io::hdus::compressed::quantize::{
+    quantize_f32, quantize_f64, unquantize, QuantizedTile, NULL_VALUE, N_RANDOM,
+};
+use crate::io::hdus::compressed::rice::{rice_compress, rice_decompress};
+use crate::io::hdus::image::ImageData;
+use crate::io::hdus::image::image::ImageParser;
+use crate::io::hdus::image::utils::vec_to_ndarray;
+use crate::io::hdus::utils::{data_size_from_header, get_size, get_size_or};
+use crate::io::utils::{pad_read_buffer_to_fits_block, read_exact_or_truncated};
+
+/// The tile compression algorithms, as given by `ZCMPTYPE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionType {
+    Rice1,
+    Gzip1,
+    Gzip2,
+    Hcompress1,
+    Plio1,
+}
+
+impl CompressionType {
+    /// Parses a `ZCMPTYPE` value, returning `FitsError::UnsupportedDataType` for unknown algorithms.
+    pub fn from_zcmptype(zcmptype: &str) -> Result<Self, FitsError> {
+        match zcmptype.trim() {
+            "RICE_1" | "RICE_ONE" => Ok(CompressionType::Rice1),
+            "GZIP_1" => Ok(CompressionType::Gzip1),
+            "GZIP_2" => Ok(CompressionType::Gzip2),
+            "HCOMPRESS_1" => Ok(CompressionType::Hcompress1),
+            "PLIO_1" => Ok(CompressionType::Plio1),
+            other => Err(FitsError::UnsupportedDataType(format!("ZCMPTYPE = {}", other))),
+        }
+    }
+
+    /// Returns the `ZCMPTYPE` value of the algorithm.
+    pub fn as_zcmptype(&self) -> &'static str {
+        match self {
+            CompressionType::Rice1 => "RICE_1",
+            CompressionType::Gzip1 => "GZIP_1",
+            CompressionType::Gzip2 => "GZIP_2",
+            CompressionType::Hcompress1 => "HCOMPRESS_1",
+            CompressionType::Plio1 => "PLIO_1",
+        }
+    }
+
+    /// Whether the algorithm is GZIP_1 or GZIP_2, the only ones able to compress floating
+    /// point pixels without quantizing them.
+    pub fn is_gzip(&self) -> bool {
+        matches!(self, CompressionType::Gzip1 | CompressionType::Gzip2)
+    }
+}
+
+/// The quantization method of floating point images, as given by `ZQUANTIZ`.
+///
+/// `NoQuantize` (`ZQUANTIZ = 'NONE'`) stores the pixels losslessly, which only GZIP_1 and
+/// GZIP_2 support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizeMethod {
+    NoQuantize,
+    NoDither,
+    SubtractiveDither1,
+    SubtractiveDither2,
+}
+
+impl QuantizeMethod {
+    /// Parses a `ZQUANTIZ` value.
+    pub fn from_zquantiz(zquantiz: &str) -> Result<Self, FitsError> {
+        match zquantiz.trim() {
+            "NONE" => Ok(QuantizeMethod::NoQuantize),
+            "NO_DITHER" => Ok(QuantizeMethod::NoDither),
+            "SUBTRACTIVE_DITHER_1" => Ok(QuantizeMethod::SubtractiveDither1),
+            "SUBTRACTIVE_DITHER_2" => Ok(QuantizeMethod::SubtractiveDither2),
+            other => Err(FitsError::UnsupportedDataType(format!("ZQUANTIZ = {}", other))),
+        }
+    }
+
+    /// Returns the `ZQUANTIZ` value of the method.
+    pub fn as_zquantiz(&self) -> &'static str {
+        match self {
+            QuantizeMethod::NoQuantize => "NONE",
+            QuantizeMethod::NoDither => "NO_DITHER",
+            QuantizeMethod::SubtractiveDither1 => "SUBTRACTIVE_DITHER_1",
+            QuantizeMethod::SubtractiveDither2 => "SUBTRACTIVE_DITHER_2",
+        }
+    }
+}
+
+/// The parameters of a tile-compressed image.
+///
+/// # Fields
+/// - `algorithm` (CompressionType): The compression algorithm (`ZCMPTYPE`).
+/// - `tile` (Vec<usize>): The tile size along each axis (`ZTILEn`), `NAXIS1` first.
+/// - `block_size` (usize): The RICE_1 block size (`BLOCKSIZE`).
+/// - `bytepix` (usize): The RICE_1 bytes per pixel (`BYTEPIX`).
+/// - `quantize_level` (f32): The quantization level of floating point images (`NOISEBIT`).
+/// - `quantize_method` (QuantizeMethod): The quantization method of floating point images (`ZQUANTIZ`).
+/// - `hcomp_scale` (f32): The HCOMPRESS_1 scale factor (`SCALE`), 0 for lossless compression.
+///   Positive values are rounded to the integer scale of every tile, negative values are relative
+///   to the noise of each tile, see `hcompress_scale`.
+/// - `hcomp_smooth` (bool): Whether HCOMPRESS_1 tiles are smoothed when decompressed (`SMOOTH`).
+/// - `dither_seed` (i32): The offset of the first tile in the dithering sequence (`ZDITHER0`),
+///   between 1 and 10000.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionParams {
+    pub algorithm: CompressionType,
+    pub tile: Vec<usize>,
+    pub block_size: usize,
+    pub bytepix: usize,
+    pub quantize_level: f32,
+    pub quantize_method: QuantizeMethod,
+    pub hcomp_scale: f32,
+    pub hcomp_smooth: bool,
+    pub dither_seed: i32,
+}
+
+impl CompressionParams {
+    /// Creates the parameters to compress an image with `algorithm` and the given tile shape.
+    ///
+    /// # Arguments
+    /// - `algorithm` (CompressionType): The compression algorithm.
+    /// - `tile` (Vec<usize>): The tile size along each axis, `NAXIS1` first. Missing trailing axes
+    ///   default to 1, so an empty vector compresses the image row by row, as fpack does by default.
+    ///   HCOMPRESS_1 needs 2-dimensional tiles and defaults to blocks of 16 rows instead.
+    ///
+    /// # Returns
+    /// - `CompressionParams`: The parameters, with the CFITSIO defaults for the other fields.
+    ///   `bytepix` is taken from `BITPIX` when the image is written, and floating point images
+    ///   are quantized with 4 levels per noise sigma and `SUBTRACTIVE_DITHER_1`, as fpack does.
+    pub fn new(algorithm: CompressionType, tile: Vec<usize>) -> Self {
+        Self {
+            algorithm,
+            tile,
+            block_size: 32,
+            bytepix: 4,
+            quantize_level: 4.0,
+            quantize_method: QuantizeMethod::SubtractiveDither1,
+            hcomp_scale: 0.0,
+            hcomp_smooth: false,
+            dither_seed: 1,
+        }
+    }
+
+    /// Reads the compression parameters from the header of a compressed image.
+    ///
+    /// # Returns
+    /// - `Result<CompressionParams, FitsError>`: The parameters. `ZTILEn` defaults to a row-by-row
+    ///   tiling and the `ZNAMEn`/`ZVALn` parameters default to the CFITSIO values.
+    pub fn from_header(header: &Header) -> Result<Self, FitsError> {
+        let algorithm = CompressionType::from_zcmptype(&header.get_string("ZCMPTYPE")?)?;
+        let zbitpix = header.get_int("ZBITPIX")?;
+        let znaxis = header.get_int("ZNAXIS")?;
+
+        let mut tile = Vec::new();
+        for i in 1..=znaxis {
+            let key = format!("ZTILE{}", i);
+            let size = if header.contains_key(&key) {
+                header.get_int(&key)?
+            } else if i == 1 {
+                header.get_int("ZNAXIS1")?
+            } else {
+                1
+            };
+            if size <= 0 {
+                return Err(FitsError::InvalidHeader(format!("{} = {}", key, size)));
+            }
+            tile.push(size as usize);
+        }
+
+        let mut params = Self {
+            algorithm,
+            tile,
+            block_size: 32,
+            bytepix: if zbitpix > 0 { (zbitpix / 8) as usize } else { 4 },
+            quantize_level: 4.0,
+            quantize_method: QuantizeMethod::NoDither,
+            hcomp_scale: 0.0,
+            hcomp_smooth: false,
+            dither_seed: 1,
+        };
+
+        let mut i = 1;
+        while header.contains_key(&format!("ZNAME{}", i)) {
+            let name = header.get_string(&format!("ZNAME{}", i))?;
+            let zval = format!("ZVAL{}", i);
+            match name.as_str() {
+                "BLOCKSIZE" => params.block_size = header.get_int(&zval)? as usize,
+                "BYTEPIX" => params.bytepix = header.get_int(&zval)? as usize,
+                "NOISEBIT" => params.quantize_level = header.get_float(&zval)? as f32,
+                "SCALE" => params.hcomp_scale = header.get_float(&zval)? as f32,
+                "SMOOTH" => params.hcomp_smooth = header.get_int(&zval)? != 0,
+                _ => {},
+            }
+            i += 1;
+        }
+
+        if header.contains_key("ZQUANTIZ") {
+            params.quantize_method = QuantizeMethod::from_zquantiz(&header.get_string("ZQUANTIZ")?)?;
+        }
+        // Files written before ZDITHER0 was introduced used an offset of 1.
+        if header.contains_key("ZDITHER0") {
+            params.dither_seed = header.get_int("ZDITHER0")? as i32;
+        }
+        Ok(params)
+    }
+}
+
+/// Checks if a header describes a tile-compressed image (`ZIMAGE = T`).
+pub fn is_compressed_image(header: &Header) -> bool {
+    matches!(header.get_value("ZIMAGE"), Ok(CardValue::LOGICAL(true)))
+}
+
+/// Keywords of the compressed BINTABLE that do not belong in the image header.
+fn is_compression_keyword(keyword: &str) -> bool {
+    const EXACT: [&str; 25] = [
+        "XTENSION", "BITPIX", "NAXIS", "PCOUNT", "GCOUNT", "TFIELDS", "THEAP",
+        "ZIMAGE", "ZSIMPLE", "ZTENSION", "ZEXTEND", "ZBLOCKED", "ZBITPIX", "ZNAXIS",
+        "ZPCOUNT", "ZGCOUNT", "ZCMPTYPE", "ZQUANTIZ", "ZDITHER0", "ZMASKCMP",
+        "ZSCALE", "ZZERO", "ZBLANK",
+        "CHECKSUM", "DATASUM",
+    ];
+    const INDEXED: [&str; 14] = [
+        "NAXIS", "TTYPE", "TFORM", "TUNIT", "TDISP", "TDIM", "TNULL", "TSCAL", "TZERO",
+        "ZNAXIS", "ZTILE", "ZNAME", "ZVAL", "TBCOL",
+    ];
+    if EXACT.contains(&keyword) || keyword == "ZHECKSUM" || keyword == "ZDATASUM" {
+        return true;
+    }
+    INDEXED.iter().any(|prefix| {
+        keyword.strip_prefix(prefix)
+            .is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+/// Builds the header of the uncompressed image from the header of the compressed BINTABLE.
+///
+/// # Arguments
+/// - `header` (&Header): The header of the compressed image.
+///
+/// # Returns
+/// - `Result<Header, FitsError>`: An IMAGE extension header with `BITPIX` and `NAXISn` taken from
+///   `ZBITPIX` and `ZNAXISn`. The table and compression keywords are dropped, the other cards are kept.
+pub fn image_header_from_compressed(header: &Header) -> Result<Header, FitsError> {
+    let zbitpix = header.get_int("ZBITPIX")?;
+    let znaxis = header.get_int("ZNAXIS")?;
+
+    let mut image_header = Header::new();
+    image_header.add_card(&Card::new("XTENSION".to_string(), "IMAGE".to_string(), Some("Image extension".to_string())));
+    image_header.add_card(&Card::new("BITPIX".to_string(), zbitpix.to_string(), Some("Number of bits per data pixel".to_string())));
+    image_header.add_card(&Card::new("NAXIS".to_string(), znaxis.to_string(), Some("Number of data axes".to_string())));
+    for i in 1..=znaxis {
+        let naxisn = header.get_int(&format!("ZNAXIS{}", i))?;
+        image_header.add_card(&Card::new(format!("NAXIS{}", i), naxisn.to_string(), Some(format!("Length of data axis {}", i))));
+    }
+    image_header.add_card(&Card::new("PCOUNT".to_string(), "0".to_string(), Some("Number of parameters".to_string())));
+    image_header.add_card(&Card::new("GCOUNT".to_string(), "1".to_string(), Some("Number of groups".to_string())));
+
+    for card in header.iter() {
+        if !is_compression_keyword(&card.keyword) {
+            image_header.add_card(card);
+        }
+    }
+    Ok(image_header)
+}
+
+/// The raw rows and heap of a compressed image table.
+struct TileTable {
+    columns: Vec<Column>,
+    row_size: usize,
+    nrows: usize,
+    data: Vec<u8>,
+    heap_start: usize,
+}
+
+impl TileTable {
+    fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.ttype == name)
+    }
+
+    /// Returns the bytes of a column in a row.
+    fn field(&self, column: &Column, row: usize) -> &[u8] {
+        let start = row * self.row_size + column.start_address;
+        &self.data[start..start + column.type_bytes]
+    }
+
+    /// Returns the heap bytes pointed to by a `P` or `Q` descriptor, with the element size of the array.
+    ///
+    /// # Returns
+    /// - `Result<&[u8], FitsError>`: The bytes of the array, `FitsError::TruncatedData` if the array
+    ///   goes past the end of the data unit, `FitsError::InvalidHeader` if its end overflows.
+    fn heap_array(&self, column: &Column, row: usize) -> Result<&[u8], FitsError> {
+        if (column.type_letter != "P") & (column.type_letter != "Q") {
+            return Err(FitsError::InvalidTform(column.tform.clone()));
+        }
+        let (count, offset) = read_descriptor(self.field(column, row));
+        let element = column.tform.trim()
+            .split_once(['P', 'Q'])
+            .and_then(|(_, rest)| rest.chars().next())
+            .map(element_size)
+            .unwrap_or(Ok(1))?;
+
+        let start = self.heap_start.checked_add(offset);
+        let end = start.and_then(|start| start.checked_add(count.checked_mul(element)?));
+        let (start, end) = start.zip(end).ok_or_else(|| FitsError::InvalidHeader(format!(
+            "descriptor of {} elements at heap offset {} in column {}", count, offset, column.ttype
+        )))?;
+        if end > self.data.len() {
+            return Err(FitsError::TruncatedData { expected: end, found: self.data.len() });
+        }
+        Ok(&self.data[start..end])
+    }
+
+    /// Returns a float stored in a `D` or `E` column.
+    fn float(&self, column: &Column, row: usize) -> f64 {
+        let field = self.field(column, row);
+        match column.type_letter.as_str() {
+            "E" => f32::from_be_bytes(field[0..4].try_into().unwrap()) as f64,
+            _ => f64::from_be_bytes(field[0..8].try_into().unwrap()),
+        }
+    }
+
+    /// Returns an integer stored in a `J` or `K` column.
+    fn int(&self, column: &Column, row: usize) -> i64 {
+        let field = self.field(column, row);
+        match column.type_letter.as_str() {
+            "K" => i64::from_be_bytes(field[0..8].try_into().unwrap()),
+            "I" => i16::from_be_bytes(field[0..2].try_into().unwrap()) as i64,
+            _ => i32::from_be_bytes(field[0..4].try_into().unwrap()) as i64,
+        }
+    }
+}
+
+/// Returns the byte size of a heap array element of type `letter`.
+fn element_size(letter: char) -> Result<usize, FitsError> {
+    match letter {
+        'L' | 'X' | 'B' | 'A' => Ok(1),
+        'I' => Ok(2),
+        'J' | 'E' => Ok(4),
+        'K' | 'D' | 'C' => Ok(8),
+        'M' => Ok(16),
+        other => Err(FitsError::InvalidTform(other.to_string())),
+    }
+}
+
+/// The geometry of the tiles of an image.
+struct Tiling {
+    dims: Vec<usize>,
+    tile: Vec<usize>,
+    ntiles: Vec<usize>,
+}
+
+impl Tiling {
+    fn new(dims: Vec<usize>, tile: Vec<usize>) -> Self {
+        let ntiles = dims.iter().zip(&tile).map(|(d, t)| d.div_ceil(*t)).collect();
+        Self { dims, tile, ntiles }
+    }
+
+    fn count(&self) -> usize {
+        self.ntiles.iter().product()
+    }
+
+    /// Returns the first pixel and the size of a tile along each axis, `NAXIS1` first.
+    fn bounds(&self, index: usize) -> (Vec<usize>, Vec<usize>) {
+        let mut rest = index;
+        let mut start = Vec::with_capacity(self.dims.len());
+        let mut size = Vec::with_capacity(self.dims.len());
+        for axis in 0..self.dims.len() {
+            let position = rest % self.ntiles[axis];
+            rest /= self.ntiles[axis];
+            let first = position * self.tile[axis];
+            start.push(first);
+            size.push(self.tile[axis].min(self.dims[axis] - first));
+        }
+        (start, size)
+    }
+
+    /// Copies the pixels of a tile into the image, both in FITS order (`NAXIS1` varying fastest).
+    fn place<T: Copy>(&self, image: &mut [T], index: usize, pixels: &[T]) {
+        let (start, size) = self.bounds(index);
+        let ndim = self.dims.len();
+        let run = size[0];
+        let nruns = pixels.len() / run.max(1);
+
+        let mut position = vec![0; ndim];
+        for r in 0..nruns {
+            let mut offset = 0;
+            let mut stride = 1;
+            for axis in 0..ndim {
+                offset += (start[axis] + position[axis]) * stride;
+                stride *= self.dims[axis];
+            }
+            image[offset..offset + run].copy_from_slice(&pixels[r * run..(r + 1) * run]);
+            Self::advance(&mut position, &size);
+        }
+    }
+
+    /// Copies the pixels of a tile out of the image, both in FITS order (`NAXIS1` varying fastest).
+    fn extract<T: Copy>(&self, image: &[T], index: usize) -> Vec<T> {
+        let (start, size) = self.bounds(index);
+        let ndim = self.dims.len();
+        let run = size[0];
+        let nruns: usize = size[1..].iter().product();
+
+        let mut pixels = Vec::with_capacity(run * nruns);
+        let mut position = vec![0; ndim];
+        for _ in 0..nruns {
+            let mut offset = 0;
+            let mut stride = 1;
+            for axis in 0..ndim {
+                offset += (start[axis] + position[axis]) * stride;
+                stride *= self.dims[axis];
+            }
+            pixels.extend_from_slice(&image[offset..offset + run]);
+            Self::advance(&mut position, &size);
+        }
+        pixels
+    }
+
+    /// Advances the position of a run within a tile over all axes but `NAXIS1`.
+    fn advance(position: &mut [usize], size: &[usize]) {
+        for axis in 1..position.len() {
+            position[axis] += 1;
+            if position[axis] < size[axis] {
+                break;
+            }
+            position[axis] = 0;
+        }
+    }
+}
+
+/// Reads a tile-compressed image from the data unit at the cursor.
+///
+/// # Arguments
+/// - `f` (&mut R): The file or reader, positioned right after the header of the compressed BINTABLE.
+/// - `header` (&Header): The header of the compressed BINTABLE.
+///
+/// # Returns
+/// - `Result<(Header, ImageData, CompressionParams), FitsError>`: The reconstructed image header,
+///   the decompressed image and the compression parameters.
+///
+/// # Behavior
+/// - Reads the table rows and the heap, then decompresses the tiles in parallel.
+/// - Floating point images are restored from their quantized values with `ZSCALE` and `ZZERO`,
+///   and pixels equal to `ZBLANK` become NaN.
+pub fn read_compressed_image<R: Read + Seek + ?Sized>(f: &mut R, header: &Header) -> Result<(Header, ImageData, CompressionParams), FitsError> {
+    let params = CompressionParams::from_header(header)?;
+    let image_header = image_header_from_compressed(header)?;
+
+    let row_size = get_size(header, "NAXIS1")?;
+    let nrows = get_size(header, "NAXIS2")?;
+    let rows_size = row_size.checked_mul(nrows)
+        .ok_or_else(|| FitsError::InvalidHeader(format!("{} rows of {} bytes", nrows, row_size)))?;
+    let heap_start = get_size_or(header, "THEAP", rows_size)?;
+
+    let mut data = vec![0; data_size_from_header(header)?];
+    read_exact_or_truncated(f, &mut data)?;
+    pad_read_buffer_to_fits_block(f, data.len())?;
+
+    let table = TileTable {
+        columns: read_tableinfo_from_header(header)?,
+        row_size,
+        nrows,
+        data,
+        heap_start,
+    };
+
+    let zbitpix = header.get_int("ZBITPIX")?;
+    let znaxis = header.get_int("ZNAXIS")?;
+    let mut dims = Vec::new();
+    for i in 1..=znaxis {
+        dims.push(get_size(header, &format!("ZNAXIS{}", i))?);
+    }
+    let tiling = Tiling::new(dims.clone(), params.tile.clone());
+    if tiling.count() != table.nrows {
+        return Err(FitsError::InvalidHeader(format!(
+            "compressed image has {} rows for {} tiles", table.nrows, tiling.count()
+        )));
+    }
+
+    let ntiles = tiling.count();
+    let npix: usize = dims.iter().product();
+    let shape = dims;
+    let data = match zbitpix {
+        8 | 16 | 32 => {
+            let tiles: Vec<Vec<i32>> = (0..ntiles)
+                .into_par_iter()
+                .map(|row| match read_tile(&table, &params, zbitpix, row, &tiling.bounds(row).1)? {
+                    TilePixels::Quantized(values) => Ok(values),
+                    TilePixels::Raw(values) => Ok(values.into_iter().map(|v| v as i32).collect()),
+                })
+                .collect::<Result<_, FitsError>>()?;
+            match zbitpix {
+                8 => ImageData::U8(vec_to_ndarray(assemble(&tiling, &tiles, npix, |v| v as u8), shape)?),
+                16 => ImageData::I16(vec_to_ndarray(assemble(&tiling, &tiles, npix, |v| v as i16), shape)?),
+                _ => ImageData::I32(vec_to_ndarray(assemble(&tiling, &tiles, npix, |v| v), shape)?),
+            }
+        },
+        -32 | -64 => {
+            let tiles: Vec<Vec<f64>> = (0..ntiles)
+                .into_par_iter()
+                .map(|row| match read_tile(&table, &params, zbitpix, row, &tiling.bounds(row).1)? {
+                    TilePixels::Quantized(values) => unquantize_tile(&table, header, &params, row, &values),
+                    TilePixels::Raw(values) => Ok(values),
+                })
+                .collect::<Result<_, FitsError>>()?;
+            if zbitpix == -32 {
+                ImageData::F32(vec_to_ndarray(assemble(&tiling, &tiles, npix, |v| v as f32), shape)?)
+            } else {
+                ImageData::F64(vec_to_ndarray(assemble(&tiling, &tiles, npix, |v| v), shape)?)
+            }
+        },
+        _ => return Err(FitsError::InvalidBitpix(zbitpix)),
+    };
+
+    Ok((image_header, data, params))
+}
+
+/// Places all the tiles into a new image, converting each pixel with `convert`.
+fn assemble<S: Copy, T: Copy + Default>(tiling: &Tiling, tiles: &[Vec<S>], npix: usize, convert: impl Fn(S) -> T) -> Vec<T> {
+    let mut image = vec![T::default(); npix];
+    for (index, tile) in tiles.iter().enumerate() {
+        let pixels: Vec<T> = tile.iter().map(|&v| convert(v)).collect();
+        tiling.place(&mut image, index, &pixels);
+    }
+    image
+}
+
+/// The pixels of a tile as stored in the table.
+enum TilePixels {
+    /// Integer pixels, or quantized floating point pixels, decompressed from `COMPRESSED_DATA`.
+    Quantized(Vec<i32>),
+    /// Pixels stored without quantization: losslessly compressed floating point tiles, and
+    /// tiles that could not be quantized, kept in `GZIP_COMPRESSED_DATA` or `UNCOMPRESSED_DATA`.
+    Raw(Vec<f64>),
+}
+
+/// Reads the pixels of the tile stored in a row of the table.
+fn read_tile(table: &TileTable, params: &CompressionParams, zbitpix: i64, row: usize, size: &[usize]) -> Result<TilePixels, FitsError> {
+    let npix: usize = size.iter().product();
+    let letter = match zbitpix {
+        8 => 'B',
+        16 => 'I',
+        32 => 'J',
+        -32 => 'E',
+        -64 => 'D',
+        _ => return Err(FitsError::InvalidBitpix(zbitpix)),
+    };
+
+    let column = table.column("COMPRESSED_DATA")
+        .ok_or_else(|| FitsError::MissingKeyword("COMPRESSED_DATA column".to_string()))?;
+    let compressed = table.heap_array(column, row)?;
+    if !compressed.is_empty() {
+        if zbitpix < 0 && params.quantize_method == QuantizeMethod::NoQuantize {
+            if !params.algorithm.is_gzip() {
+                return Err(FitsError::UnsupportedDataType(format!(
+                    "unquantized floating point pixels compressed with {}", params.algorithm.as_zcmptype()
+                )));
+            }
+            let element = element_size(letter)?;
+            let shuffled = params.algorithm == CompressionType::Gzip2;
+            let bytes = gzip_decompress(compressed, npix * element, element, shuffled)?;
+            return Ok(TilePixels::Raw(be_values(&bytes, letter)?));
+        }
+        return decompress_tile(compressed, params, size).map(TilePixels::Quantized);
+    }
+
+    // CFITSIO stores tiles that cannot be quantized in GZIP_COMPRESSED_DATA or UNCOMPRESSED_DATA.
+    if let Some(column) = table.column("GZIP_COMPRESSED_DATA") {
+        let compressed = table.heap_array(column, row)?;
+        if !compressed.is_empty() {
+            let element = element_size(letter)?;
+            let bytes = gzip_decompress(compressed, npix * element, element, false)?;
+            return Ok(TilePixels::Raw(be_values(&bytes, letter)?));
+        }
+    }
+    if let Some(column) = table.column("UNCOMPRESSED_DATA") {
+        let raw = table.heap_array(column, row)?;
+        let letter = column.tform.trim().split_once(['P', 'Q']).and_then(|(_, rest)| rest.chars().next());
+        let letter = letter.ok_or_else(|| FitsError::InvalidTform(column.tform.clone()))?;
+        return Ok(TilePixels::Raw(be_values(raw, letter)?));
+    }
+    Err(FitsError::UnsupportedDataType(format!("tile {} is not stored in COMPRESSED_DATA", row)))
+}
+
+/// Converts big-endian values of the binary table type `letter` to `f64`.
+fn be_values(bytes: &[u8], letter: char) -> Result<Vec<f64>, FitsError> {
+    Ok(match letter {
+        'E' => bytes.chunks_exact(4).map(|b| f32::from_be_bytes(b.try_into().unwrap()) as f64).collect(),
+        'D' => bytes.chunks_exact(8).map(|b| f64::from_be_bytes(b.try_into().unwrap())).collect(),
+        'B' => bytes.iter().map(|&b| b as f64).collect(),
+        'I' => bytes.chunks_exact(2).map(|b| i16::from_be_bytes(b.try_into().unwrap()) as f64).collect(),
+        'J' => bytes.chunks_exact(4).map(|b| i32::from_be_bytes(b.try_into().unwrap()) as f64).collect(),
+        other => return Err(FitsError::InvalidTform(other.to_string())),
+    })
+}
+
+/// Decompresses the bytes of a tile.
+fn decompress_tile(compressed: &[u8], params: &CompressionParams, size: &[usize]) -> Result<Vec<i32>, FitsError> {
+    match params.algorithm {
+        CompressionType::Rice1 => rice_decompress(compressed, size.iter().product(), params.bytepix, params.block_size),
+        CompressionType::Gzip1 | CompressionType::Gzip2 => {
+            let shuffled = params.algorithm == CompressionType::Gzip2;
+            gzip_decompress_pixels(compressed, size.iter().product(), params.bytepix, shuffled)
+        },
+        CompressionType::Hcompress1 => {
+            let ny = size.get(1).copied().unwrap_or(1);
+            hdecompress(compressed, size[0], ny, params.hcomp_smooth, params.bytepix)
+        },
+        CompressionType::Plio1 => plio_decompress(compressed, size.iter().product()),
+    }
+}
+
+/// Restores the floating point values of a quantized tile with its `ZSCALE`, `ZZERO` and `ZBLANK`.
+fn unquantize_tile(table: &TileTable, header: &Header, params: &CompressionParams, row: usize, values: &[i32]) -> Result<Vec<f64>, FitsError> {
+    let zscale = match table.column("ZSCALE") {
+        Some(column) => table.float(column, row),
+        None => header.get_float("ZSCALE")?,
+    };
+    let zzero = match table.column("ZZERO") {
+        Some(column) => table.float(column, row),
+        None => header.get_float("ZZERO").unwrap_or(0.0),
+    };
+    let zblank = match table.column("ZBLANK") {
+        Some(column) => Some(table.int(column, row)),
+        None => header.get_int("ZBLANK").ok(),
+    };
+    unquantize(values, zscale, zzero, zblank, params.quantize_method, row + params.dither_seed as usize)
+}
+
+/// A tile ready to be written.
+///
+/// Tiles that cannot be quantized keep their gzipped pixels in `raw` and leave `compressed` empty.
+#[derive(Default)]
+struct EncodedTile {
+    compressed: Vec<u8>,
+    raw: Vec<u8>,
+    zscale: f64,
+    zzero: f64,
+    has_nulls: bool,
+}
+
+/// Returns the tile shape used to write an image of shape `dims`.
+///
+/// Axes missing from the tile shape default to 1, an empty tile shape to one row per tile
+/// (16 rows for HCOMPRESS_1), and tiles larger than the image are clipped, as CFITSIO does.
+fn tile_shape(params: &CompressionParams, dims: &[usize]) -> Result<Vec<usize>, FitsError> {
+    let mut tile = params.tile.clone();
+    if tile.is_empty() {
+        tile.push(dims[0]);
+        if params.algorithm == CompressionType::Hcompress1 && dims.len() > 1 {
+            tile.push(dims[1].min(16));
+        }
+    }
+    if tile.len() < dims.len() {
+        tile.resize(dims.len(), 1);
+    }
+    if tile.len() != dims.len() || tile.contains(&0) {
+        return Err(FitsError::InvalidValue(format!("tile {:?} for image of shape {:?}", tile, dims)));
+    }
+    let tile: Vec<usize> = tile.iter().zip(dims).map(|(t, d)| *t.min(d)).collect();
+    if params.algorithm == CompressionType::Hcompress1 && (dims.len() < 2 || tile[2..].iter().any(|&t| t != 1)) {
+        return Err(FitsError::InvalidValue(format!("HCOMPRESS_1 needs 2-dimensional tiles, got {:?}", tile)));
+    }
+    Ok(tile)
+}
+
+/// Compresses the integer pixels of a tile of shape `size`.
+fn compress_tile(pixels: &[i32], params: &CompressionParams, size: &[usize], bytepix: usize) -> Result<Vec<u8>, FitsError> {
+    match params.algorithm {
+        CompressionType::Rice1 => rice_compress(pixels, bytepix, params.block_size),
+        CompressionType::Gzip1 | CompressionType::Gzip2 => {
+            gzip_compress_pixels(pixels, bytepix, params.algorithm == CompressionType::Gzip2)
+        },
+        CompressionType::Hcompress1 => {
+            let null = pixels.contains(&NULL_VALUE).then_some(NULL_VALUE);
+            let scale = hcompress_scale(pixels, size[0], size[1], params.hcomp_scale, null)?;
+            hcompress(pixels, size[0], size[1], scale, bytepix)
+        },
+        CompressionType::Plio1 => plio_compress(pixels),
+    }
+}
+
+/// `quantize_f32` or `quantize_f64`.
+type QuantizeFn<T> = fn(&[T], usize, usize, f32, QuantizeMethod) -> Result<Option<QuantizedTile>, FitsError>;
+
+/// Quantizes and compresses the tiles of a floating point image.
+fn encode_float_tiles<T: Copy + Send + Sync, const N: usize>(
+    tiling: &Tiling,
+    pixels: &[T],
+    params: &CompressionParams,
+    quantize: QuantizeFn<T>,
+    to_be_bytes: fn(T) -> [u8; N],
+) -> Result<Vec<EncodedTile>, FitsError> {
+    (0..tiling.count())
+        .into_par_iter()
+        .map(|index| {
+            let tile_pixels = tiling.extract(pixels, index);
+            let bytes: Vec<u8> = tile_pixels.iter().flat_map(|&v| to_be_bytes(v)).collect();
+            if params.quantize_method == QuantizeMethod::NoQuantize {
+                let compressed = gzip_compress(&bytes, N, params.algorithm == CompressionType::Gzip2)?;
+                return Ok(EncodedTile { compressed, ..Default::default() });
+            }
+
+            let size = tiling.bounds(index).1;
+            let row = index + params.dither_seed as usize;
+            match quantize(&tile_pixels, size[0], row, params.quantize_level, params.quantize_method)? {
+                Some(tile) => Ok(EncodedTile {
+                    compressed: compress_tile(&tile.values, params, &size, 4)?,
+                    zscale: tile.zscale,
+                    zzero: tile.zzero,
+                    has_nulls: tile.has_nulls,
+                    ..Default::default()
+                }),
+                None => Ok(EncodedTile {
+                    raw: gzip_compress(&bytes, N, false)?,
+                    ..Default::default()
+                }),
+            }
+        })
+        .collect()
+}
+
+/// Writes an image as a tile-compressed BINTABLE.
+///
+/// # Arguments
+/// - `f` (&mut W): The file or writer.
+/// - `header` (&Header): The header of the image. Its structural keywords are replaced by the
+///   table and `Z*` keywords, the other cards are copied.
+/// - `data` (&ImageData): The image to compress.
+/// - `params` (&CompressionParams): The compression algorithm and tile shape.
+///
+/// # Returns
+/// - `Result<(), FitsError>`: `FitsError::InvalidValue` if the tile shape does not match the image
+///   or the dithering seed is out of range, `FitsError::UnsupportedDataType` for floating point
+///   images with `QuantizeMethod::NoQuantize` and an algorithm other than GZIP_1 or GZIP_2.
+///
+/// # Behavior
+/// - Compresses the tiles in parallel, then writes the header, one descriptor row per tile and
+///   the heap, padded to a full FITS block.
+/// - `I8`, `U16` and `U32` pixels are compressed as the signed integers that store them, with
+///   the `BZERO` of the header.
+/// - Floating point tiles are quantized with `quantize_level` and `quantize_method`, and their
+///   `ZSCALE` and `ZZERO` are written per row. NaN pixels are stored as `ZBLANK`. Tiles that
+///   cannot be quantized, such as constant tiles, are gzipped into `GZIP_COMPRESSED_DATA`.
+/// - With `QuantizeMethod::NoQuantize`, GZIP_1 and GZIP_2 compress the floating point pixels
+///   losslessly.
+pub fn write_compressed_image<W: Write + ?Sized>(f: &mut W, header: &Header, data: &ImageData, params: &CompressionParams) -> Result<(), FitsError> {
+    if let Some(stored) = ImageParser::offset_to_stored(data) {
+        return write_compressed_image(f, header, &stored, params);
+    }
+    let dims = data.get_shape();
+    if data.is_empty() || dims.is_empty() {
+        return Err(FitsError::InvalidValue("cannot compress an empty image".to_string()));
+    }
+    let tile = tile_shape(params, &dims)?;
+    let tiling = Tiling::new(dims.clone(), tile.clone());
+
+    let is_float = data.get_bitpix() < 0;
+    let quantized = is_float && params.quantize_method != QuantizeMethod::NoQuantize;
+    if is_float && !quantized && !params.algorithm.is_gzip() {
+        return Err(FitsError::UnsupportedDataType(format!(
+            "{} compression of floating point pixels without quantization", params.algorithm.as_zcmptype()
+        )));
+    }
+    let dithered = quantized && params.quantize_method != QuantizeMethod::NoDither;
+    if dithered && !(1..=N_RANDOM as i32).contains(&params.dither_seed) {
+        return Err(FitsError::InvalidValue(format!("ZDITHER0 = {}, must be between 1 and {}", params.dither_seed, N_RANDOM)));
+    }
+
+    let encode_ints = |pixels: Vec<i32>, bytepix: usize| -> Result<Vec<EncodedTile>, FitsError> {
+        (0..tiling.count())
+            .into_par_iter()
+            .map(|index| {
+                let tile_pixels = tiling.extract(&pixels, index);
+                let compressed = compress_tile(&tile_pixels, params, &tiling.bounds(index).1, bytepix)?;
+                Ok(EncodedTile { compressed, ..Default::default() })
+            })
+            .collect()
+    };
+    let (tiles, bytepix) = match data {
+        ImageData::U8(array) => (encode_ints(array.iter().map(|&v| v as i32).collect(), 1)?, 1),
+        ImageData::I16(array) => (encode_ints(array.iter().map(|&v| v as i32).collect(), 2)?, 2),
+        ImageData::I32(array) => (encode_ints(array.iter().copied().collect(), 4)?, 4),
+        ImageData::F32(array) => {
+            let pixels: Vec<f32> = array.iter().copied().collect();
+            (encode_float_tiles(&tiling, &pixels, params, quantize_f32, f32::to_be_bytes)?, 4)
+        },
+        ImageData::F64(array) => {
+            let pixels: Vec<f64> = array.iter().copied().collect();
+            (encode_float_tiles(&tiling, &pixels, params, quantize_f64, f64::to_be_bytes)?, 4)
+        },
+        _ => return Err(FitsError::UnsupportedDataType(
+            format!("{} compression of BITPIX = {}", params.algorithm.as_zcmptype(), data.get_bitpix())
+        )),
+    };
+
+    // PLIO_1 line lists are arrays of 16-bit words, the other algorithms produce bytes.
+    let (letter, element) = match params.algorithm {
+        CompressionType::Plio1 => ('I', 2),
+        _ => ('B', 1),
+    };
+    let has_raw = tiles.iter().any(|t| !t.raw.is_empty());
+    let has_nulls = tiles.iter().any(|t| t.has_nulls);
+    let heap_size: usize = tiles.iter().map(|t| t.compressed.len() + t.raw.len()).sum();
+    let max_len = tiles.iter().map(|t| t.compressed.len() / element).max().unwrap_or(0);
+    let max_raw_len = tiles.iter().map(|t| t.raw.len()).max().unwrap_or(0);
+
+    // Heaps beyond the reach of 32-bit offsets need 64-bit `Q` descriptors.
+    let wide = heap_size > i32::MAX as usize;
+    let descriptor = if wide { 'Q' } else { 'P' };
+    let mut columns = vec![("COMPRESSED_DATA", format!("1{}{}({})", descriptor, letter, max_len))];
+    if has_raw {
+        columns.push(("GZIP_COMPRESSED_DATA", format!("1{}B({})", descriptor, max_raw_len)));
+    }
+    let ndescriptors = columns.len();
+    if quantized {
+        columns.push(("ZSCALE", "1D".to_string()));
+        columns.push(("ZZERO", "1D".to_string()));
+    }
+    let row_size = ndescriptors * if wide { 16 } else { 8 } + if quantized { 16 } else { 0 };
+
+    let mut table_header = Header::new();
+    let mut add = |keyword: &str, value: String, comment: &str| {
+        table_header.add_card(&Card::new(keyword.to_string(), value, Some(comment.to_string())));
+    };
+    add("XTENSION", "BINTABLE".to_string(), "binary table extension");
+    add("BITPIX", "8".to_string(), "8-bit bytes");
+    add("NAXIS", "2".to_string(), "2-dimensional binary table");
+    add("NAXIS1", row_size.to_string(), "width of table in bytes");
+    add("NAXIS2", tiling.count().to_string(), "number of rows in table");
+    add("PCOUNT", heap_size.to_string(), "size of special data area");
+    add("GCOUNT", "1".to_string(), "one data group (required keyword)");
+    add("TFIELDS", columns.len().to_string(), "number of fields in each row");
+    for (i, (name, tform)) in columns.iter().enumerate() {
+        add(&format!("TTYPE{}", i + 1), name.to_string(), &format!("label for field {}", i + 1));
+        add(&format!("TFORM{}", i + 1), tform.clone(), "data format of field");
+    }
+    add("ZIMAGE", "T".to_string(), "extension contains compressed image");
+    add("ZTENSION", "IMAGE".to_string(), "extension type of the image");
+    add("ZBITPIX", data.get_bitpix().to_string(), "data type of original image");
+    add("ZNAXIS", dims.len().to_string(), "dimension of original image");
+    for (i, d) in dims.iter().enumerate() {
+        add(&format!("ZNAXIS{}", i + 1), d.to_string(), &format!("length of original image axis {}", i + 1));
+    }
+    for (i, t) in tile.iter().enumerate() {
+        add(&format!("ZTILE{}", i + 1), t.to_string(), &format!("size of tiles to be compressed along axis {}", i + 1));
+    }
+    add("ZPCOUNT", "0".to_string(), "number of parameters of the image");
+    add("ZGCOUNT", "1".to_string(), "number of groups of the image");
+    add("ZCMPTYPE", params.algorithm.as_zcmptype().to_string(), "compression algorithm");
+    let mut zvals = match params.algorithm {
+        CompressionType::Rice1 => vec![
+            ("BLOCKSIZE", params.block_size.to_string(), "compression block size"),
+            ("BYTEPIX", bytepix.to_string(), "bytes per pixel (1, 2, 4, or 8)"),
+        ],
+        CompressionType::Hcompress1 => vec![
+            ("SCALE", format!("{:?}", params.hcomp_scale), "HCOMPRESS scale factor"),
+            ("SMOOTH", (params.hcomp_smooth as i32).to_string(), "HCOMPRESS smooth option"),
+        ],
+        CompressionType::Gzip1 | CompressionType::Gzip2 | CompressionType::Plio1 => vec![],
+    };
+    if quantized {
+        zvals.push(("NOISEBIT", format!("{:?}", params.quantize_level), "floating point quantization level"));
+    }
+    for (i, (name, value, comment)) in zvals.into_iter().enumerate() {
+        add(&format!("ZNAME{}", i + 1), name.to_string(), comment);
+        add(&format!("ZVAL{}", i + 1), value, comment);
+    }
+    if is_float {
+        add("ZQUANTIZ", params.quantize_method.as_zquantiz().to_string(), "pixel quantization algorithm");
+    }
+    if dithered {
+        add("ZDITHER0", params.dither_seed.to_string(), "dithering offset when quantizing floats");
+    }
+    if has_nulls {
+        add("ZBLANK", NULL_VALUE.to_string(), "null value in the compressed integer array");
+    }
+    for card in header.iter() {
+        if !is_compression_keyword(&card.keyword) && card.keyword != "SIMPLE" && card.keyword != "EXTEND" {
+            table_header.add_card(card);
+        }
+    }
+    table_header.write_to_buffer(f)?;
+
+    let mut buffer = Vec::with_capacity(row_size * tiles.len() + heap_size);
+    let mut offset = 0;
+    for tile in &tiles {
+        let mut arrays = vec![(tile.compressed.len() / element, tile.compressed.len())];
+        if has_raw {
+            arrays.push((tile.raw.len(), tile.raw.len()));
+        }
+        for (count, nbytes) in arrays {
+            if wide {
+                buffer.extend_from_slice(&(count as i64).to_be_bytes());
+                buffer.extend_from_slice(&(offset as i64).to_be_bytes());
+            } else {
+                buffer.extend_from_slice(&(count as i32).to_be_bytes());
+                buffer.extend_from_slice(&(offset as i32).to_be_bytes());
+            }
+            offset += nbytes;
+        }
+        if quantized {
+            buffer.extend_from_slice(&tile.zscale.to_be_bytes());
+            buffer.extend_from_slice(&tile.zzero.to_be_bytes());
+        }
+    }
+    for tile in &tiles {
+        buffer.extend_from_slice(&tile.compressed);
+        buffer.extend_from_slice(&tile.raw);
+    }
+    let remainder = buffer.len() % 2880;
+    if remainder != 0 {
+        buffer.resize(buffer.len() + 2880 - remainder, 0);
+    }
+    f.write_all(&buffer)?;
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! GZIP_1 and GZIP_2 tile compression, implemented with `flate2`.
+//!
+//! Tiles are gzip streams of the big-endian pixels. GZIP_2 shuffles the bytes first, so that
+//! the most significant bytes of all the pixels come first, which usually compresses better.
+
+use std::io::{Read, Write};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::io::errors::FitsError;
+use crate::io::utils::GZIP_MAGIC;
+
+/// Groups the bytes of the elements by significance, most significant first.
+fn shuffle(bytes: &[u8], element: usize) -> Vec<u8> {
+    let n = bytes.len() / element;
+    let mut out = vec![0u8; bytes.len()];
+    for (i, value) in bytes.chunks_exact(element).enumerate() {
+        for (j, &b) in value.iter().enumerate() {
+            out[j * n + i] = b;
+        }
+    }
+    out
+}
+
+/// Undoes `shuffle`.
+fn unshuffle(bytes: &[u8], element: usize) -> Vec<u8> {
+    let n = bytes.len() / element;
+    let mut out = vec![0u8; bytes.len()];
+    for (i, value) in out.chunks_exact_mut(element).enumerate() {
+        for (j, b) in value.iter_mut().enumerate() {
+            *b = bytes[j * n + i];
+        }
+    }
+    out
+}
+
+/// Compresses the big-endian bytes of a tile.
+///
+/// # Arguments
+/// - `bytes` (&[u8]): The pixels of the tile, big-endian.
+/// - `element` (usize): The number of bytes per pixel.
+/// - `shuffled` (bool): Whether to shuffle the bytes first (GZIP_2).
+///
+/// # Returns
+/// - `Result<Vec<u8>, FitsError>`: The gzip stream.
+pub fn gzip_compress(bytes: &[u8], element: usize, shuffled: bool) -> Result<Vec<u8>, FitsError> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(bytes.len() / 2 + 64), Compression::default());
+    if shuffled && element > 1 {
+        encoder.write_all(&shuffle(bytes, element))?;
+    } else {
+        encoder.write_all(bytes)?;
+    }
+    Ok(encoder.finish()?)
+}
+
+/// Decompresses a tile, returning its big-endian bytes.
+///
+/// # Arguments
+/// - `compressed` (&[u8]): The gzip (or zlib) stream of the tile.
+/// - `nbytes` (usize): The expected number of bytes of the tile.
+/// - `element` (usize): The number of bytes per pixel.
+/// - `shuffled` (bool): Whether the bytes were shuffled (GZIP_2).
+///
+/// # Returns
+/// - `Result<Vec<u8>, FitsError>`: The bytes of the tile, `FitsError::InvalidValue` if the stream
+///   is corrupted or does not hold `nbytes` bytes.
+pub fn gzip_decompress(compressed: &[u8], nbytes: usize, element: usize, shuffled: bool) -> Result<Vec<u8>, FitsError> {
+    let mut bytes = Vec::with_capacity(nbytes);
+    let result = if compressed.starts_with(&GZIP_MAGIC) {
+        GzDecoder::new(compressed).read_to_end(&mut bytes)
+    } else {
+        ZlibDecoder::new(compressed).read_to_end(&mut bytes)
+    };
+    result.map_err(|e| FitsError::InvalidValue(format!("GZIP tile could not be decompressed ({})", e)))?;
+    if bytes.len() != nbytes {
+        return Err(FitsError::InvalidValue(format!("GZIP tile of {} bytes where {} were expected", bytes.len(), nbytes)));
+    }
+
+    if shuffled && element > 1 {
+        Ok(unshuffle(&bytes, element))
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// Compresses a tile of integer pixels stored with `bytepix` bytes per pixel.
+///
+/// # Arguments
+/// - `pixels` (&[i32]): The pixels of the tile. With `bytepix` 1 they must fit in a `u8`,
+///   with `bytepix` 2 in an `i16`.
+/// - `bytepix` (usize): The number of bytes per pixel, 1, 2 or 4.
+/// - `shuffled` (bool): Whether to shuffle the bytes first (GZIP_2).
+///
+/// # Returns
+/// - `Result<Vec<u8>, FitsError>`: The gzip stream.
+pub fn gzip_compress_pixels(pixels: &[i32], bytepix: usize, shuffled: bool) -> Result<Vec<u8>, FitsError> {
+    let bytes: Vec<u8> = match bytepix {
+        1 => pixels.iter().map(|&v| v as u8).collect(),
+        2 => pixels.iter().flat_map(|&v| (v as i16).to_be_bytes()).collect(),
+        4 => pixels.iter().flat_map(|&v| v.to_be_bytes()).collect(),
+        _ => return Err(FitsError::InvalidValue(format!("GZIP BYTEPIX = {}", bytepix))),
+    };
+    gzip_compress(&bytes, bytepix, shuffled)
+}
+
+/// Decompresses a tile of `npix` integer pixels stored with `bytepix` bytes per pixel.
+///
+/// # Returns
+/// - `Result<Vec<i32>, FitsError>`: The pixels, widened to `i32`. Pixels stored with 1 byte are
+///   unsigned, pixels stored with 2 or 4 bytes are signed.
+pub fn gzip_decompress_pixels(compressed: &[u8], npix: usize, bytepix: usize, shuffled: bool) -> Result<Vec<i32>, FitsError> {
+    let bytes = gzip_decompress(compressed, npix * bytepix, bytepix, shuffled)?;
+    match bytepix {
+        1 => Ok(bytes.into_iter().map(|v| v as i32).collect()),
+        2 => Ok(bytes.chunks_exact(2).map(|b| i16::from_be_bytes([b[0], b[1]]) as i32).collect()),
+        4 => Ok(bytes.chunks_exact(4).map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]])).collect()),
+        _ => Err(FitsError::InvalidValue(format!("GZIP BYTEPIX = {}", bytepix))),
+    }
+}
@@ -0,0 +1,151 @@
+//! Safe wrappers around the HCOMPRESS_1 routines of `fits_hcompress.c` and `fits_hdecompress.c`.
+//!
+//! The C code keeps its bit buffers and stream position in static variables, so every call
+//! goes through a global lock and tiles are coded one at a time.
+
+use std::os::raw::c_long;
+use std::ptr::null_mut;
+use std::sync::Mutex;
+
+use crate::io::compression_algorithms::{fits_hcompress, fits_hcompress64, fits_hdecompress, fits_hdecompress64, fits_img_stats_int};
+use crate::io::errors::FitsError;
+
+static HCOMPRESS_LOCK: Mutex<()> = Mutex::new(());
+
+/// The two magic bytes that start an HCOMPRESS_1 stream.
+const MAGIC: [u8; 2] = [0xDD, 0x99];
+
+/// The size of the stream header: magic, both axis lengths, scale, pixel sum and bit planes.
+const STREAM_HEADER_SIZE: usize = 2 + 4 + 4 + 4 + 8 + 3;
+
+/// Returns the integer scale used to compress a tile, from the `SCALE` parameter.
+///
+/// # Arguments
+/// - `pixels` (&[i32]): The pixels of the tile, `NAXIS1` varying fastest.
+/// - `nx` (usize): The length of the tile along `NAXIS1`.
+/// - `ny` (usize): The length of the tile along `NAXIS2`.
+/// - `scale` (f32): The `SCALE` parameter. Positive values are the scale itself and negative
+///   values are relative to the noise of the tile.
+/// - `null` (Option<i32>): The value of the undefined pixels, left out of the noise estimate.
+///
+/// # Returns
+/// - `Result<i32, FitsError>`: The scale, rounded to the nearest integer.
+///
+/// # Behavior
+/// - As in `imcomp_compress_tile` of CFITSIO, a negative `SCALE` gives `-SCALE` times the 3rd
+///   order noise estimate of `fits_img_stats_int`, so that the loss follows the noise of each tile.
+pub fn hcompress_scale(pixels: &[i32], nx: usize, ny: usize, scale: f32, null: Option<i32>) -> Result<i32, FitsError> {
+    if scale >= 0.0 {
+        return Ok(scale.round() as i32);
+    }
+
+    let mut input = pixels.to_vec();
+    let (mut noise3, mut status) = (0.0, 0);
+    unsafe {
+        fits_img_stats_int(
+            input.as_mut_ptr(), nx as c_long, ny as c_long, null.is_some() as i32, null.unwrap_or(0),
+            null_mut(), null_mut(), null_mut(), null_mut(), null_mut(), null_mut(), null_mut(), &mut noise3, null_mut(), &mut status,
+        )
+    };
+    if status != 0 {
+        return Err(FitsError::InvalidValue(format!("noise of HCOMPRESS_1 tile could not be estimated (status {})", status)));
+    }
+    Ok((-scale as f64 * noise3).round() as i32)
+}
+
+/// Compresses a 2-dimensional tile with HCOMPRESS_1.
+///
+/// # Arguments
+/// - `pixels` (&[i32]): The pixels of the tile, `NAXIS1` varying fastest.
+/// - `nx` (usize): The length of the tile along `NAXIS1`.
+/// - `ny` (usize): The length of the tile along `NAXIS2`.
+/// - `scale` (i32): The quantization scale, 0 for lossless compression.
+/// - `bytepix` (usize): The number of bytes per pixel of the image. Tiles of 4-byte pixels are
+///   transformed with 64-bit integers, as CFITSIO does, so that the H-transform cannot overflow.
+///
+/// # Returns
+/// - `Result<Vec<u8>, FitsError>`: The compressed bytes of the tile.
+pub fn hcompress(pixels: &[i32], nx: usize, ny: usize, scale: i32, bytepix: usize) -> Result<Vec<u8>, FitsError> {
+    if nx * ny != pixels.len() {
+        return Err(FitsError::InvalidValue(format!("HCOMPRESS_1 tile of {}x{} with {} pixels", nx, ny, pixels.len())));
+    }
+
+    // The transformed coefficients rarely take more room than the raw pixels.
+    let mut out = vec![0u8; pixels.len() * bytepix.max(4) * 2 + 1024];
+    let mut nbytes = out.len() as c_long;
+    let mut status = 0;
+
+    // The C routines take the axes in reverse order: their `ny` is the fastest axis.
+    {
+        let _guard = HCOMPRESS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if bytepix <= 2 {
+            let mut input = pixels.to_vec();
+            unsafe { fits_hcompress(input.as_mut_ptr(), nx as i32, ny as i32, scale, out.as_mut_ptr().cast(), &mut nbytes, &mut status) };
+        } else {
+            let mut input: Vec<i64> = pixels.iter().map(|&v| v as i64).collect();
+            unsafe { fits_hcompress64(input.as_mut_ptr(), nx as i32, ny as i32, scale, out.as_mut_ptr().cast(), &mut nbytes, &mut status) };
+        }
+    }
+
+    if status != 0 {
+        return Err(FitsError::InvalidValue(format!("HCOMPRESS_1 tile could not be compressed (status {})", status)));
+    }
+    out.truncate(nbytes as usize);
+    Ok(out)
+}
+
+/// Decompresses a 2-dimensional HCOMPRESS_1 tile.
+///
+/// # Arguments
+/// - `compressed` (&[u8]): The compressed bytes of the tile.
+/// - `nx` (usize): The length of the tile along `NAXIS1`.
+/// - `ny` (usize): The length of the tile along `NAXIS2`.
+/// - `smooth` (bool): Whether to smooth the image while undoing the H-transform (`SMOOTH`).
+/// - `bytepix` (usize): The number of bytes per pixel of the image.
+///
+/// # Returns
+/// - `Result<Vec<i32>, FitsError>`: The decompressed pixels, `NAXIS1` varying fastest. Pixels of
+///   1 and 2-byte images are clipped to the range of their type.
+pub fn hdecompress(compressed: &[u8], nx: usize, ny: usize, smooth: bool, bytepix: usize) -> Result<Vec<i32>, FitsError> {
+    // The decoder does not know the length of its input, so check the stream header before
+    // handing it a buffer, and leave some slack after the stream.
+    if compressed.len() < STREAM_HEADER_SIZE || compressed[0..2] != MAGIC {
+        return Err(FitsError::InvalidValue("HCOMPRESS_1 tile has no valid stream header".to_string()));
+    }
+    let stream_nx = i32::from_be_bytes(compressed[6..10].try_into().unwrap());
+    let stream_ny = i32::from_be_bytes(compressed[2..6].try_into().unwrap());
+    if stream_nx as usize != nx || stream_ny as usize != ny {
+        return Err(FitsError::InvalidValue(format!(
+            "HCOMPRESS_1 tile of {}x{} where {}x{} was expected", stream_nx, stream_ny, nx, ny
+        )));
+    }
+
+    let mut input = compressed.to_vec();
+    input.resize(compressed.len() + 64, 0);
+    let npix = nx * ny;
+    let (mut cnx, mut cny, mut scale, mut status) = (0, 0, 0, 0);
+
+    let pixels: Vec<i32> = {
+        let _guard = HCOMPRESS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if bytepix <= 2 {
+            let mut out = vec![0i32; npix];
+            unsafe { fits_hdecompress(input.as_mut_ptr().cast(), smooth as i32, out.as_mut_ptr(), &mut cnx, &mut cny, &mut scale, &mut status) };
+            out
+        } else {
+            let mut out = vec![0i64; npix];
+            unsafe { fits_hdecompress64(input.as_mut_ptr().cast(), smooth as i32, out.as_mut_ptr(), &mut cnx, &mut cny, &mut scale, &mut status) };
+            // The 64-bit routine packs its result as `int`s at the start of the buffer.
+            unsafe { std::slice::from_raw_parts(out.as_ptr().cast::<i32>(), npix) }.to_vec()
+        }
+    };
+
+    if status != 0 {
+        return Err(FitsError::InvalidValue(format!("HCOMPRESS_1 tile could not be decompressed (status {})", status)));
+    }
+
+    Ok(match bytepix {
+        1 => pixels.into_iter().map(|v| v.clamp(0, u8::MAX as i32)).collect(),
+        2 => pixels.into_iter().map(|v| v.clamp(i16::MIN as i32, i16::MAX as i32)).collect(),
+        _ => pixels,
+    })
+}
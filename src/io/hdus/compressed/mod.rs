@@ -0,0 +1,8 @@
+pub mod compressed;
+pub mod gzip;
+pub mod hcompress;
+pub mod plio;
+pub mod quantize;
+pub mod rice;
+
+pub use self::compressed::{CompressionParams, CompressionType, QuantizeMethod};
@@ -0,0 +1,69 @@
+//! Safe wrappers around the PLIO_1 routines of `pliocomp.c`.
+//!
+//! PLIO_1 encodes a tile as an IRAF line list of 16-bit words, stored big-endian in the heap.
+//! It is meant for masks and only supports pixel values from 0 to 2^24 - 1.
+
+use crate::io::compression_algorithms::{pl_l2pi, pl_p2li};
+use crate::io::errors::FitsError;
+
+/// The largest pixel value a line list can hold.
+pub const PLIO_MAX_VALUE: i32 = (1 << 24) - 1;
+
+/// The number of words of the line list header.
+const LIST_HEADER_SIZE: usize = 7;
+
+/// Compresses a tile of pixels with PLIO_1.
+///
+/// # Arguments
+/// - `pixels` (&[i32]): The pixels of the tile.
+///
+/// # Returns
+/// - `Result<Vec<u8>, FitsError>`: The line list as big-endian 16-bit words, or
+///   `FitsError::InvalidValue` if a pixel is negative or larger than `PLIO_MAX_VALUE`.
+pub fn plio_compress(pixels: &[i32]) -> Result<Vec<u8>, FitsError> {
+    if let Some(v) = pixels.iter().find(|&&v| !(0..=PLIO_MAX_VALUE).contains(&v)) {
+        return Err(FitsError::InvalidValue(format!("PLIO_1 cannot store pixel value {}", v)));
+    }
+
+    // Each pixel costs at most a value change of two words, a zero run and a data run.
+    let mut input = pixels.to_vec();
+    let mut list = vec![0i16; pixels.len() * 4 + LIST_HEADER_SIZE + 1];
+    let nwords = unsafe { pl_p2li(input.as_mut_ptr(), 1, list.as_mut_ptr(), pixels.len() as i32) };
+    if nwords < 0 {
+        return Err(FitsError::InvalidValue(format!("PLIO_1 tile could not be compressed (status {})", nwords)));
+    }
+
+    Ok(list[..nwords as usize].iter().flat_map(|w| w.to_be_bytes()).collect())
+}
+
+/// Decompresses a PLIO_1 tile of `npix` pixels.
+///
+/// # Arguments
+/// - `compressed` (&[u8]): The line list as big-endian 16-bit words.
+/// - `npix` (usize): The number of pixels in the tile.
+///
+/// # Returns
+/// - `Result<Vec<i32>, FitsError>`: The decompressed pixels.
+pub fn plio_decompress(compressed: &[u8], npix: usize) -> Result<Vec<i32>, FitsError> {
+    let mut list: Vec<i16> = compressed.chunks_exact(2).map(|w| i16::from_be_bytes([w[0], w[1]])).collect();
+
+    // The decoder trusts the length stored in the list header, check it against the heap array.
+    if list.len() < LIST_HEADER_SIZE {
+        return Err(FitsError::InvalidValue("PLIO_1 tile is shorter than its line list header".to_string()));
+    }
+    let length = if list[2] > 0 {
+        list[2] as usize
+    } else {
+        ((list[4] as usize) << 15) + list[3] as usize
+    };
+    if length > list.len() {
+        return Err(FitsError::TruncatedData { expected: length * 2, found: compressed.len() });
+    }
+
+    let mut pixels = vec![0i32; npix];
+    let status = unsafe { pl_l2pi(list.as_mut_ptr(), 1, pixels.as_mut_ptr(), npix as i32) };
+    if status != npix as i32 {
+        return Err(FitsError::InvalidValue(format!("PLIO_1 tile could not be decompressed (status {})", status)));
+    }
+    Ok(pixels)
+}
@@ -0,0 +1,171 @@
+//! Quantization of floating point tiles, wrapping `fits_quantize_float` and
+//! `fits_quantize_double` of `quantize.c`.
+//!
+//! Floating point pixels are stored as integers `round((value - ZZERO) / ZSCALE)`, optionally
+//! dithered with the random sequence shared with CFITSIO, so that decompressed pixels match
+//! the ones restored by CFITSIO bit for bit.
+
+use std::os::raw::c_long;
+use std::sync::OnceLock;
+
+use crate::io::compression_algorithms::{fits_init_randoms, fits_quantize_double, fits_quantize_float, fits_rand_value};
+use crate::io::errors::FitsError;
+use crate::io::hdus::compressed::compressed::QuantizeMethod;
+
+/// The quantized value of undefined pixels, written to `ZBLANK`.
+pub const NULL_VALUE: i32 = -2147483647;
+
+/// The quantized value of pixels exactly equal to zero with `SUBTRACTIVE_DITHER_2`.
+pub const ZERO_VALUE: i32 = -2147483646;
+
+/// The length of the random sequence used for dithering.
+pub const N_RANDOM: usize = 10000;
+
+/// Stand-ins for NaN pixels while quantizing, as used by CFITSIO.
+const FLOAT_NULL_VALUE: f32 = -9.11912E-36;
+const DOUBLE_NULL_VALUE: f64 = -9.1191291391491E-36;
+
+/// A quantized tile.
+///
+/// # Fields
+/// - `values` (Vec<i32>): The quantized pixels.
+/// - `zscale` (f64): The scale of the quantized values (`ZSCALE`).
+/// - `zzero` (f64): The offset of the quantized values (`ZZERO`).
+/// - `has_nulls` (bool): Whether some pixels are NaN and were stored as `NULL_VALUE`.
+pub struct QuantizedTile {
+    pub values: Vec<i32>,
+    pub zscale: f64,
+    pub zzero: f64,
+    pub has_nulls: bool,
+}
+
+/// Returns the random sequence used for dithering, generating it on first use.
+pub fn dither_randoms() -> Result<&'static [f32], FitsError> {
+    static RANDOMS: OnceLock<Option<&'static [f32]>> = OnceLock::new();
+    RANDOMS.get_or_init(|| unsafe {
+        if fits_init_randoms() != 0 {
+            return None;
+        }
+        let values = *std::ptr::addr_of!(fits_rand_value);
+        Some(std::slice::from_raw_parts(values, N_RANDOM))
+    }).ok_or_else(|| FitsError::InvalidValue("the dithering random sequence could not be generated".to_string()))
+}
+
+/// Returns the value the C routines take as the tile row: 0 disables dithering.
+fn dither_row(method: QuantizeMethod, row: usize) -> c_long {
+    match method {
+        QuantizeMethod::NoQuantize | QuantizeMethod::NoDither => 0,
+        _ => row as c_long,
+    }
+}
+
+/// Returns the CFITSIO code of a quantization method.
+fn dither_method(method: QuantizeMethod) -> i32 {
+    match method {
+        QuantizeMethod::NoQuantize | QuantizeMethod::NoDither => -1,
+        QuantizeMethod::SubtractiveDither1 => 1,
+        QuantizeMethod::SubtractiveDither2 => 2,
+    }
+}
+
+/// Quantizes a tile of `f32` pixels.
+///
+/// # Arguments
+/// - `pixels` (&[f32]): The pixels of the tile. NaN pixels are stored as `NULL_VALUE`.
+/// - `nx` (usize): The length of the rows of the tile, used to estimate the noise.
+/// - `row` (usize): The dithering row, i.e. the tile number plus `ZDITHER0`, counting from 1.
+/// - `level` (f32): The quantization level: the noise is divided into `level` steps. A negative
+///   level is the absolute size of a step, and 0 selects the default of 4 steps.
+/// - `method` (QuantizeMethod): The quantization method.
+///
+/// # Returns
+/// - `Result<Option<QuantizedTile>, FitsError>`: The quantized tile, or `None` if the tile cannot
+///   be quantized, for instance because it has no noise.
+pub fn quantize_f32(pixels: &[f32], nx: usize, row: usize, level: f32, method: QuantizeMethod) -> Result<Option<QuantizedTile>, FitsError> {
+    if !matches!(method, QuantizeMethod::NoQuantize | QuantizeMethod::NoDither) {
+        dither_randoms()?;
+    }
+    let has_nulls = pixels.iter().any(|v| v.is_nan());
+    let mut input: Vec<f32> = pixels.iter().map(|&v| if v.is_nan() { FLOAT_NULL_VALUE } else { v }).collect();
+    let mut values = vec![0i32; pixels.len()];
+    let (mut zscale, mut zzero, mut imin, mut imax) = (0.0, 0.0, 0, 0);
+    let ny = pixels.len() / nx.max(1);
+
+    let quantized = unsafe {
+        fits_quantize_float(
+            dither_row(method, row), input.as_mut_ptr(), nx as c_long, ny as c_long, has_nulls as i32, FLOAT_NULL_VALUE,
+            level, dither_method(method), values.as_mut_ptr(), &mut zscale, &mut zzero, &mut imin, &mut imax,
+        )
+    };
+    quantized_tile(quantized, values, zscale, zzero, has_nulls)
+}
+
+/// Quantizes a tile of `f64` pixels, see `quantize_f32`.
+pub fn quantize_f64(pixels: &[f64], nx: usize, row: usize, level: f32, method: QuantizeMethod) -> Result<Option<QuantizedTile>, FitsError> {
+    if !matches!(method, QuantizeMethod::NoQuantize | QuantizeMethod::NoDither) {
+        dither_randoms()?;
+    }
+    let has_nulls = pixels.iter().any(|v| v.is_nan());
+    let mut input: Vec<f64> = pixels.iter().map(|&v| if v.is_nan() { DOUBLE_NULL_VALUE } else { v }).collect();
+    let mut values = vec![0i32; pixels.len()];
+    let (mut zscale, mut zzero, mut imin, mut imax) = (0.0, 0.0, 0, 0);
+    let ny = pixels.len() / nx.max(1);
+
+    let quantized = unsafe {
+        fits_quantize_double(
+            dither_row(method, row), input.as_mut_ptr(), nx as c_long, ny as c_long, has_nulls as i32, DOUBLE_NULL_VALUE,
+            level, dither_method(method), values.as_mut_ptr(), &mut zscale, &mut zzero, &mut imin, &mut imax,
+        )
+    };
+    quantized_tile(quantized, values, zscale, zzero, has_nulls)
+}
+
+fn quantized_tile(quantized: i32, values: Vec<i32>, zscale: f64, zzero: f64, has_nulls: bool) -> Result<Option<QuantizedTile>, FitsError> {
+    match quantized {
+        1 => Ok(Some(QuantizedTile { values, zscale, zzero, has_nulls })),
+        0 => Ok(None),
+        status => Err(FitsError::InvalidValue(format!("tile could not be quantized (status {})", status))),
+    }
+}
+
+/// Restores the floating point pixels of a quantized tile.
+///
+/// # Arguments
+/// - `values` (&[i32]): The quantized pixels.
+/// - `zscale` (f64): The scale of the quantized values (`ZSCALE`).
+/// - `zzero` (f64): The offset of the quantized values (`ZZERO`).
+/// - `zblank` (Option<i64>): The quantized value of undefined pixels (`ZBLANK`), restored as NaN.
+/// - `method` (QuantizeMethod): The quantization method (`ZQUANTIZ`).
+/// - `row` (usize): The dithering row, i.e. the tile number plus `ZDITHER0`, counting from 1.
+///
+/// # Returns
+/// - `Result<Vec<f64>, FitsError>`: The restored pixels. Casting them to `f32` gives the values
+///   CFITSIO restores for `ZBITPIX = -32`.
+pub fn unquantize(values: &[i32], zscale: f64, zzero: f64, zblank: Option<i64>, method: QuantizeMethod, row: usize) -> Result<Vec<f64>, FitsError> {
+    if matches!(method, QuantizeMethod::NoQuantize | QuantizeMethod::NoDither) {
+        return Ok(values.iter().map(|&v| {
+            if Some(v as i64) == zblank { f64::NAN } else { v as f64 * zscale + zzero }
+        }).collect());
+    }
+
+    let randoms = dither_randoms()?;
+    let mut iseed = (row.max(1) - 1) % N_RANDOM;
+    let mut next = (randoms[iseed] * 500.0) as usize;
+    Ok(values.iter().map(|&v| {
+        let value = if Some(v as i64) == zblank {
+            f64::NAN
+        } else if method == QuantizeMethod::SubtractiveDither2 && v == ZERO_VALUE {
+            0.0
+        } else {
+            (v as f64 - randoms[next] as f64 + 0.5) * zscale + zzero
+        };
+
+        // Every pixel, null or not, consumes a random number.
+        next += 1;
+        if next == N_RANDOM {
+            iseed = (iseed + 1) % N_RANDOM;
+            next = (randoms[iseed] * 500.0) as usize;
+        }
+        value
+    }).collect())
+}
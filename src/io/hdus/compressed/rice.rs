@@ -0,0 +1,89 @@
+//! Safe wrappers around the RICE_1 routines of `ricecomp.c`.
+
+use crate::io::compression_algorithms::{
+    fits_rcomp, fits_rcomp_byte, fits_rcomp_short, fits_rdecomp, fits_rdecomp_byte, fits_rdecomp_short,
+};
+use crate::io::errors::FitsError;
+
+/// Decompresses a RICE_1 tile of `npix` pixels stored with `bytepix` bytes per pixel.
+///
+/// # Arguments
+/// - `compressed` (&[u8]): The compressed bytes of the tile.
+/// - `npix` (usize): The number of pixels in the tile.
+/// - `bytepix` (usize): The number of bytes per pixel (`BYTEPIX`), 1, 2 or 4.
+/// - `block_size` (usize): The number of pixels per block (`BLOCKSIZE`).
+///
+/// # Returns
+/// - `Result<Vec<i32>, FitsError>`: The decompressed pixels, widened to `i32`.
+///   Pixels stored with 1 byte are unsigned, pixels stored with 2 or 4 bytes are signed.
+pub fn rice_decompress(compressed: &[u8], npix: usize, bytepix: usize, block_size: usize) -> Result<Vec<i32>, FitsError> {
+    // The C routines take a mutable pointer to the input even though they only read it.
+    let mut input = compressed.to_vec();
+    let clen = input.len() as i32;
+    let nx = npix as i32;
+    let nblock = block_size as i32;
+
+    let (status, pixels) = match bytepix {
+        1 => {
+            let mut out = vec![0u8; npix];
+            let status = unsafe { fits_rdecomp_byte(input.as_mut_ptr(), clen, out.as_mut_ptr(), nx, nblock) };
+            (status, out.into_iter().map(|v| v as i32).collect())
+        },
+        2 => {
+            let mut out = vec![0u16; npix];
+            let status = unsafe { fits_rdecomp_short(input.as_mut_ptr(), clen, out.as_mut_ptr(), nx, nblock) };
+            (status, out.into_iter().map(|v| v as i16 as i32).collect())
+        },
+        4 => {
+            let mut out = vec![0u32; npix];
+            let status = unsafe { fits_rdecomp(input.as_mut_ptr(), clen, out.as_mut_ptr(), nx, nblock) };
+            (status, out.into_iter().map(|v| v as i32).collect())
+        },
+        _ => return Err(FitsError::InvalidValue(format!("RICE_1 BYTEPIX = {}", bytepix))),
+    };
+
+    if status != 0 {
+        return Err(FitsError::InvalidValue(format!("RICE_1 tile could not be decompressed (status {})", status)));
+    }
+    Ok(pixels)
+}
+
+/// Compresses a tile of pixels with RICE_1, storing each pixel with `bytepix` bytes.
+///
+/// # Arguments
+/// - `pixels` (&[i32]): The pixels of the tile. With `bytepix` 1 they must fit in a `u8`,
+///   with `bytepix` 2 in an `i16`.
+/// - `bytepix` (usize): The number of bytes per pixel (`BYTEPIX`), 1, 2 or 4.
+/// - `block_size` (usize): The number of pixels per block (`BLOCKSIZE`).
+///
+/// # Returns
+/// - `Result<Vec<u8>, FitsError>`: The compressed bytes of the tile.
+pub fn rice_compress(pixels: &[i32], bytepix: usize, block_size: usize) -> Result<Vec<u8>, FitsError> {
+    let nx = pixels.len() as i32;
+    let nblock = block_size as i32;
+    // Incompressible data grows by a few bits per block, leave room for it.
+    let mut out = vec![0u8; pixels.len() * bytepix + pixels.len() / block_size.max(1) + 64];
+    let clen = out.len() as i32;
+
+    let nbytes = match bytepix {
+        1 => {
+            let mut input: Vec<i8> = pixels.iter().map(|&v| v as u8 as i8).collect();
+            unsafe { fits_rcomp_byte(input.as_mut_ptr(), nx, out.as_mut_ptr(), clen, nblock) }
+        },
+        2 => {
+            let mut input: Vec<i16> = pixels.iter().map(|&v| v as i16).collect();
+            unsafe { fits_rcomp_short(input.as_mut_ptr(), nx, out.as_mut_ptr(), clen, nblock) }
+        },
+        4 => {
+            let mut input = pixels.to_vec();
+            unsafe { fits_rcomp(input.as_mut_ptr(), nx, out.as_mut_ptr(), clen, nblock) }
+        },
+        _ => return Err(FitsError::InvalidValue(format!("RICE_1 BYTEPIX = {}", bytepix))),
+    };
+
+    if nbytes < 0 {
+        return Err(FitsError::InvalidValue(format!("RICE_1 tile could not be compressed (status {})", nbytes)));
+    }
+    out.truncate(nbytes as usize);
+    Ok(out)
+}
@@ -1,15 +1,15 @@
-/// Module for handling ImageData and related operations, including 
-/// parsing, reading, and writing FITS images.
+//! Module for handling ImageData and related operations, including 
+//! parsing, reading, and writing FITS images.
 
-use std::fs::File;
-use std::io::Read;
 
-use crate::io::header::{Header, card::CardValue};
+use crate::io::errors::FitsError;
+use crate::io::header::{Header, card::{Card, CardValue}};
+use crate::io::utils::read_exact_or_truncated;
 
 use rayon::prelude::*;
 use ndarray::ArrayBase;
 
-use std::io::{Write, BufWriter};
+use std::io::{Read, Write, BufWriter};
 
 use crate::io::hdus::image::utils::{
     get_shape,
@@ -18,18 +18,28 @@ use crate::io::hdus::image::utils::{
     pre_bytes_to_u8_vec,
     pre_bytes_to_i16_vec,
     pre_bytes_to_i32_vec, 
+    pre_bytes_to_i64_vec,
     vec_to_ndarray, 
     nbytes_from_bitpix
 };
-use ndarray::ArrayD;
+use ndarray::{ArrayD, IxDyn};
 
 /// Enum to represent image data stored in various data types.
 /// This provides flexibility in handling different pixel data types
 /// in FITS files.
+///
+/// `I8`, `U16`, `U32` and `U64` have no `BITPIX` of their own: they are stored as the
+/// integers of the same width with the `BZERO` offset of the standard, e.g. `BZERO = 32768`
+/// for `U16`.
 pub enum ImageData {
     U8(ArrayD<u8>),
+    I8(ArrayD<i8>),
     I16(ArrayD<i16>),
+    U16(ArrayD<u16>),
     I32(ArrayD<i32>),
+    U32(ArrayD<u32>),
+    I64(ArrayD<i64>),
+    U64(ArrayD<u64>),
     F32(ArrayD<f32>),
     F64(ArrayD<f64>),
     EMPTY,
@@ -49,18 +59,16 @@ impl ImageData {
 
     /// Checks if the ImageData is empty.
     pub fn is_empty(&self) -> bool {
-        match self {
-            ImageData::EMPTY => true,
-            _ => false,
-        }
+        matches!(self, ImageData::EMPTY)
     }
 
     /// Retrieves the BITPIX value for the data type of the image.
     pub fn get_bitpix(&self) -> i32 {
         match self {
-            ImageData::U8(_) => 8,
-            ImageData::I16(_) => 16,
-            ImageData::I32(_) => 32,
+            ImageData::U8(_) | ImageData::I8(_) => 8,
+            ImageData::I16(_) | ImageData::U16(_) => 16,
+            ImageData::I32(_) | ImageData::U32(_) => 32,
+            ImageData::I64(_) | ImageData::U64(_) => 64,
             ImageData::F32(_) => -32,
             ImageData::F64(_) => -64,
             _ => 8,
@@ -71,26 +79,72 @@ impl ImageData {
     pub fn get_dtype(&self) -> String {
         match self {
             ImageData::U8(_) => String::from("uint8"),
+            ImageData::I8(_) => String::from("int8"),
             ImageData::I16(_) => String::from("int16"),
+            ImageData::U16(_) => String::from("uint16"),
             ImageData::I32(_) => String::from("int32"),
+            ImageData::U32(_) => String::from("uint32"),
+            ImageData::I64(_) => String::from("int64"),
+            ImageData::U64(_) => String::from("uint64"),
             ImageData::F32(_) => String::from("float32"),
             ImageData::F64(_) => String::from("float64"),
             _ => String::from("uint8"),
         }
     }
 
+    /// Creates an image of zeros.
+    ///
+    /// # Arguments
+    /// - `dtype` (&str): The type of the pixels, as returned by `get_dtype` (e.g. "uint16").
+    /// - `shape` (&[usize]): The shape of the image.
+    ///
+    /// # Returns
+    /// - `Result<ImageData, FitsError>`: The image, or `FitsError::UnsupportedDataType` for an
+    ///   unknown `dtype`.
+    pub fn zeros(dtype: &str, shape: &[usize]) -> Result<Self, FitsError> {
+        let shape = IxDyn(shape);
+        match dtype {
+            "uint8" => Ok(ImageData::U8(ArrayD::zeros(shape))),
+            "int8" => Ok(ImageData::I8(ArrayD::zeros(shape))),
+            "int16" => Ok(ImageData::I16(ArrayD::zeros(shape))),
+            "uint16" => Ok(ImageData::U16(ArrayD::zeros(shape))),
+            "int32" => Ok(ImageData::I32(ArrayD::zeros(shape))),
+            "uint32" => Ok(ImageData::U32(ArrayD::zeros(shape))),
+            "int64" => Ok(ImageData::I64(ArrayD::zeros(shape))),
+            "uint64" => Ok(ImageData::U64(ArrayD::zeros(shape))),
+            "float32" => Ok(ImageData::F32(ArrayD::zeros(shape))),
+            "float64" => Ok(ImageData::F64(ArrayD::zeros(shape))),
+            _ => Err(FitsError::UnsupportedDataType(format!("image pixels of type {}", dtype))),
+        }
+    }
+
     /// Retrieves the shape of the image as a vector of dimensions.
     pub fn get_shape(&self) -> Vec<usize> {
         match self {
             ImageData::U8(array) => {
                 array.shape().to_vec()
             },
+            ImageData::I8(array) => {
+                array.shape().to_vec()
+            },
             ImageData::I16(array) => {
                 array.shape().to_vec()
             },
+            ImageData::U16(array) => {
+                array.shape().to_vec()
+            },
             ImageData::I32(array) => {
                 array.shape().to_vec()
             },
+            ImageData::U32(array) => {
+                array.shape().to_vec()
+            },
+            ImageData::I64(array) => {
+                array.shape().to_vec()
+            },
+            ImageData::U64(array) => {
+                array.shape().to_vec()
+            },
             ImageData::F32(array) => {
                 array.shape().to_vec()
             },
@@ -100,6 +154,124 @@ impl ImageData {
             _ => vec![0, 0],
         }
     }
+
+    /// Returns the `BZERO` that stores the pixels as the signed integers of `BITPIX`, for the
+    /// types that need one: -128 for `I8`, 2^15 for `U16`, 2^31 for `U32` and 2^63 for `U64`.
+    pub fn offset_bzero(&self) -> Option<f64> {
+        match self {
+            ImageData::I8(_) => Some(-128.0),
+            ImageData::U16(_) => Some(32768.0),
+            ImageData::U32(_) => Some(2147483648.0),
+            ImageData::U64(_) => Some(9223372036854775808.0),
+            _ => None,
+        }
+    }
+}
+
+/// Parameters to store floating point pixels as scaled integers.
+///
+/// The stored integers are `round((value - bzero) / bscale)`, so that readers restore
+/// `stored * bscale + bzero`.
+///
+/// # Fields
+/// - `bitpix` (i32): The `BITPIX` of the stored integers, 8, 16 or 32.
+/// - `bscale` (f64): The scale of the stored integers (`BSCALE`).
+/// - `bzero` (f64): The offset of the stored integers (`BZERO`).
+/// - `blank` (Option<i64>): The integer stored for NaN pixels (`BLANK`). Writing NaN pixels
+///   without it is an error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageScaling {
+    pub bitpix: i32,
+    pub bscale: f64,
+    pub bzero: f64,
+    pub blank: Option<i64>,
+}
+
+impl ImageScaling {
+    /// Creates scaling parameters without a `BLANK` value.
+    pub fn new(bitpix: i32, bscale: f64, bzero: f64) -> Self {
+        Self { bitpix, bscale, bzero, blank: None }
+    }
+
+    /// Chooses `BSCALE` and `BZERO` so that the finite pixels of `data` span the whole range
+    /// of the integer type.
+    ///
+    /// # Arguments
+    /// - `data` (&ImageData): The pixels to store.
+    /// - `bitpix` (i32): The `BITPIX` of the stored integers, 8, 16 or 32.
+    ///
+    /// # Returns
+    /// - `Result<ImageScaling, FitsError>`: The scaling parameters. If some pixels are NaN, the
+    ///   lowest integer of the type is reserved for them as `BLANK`.
+    pub fn fit(data: &ImageData, bitpix: i32) -> Result<Self, FitsError> {
+        let (lowest, highest) = integer_range(bitpix)?;
+        let values = data_to_f64_vec(data);
+        let has_nan = values.iter().any(|v| v.is_nan());
+        let (min, max) = values.iter()
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)));
+
+        let lowest_valid = if has_nan { lowest + 1.0 } else { lowest };
+        let bscale = if max > min { (max - min) / (highest - lowest_valid) } else { 1.0 };
+        let bzero = if min.is_finite() { min - lowest_valid * bscale } else { 0.0 };
+        Ok(Self {
+            bitpix,
+            bscale,
+            bzero,
+            blank: has_nan.then_some(lowest as i64),
+        })
+    }
+}
+
+/// Returns the range of the integers stored with `bitpix`.
+fn integer_range(bitpix: i32) -> Result<(f64, f64), FitsError> {
+    match bitpix {
+        8 => Ok((u8::MIN as f64, u8::MAX as f64)),
+        16 => Ok((i16::MIN as f64, i16::MAX as f64)),
+        32 => Ok((i32::MIN as f64, i32::MAX as f64)),
+        _ => Err(FitsError::InvalidBitpix(bitpix as i64)),
+    }
+}
+
+/// Converts the pixels to `f64`, in the order they are stored.
+fn data_to_f64_vec(data: &ImageData) -> Vec<f64> {
+    match data {
+        ImageData::U8(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::I8(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::I16(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::U16(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::I32(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::U32(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::I64(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::U64(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::F32(array) => array.iter().map(|&v| v as f64).collect(),
+        ImageData::F64(array) => array.iter().copied().collect(),
+        ImageData::EMPTY => vec![],
+    }
+}
+
+/// Sets the value of header cards, inserting the missing ones after the last `NAXISn` card.
+fn set_cards_after_axes(header: &mut Header, naxis: usize, cards: Vec<(&str, CardValue, &str)>) {
+    let mut previous = if naxis > 0 && header.contains_key(&format!("NAXIS{}", naxis)) {
+        format!("NAXIS{}", naxis)
+    } else {
+        "NAXIS".to_string()
+    };
+    for (keyword, value, comment) in cards {
+        match header.get_mut_card(keyword) {
+            Some(card) => card.value = value,
+            None => {
+                let mut card = Card::new(keyword.to_string(), String::new(), Some(comment.to_string()));
+                card.value = value;
+                if header.contains_key(&previous) {
+                    header.add_card_after(&card, &previous);
+                } else {
+                    header.add_card(&card);
+                }
+            },
+        }
+        previous = keyword.to_string();
+    }
 }
 
 /// Struct to represent image data with a generic type parameter `T`.
@@ -116,12 +288,27 @@ impl fmt::Debug for ImageData {
             ImageData::U8(array) => {
                 write!(f, "FitsData::U8({:?})", array)
             },
+            ImageData::I8(array) => {
+                write!(f, "FitsData::I8({:?})", array)
+            },
             ImageData::I16(array) => {
                 write!(f, "FitsData::I16({:?})", array)
             },
+            ImageData::U16(array) => {
+                write!(f, "FitsData::U16({:?})", array)
+            },
             ImageData::I32(array) => {
                 write!(f, "FitsData::I32({:?})", array)
             },
+            ImageData::U32(array) => {
+                write!(f, "FitsData::U32({:?})", array)
+            },
+            ImageData::I64(array) => {
+                write!(f, "FitsData::I64({:?})", array)
+            },
+            ImageData::U64(array) => {
+                write!(f, "FitsData::U64({:?})", array)
+            },
             ImageData::F32(array) => {
                 write!(f, "FitsData::F32({:?})", array)
             },
@@ -139,81 +326,271 @@ impl fmt::Debug for ImageData {
 pub struct ImageParser;
 
 impl ImageParser {
-    //TODO: Find where to implement BZERO and BSCALE
+    /// Returns the `BSCALE` and `BZERO` of a header, 1 and 0 when they are absent.
+    pub fn get_scaling(header: &Header) -> Result<(f64, f64), FitsError> {
+        let bscale = if header.contains_key("BSCALE") { header.get_float("BSCALE")? } else { 1.0 };
+        let bzero = if header.contains_key("BZERO") { header.get_float("BZERO")? } else { 0.0 };
+        Ok((bscale, bzero))
+    }
+
+    /// Checks if the pixels of an image must be scaled to get their physical values,
+    /// i.e. if `BSCALE` is not 1 or `BZERO` is not 0.
+    pub fn is_scaled(header: &Header) -> Result<bool, FitsError> {
+        let (bscale, bzero) = ImageParser::get_scaling(header)?;
+        Ok(bscale != 1.0 || bzero != 0.0)
+    }
+
+    /// Converts the stored pixels of an image to their physical values,
+    /// `stored * BSCALE + BZERO`.
+    ///
+    /// # Arguments
+    /// - `header` (&Header): The header of the image.
+    /// - `data` (ImageData): The pixels as stored in the file.
+    ///
+    /// # Returns
+    /// - `Result<ImageData, FitsError>`: The physical values. Unscaled images are returned
+    ///   unchanged. Integers stored with the `BZERO` offset of an unsigned (or, for `BITPIX = 8`,
+    ///   signed) type and no `BLANK` become `I8`, `U16`, `U32` or `U64`. Other scaled 8 and 16-bit
+    ///   integers become `F32` and 32 and 64-bit integers `F64`, with the `BLANK` pixels set to NaN.
+    pub fn scale_physical(header: &Header, data: ImageData) -> Result<ImageData, FitsError> {
+        if !ImageParser::is_scaled(header)? {
+            return Ok(data);
+        }
+        let (bscale, bzero) = ImageParser::get_scaling(header)?;
+        let data = if bscale == 1.0 && !header.contains_key("BLANK") {
+            match ImageParser::stored_to_offset(data, bzero) {
+                Ok(converted) => return Ok(converted),
+                Err(data) => data,
+            }
+        } else {
+            data
+        };
+        let blank = if header.contains_key("BLANK") { Some(header.get_int("BLANK")?) } else { None };
+        let scale = |v: i64| if Some(v) == blank { f64::NAN } else { v as f64 * bscale + bzero };
+
+        Ok(match data {
+            ImageData::U8(array) => ImageData::F32(array.mapv(|v| scale(v as i64) as f32)),
+            ImageData::I8(array) => ImageData::F32(array.mapv(|v| scale(v as i64) as f32)),
+            ImageData::I16(array) => ImageData::F32(array.mapv(|v| scale(v as i64) as f32)),
+            ImageData::U16(array) => ImageData::F32(array.mapv(|v| scale(v as i64) as f32)),
+            ImageData::I32(array) => ImageData::F64(array.mapv(|v| scale(v as i64))),
+            ImageData::U32(array) => ImageData::F64(array.mapv(|v| scale(v as i64))),
+            ImageData::I64(array) => ImageData::F64(array.mapv(scale)),
+            ImageData::U64(array) => ImageData::F64(array.mapv(|v| v as f64 * bscale + bzero)),
+            ImageData::F32(array) => ImageData::F32(array.mapv(|v| (v as f64 * bscale + bzero) as f32)),
+            ImageData::F64(array) => ImageData::F64(array.mapv(|v| v * bscale + bzero)),
+            ImageData::EMPTY => ImageData::EMPTY,
+        })
+    }
+
+    /// Converts integers stored with the `BZERO` offset of `ImageData::offset_bzero` to the
+    /// type they represent, flipping their sign bit.
+    ///
+    /// # Returns
+    /// - `Result<ImageData, ImageData>`: The converted pixels, or the pixels unchanged in `Err`
+    ///   if `bzero` is not the offset of their type.
+    pub fn stored_to_offset(data: ImageData, bzero: f64) -> Result<ImageData, ImageData> {
+        match data {
+            ImageData::U8(array) if bzero == -128.0 => Ok(ImageData::I8(array.mapv(|v| (v ^ 0x80) as i8))),
+            ImageData::I16(array) if bzero == 32768.0 => Ok(ImageData::U16(array.mapv(|v| (v as u16) ^ 0x8000))),
+            ImageData::I32(array) if bzero == 2147483648.0 => Ok(ImageData::U32(array.mapv(|v| (v as u32) ^ 0x8000_0000))),
+            ImageData::I64(array) if bzero == 9223372036854775808.0 => Ok(ImageData::U64(array.mapv(|v| (v as u64) ^ (1 << 63)))),
+            data => Err(data),
+        }
+    }
+
+    /// Converts `I8`, `U16`, `U32` and `U64` pixels to the signed integers that store them with
+    /// the `BZERO` offset of `ImageData::offset_bzero`.
+    ///
+    /// # Returns
+    /// - `Option<ImageData>`: The stored integers, or `None` for the other types, which are
+    ///   stored as they are.
+    pub fn offset_to_stored(data: &ImageData) -> Option<ImageData> {
+        match data {
+            ImageData::I8(array) => Some(ImageData::U8(array.mapv(|v| (v as u8) ^ 0x80))),
+            ImageData::U16(array) => Some(ImageData::I16(array.mapv(|v| (v ^ 0x8000) as i16))),
+            ImageData::U32(array) => Some(ImageData::I32(array.mapv(|v| (v ^ 0x8000_0000) as i32))),
+            ImageData::U64(array) => Some(ImageData::I64(array.mapv(|v| (v ^ (1 << 63)) as i64))),
+            _ => None,
+        }
+    }
+
+    /// Removes `BSCALE`, `BZERO` and `BLANK` from the header of an image converted with
+    /// `scale_physical`, and sets `BITPIX` to the type of the physical values.
+    pub fn strip_scaling_keywords(header: &mut Header, data: &ImageData) {
+        for keyword in ["BSCALE", "BZERO", "BLANK"] {
+            header.remove(keyword);
+        }
+        if let Some(card) = header.get_mut_card("BITPIX") {
+            card.value = CardValue::INT(data.get_bitpix() as i64);
+        }
+    }
+
+    /// Converts the stored pixels of an image to their physical values and updates the header
+    /// accordingly. See `scale_physical` and `strip_scaling_keywords`.
+    pub fn apply_scaling(header: &mut Header, data: ImageData) -> Result<ImageData, FitsError> {
+        if !ImageParser::is_scaled(header)? {
+            return Ok(data);
+        }
+        let data = ImageParser::scale_physical(header, data)?;
+        ImageParser::strip_scaling_keywords(header, &data);
+        Ok(data)
+    }
+
+    /// Returns the mask of the `BLANK` pixels of an integer image.
+    ///
+    /// # Returns
+    /// - `Option<ArrayD<bool>>`: `true` where the pixel equals `BLANK`, or `None` if the header
+    ///   has no `BLANK` or the pixels are not integers. Undefined floating point pixels are NaN.
+    pub fn blank_mask(header: &Header, data: &ImageData) -> Option<ArrayD<bool>> {
+        let blank = header.get_int("BLANK").ok()?;
+        match data {
+            ImageData::U8(array) => Some(array.mapv(|v| v as i64 == blank)),
+            ImageData::I16(array) => Some(array.mapv(|v| v as i64 == blank)),
+            ImageData::I32(array) => Some(array.mapv(|v| v as i64 == blank)),
+            ImageData::I64(array) => Some(array.mapv(|v| v == blank)),
+            _ => None,
+        }
+    }
+
+    /// Converts pixels to the scaled integers of `scaling` and writes `BSCALE`, `BZERO` and
+    /// `BLANK` to the header.
+    ///
+    /// # Arguments
+    /// - `header` (&mut Header): The header of the image.
+    /// - `data` (&ImageData): The physical values.
+    /// - `scaling` (&ImageScaling): The integer type, scale and offset to store them with.
+    ///
+    /// # Returns
+    /// - `Result<ImageData, FitsError>`: The integers to write. `FitsError::InvalidValue` if a pixel
+    ///   is NaN and there is no `BLANK`, or if it does not fit in the integer type once scaled.
+    pub fn scale_to_integers(header: &mut Header, data: &ImageData, scaling: &ImageScaling) -> Result<ImageData, FitsError> {
+        let (lowest, highest) = integer_range(scaling.bitpix)?;
+        if scaling.bscale == 0.0 || !scaling.bscale.is_finite() || !scaling.bzero.is_finite() {
+            return Err(FitsError::InvalidValue(format!("BSCALE = {}, BZERO = {}", scaling.bscale, scaling.bzero)));
+        }
+
+        let values = data_to_f64_vec(data)
+            .into_iter()
+            .map(|v| {
+                if v.is_nan() {
+                    return scaling.blank.ok_or_else(|| FitsError::InvalidValue(
+                        "NaN pixels cannot be stored as scaled integers without BLANK".to_string()
+                    ));
+                }
+                let stored = ((v - scaling.bzero) / scaling.bscale).round();
+                if stored < lowest || stored > highest {
+                    return Err(FitsError::InvalidValue(format!(
+                        "pixel value {} does not fit in BITPIX = {} with BSCALE = {} and BZERO = {}",
+                        v, scaling.bitpix, scaling.bscale, scaling.bzero
+                    )));
+                }
+                Ok(stored as i64)
+            })
+            .collect::<Result<Vec<i64>, FitsError>>()?;
+
+        let shape = data.get_shape();
+        let scaled = match scaling.bitpix {
+            8 => ImageData::U8(vec_to_ndarray(values.into_iter().map(|v| v as u8).collect(), shape)?),
+            16 => ImageData::I16(vec_to_ndarray(values.into_iter().map(|v| v as i16).collect(), shape)?),
+            _ => ImageData::I32(vec_to_ndarray(values.into_iter().map(|v| v as i32).collect(), shape)?),
+        };
+
+        let mut cards = vec![
+            ("BSCALE", CardValue::FLOAT(scaling.bscale), "physical = BZERO + BSCALE * stored"),
+            ("BZERO", CardValue::FLOAT(scaling.bzero), "physical = BZERO + BSCALE * stored"),
+        ];
+        match scaling.blank {
+            Some(blank) => cards.push(("BLANK", CardValue::INT(blank), "stored value of undefined pixels")),
+            None => { header.remove("BLANK"); },
+        }
+        set_cards_after_axes(header, scaled.get_shape().len(), cards);
+        Ok(scaled)
+    }
+
     /// Calculates the total size (in bytes) required to store the image data
     /// based on its header metadata.
-    pub fn calculate_image_bytes(header: &Header) -> usize {
-        let bitpix : i32 = header["BITPIX"].value.as_int().unwrap_or(0) as i32;
-        let shape = get_shape(header).unwrap();
-        let dtype_bytes = nbytes_from_bitpix(bitpix);
-        shape.iter().product::<usize>() * dtype_bytes
+    pub fn calculate_image_bytes(header: &Header) -> Result<usize, FitsError> {
+        let bitpix : i32 = header.get_int("BITPIX")? as i32;
+        let shape = get_shape(header)?;
+        let dtype_bytes = nbytes_from_bitpix(bitpix)?;
+        Ok(shape.iter().product::<usize>() * dtype_bytes)
     }
 
     /// Reads image data from a file buffer and converts it into ImageData.
-    pub fn read_from_buffer(f: &mut File, header: &mut Header) -> Result<ImageData, std::io::Error>  {
-        let _naxis: usize = header["NAXIS"].value.as_int().unwrap_or(0) as usize;
-    
-        let bitpix : i32 = header["BITPIX"].value.as_int().unwrap_or(0) as i32;
+    pub fn read_from_buffer<R: Read + ?Sized>(f: &mut R, header: &Header) -> Result<ImageData, FitsError>  {
+        let bitpix : i32 = header.get_int("BITPIX")? as i32;
         let shape = get_shape(header)?;
 
         // Get data type from BITPIX
-        let dtype_bytes = nbytes_from_bitpix(bitpix);
+        let dtype_bytes = nbytes_from_bitpix(bitpix)?;
+        // NAXIS = 0: there is no data unit.
+        if shape.is_empty() {
+            return Ok(ImageData::EMPTY);
+        }
 
         let total_bytes = shape.iter().product::<usize>() * dtype_bytes;
         let mut databuf = vec![0; total_bytes]; 
-        let _ = f.read(&mut databuf)?;
+        read_exact_or_truncated(f, &mut databuf)?;
 
-        // Read until the end of the current FITS block
+        // Read until the end of the current FITS block. The last block of a file
+        // is sometimes not padded, so a short read here is not an error.
         let remainder = total_bytes % 2880;
         if remainder != 0 {
             let mut padding = vec![0; 2880 - remainder];
-            let _ = f.read(&mut padding)?;
-            // println!("Padding: {:?}", padding.len());
+            let _ = read_exact_or_truncated(f, &mut padding);
         }
 
         ImageParser::image_buffer_to_ndarray(&databuf, shape, bitpix) 
     }
 
     /// Converts raw image data from a buffer into a NumPy-like n-dimensional array.
-    pub fn image_buffer_to_ndarray(databuf: &Vec<u8>, shape: Vec<usize>, bitpix: i32) -> Result<ImageData, std::io::Error>  {
+    pub fn image_buffer_to_ndarray(databuf: &[u8], shape: Vec<usize>, bitpix: i32) -> Result<ImageData, FitsError>  {
         match bitpix {
             8 => {
                 let mut vect: Vec<u8> = vec![0; databuf.len()];
                 pre_bytes_to_u8_vec(databuf, &mut vect);
-                let ndarray = vec_to_ndarray(vect, shape);
+                let ndarray = vec_to_ndarray(vect, shape)?;
                 let data: ImageData = ImageData::U8(ndarray);
                 Ok(data)
             },
             16 => {
                 let mut vect: Vec<i16> = vec![0; databuf.len() / 2];
                 pre_bytes_to_i16_vec(databuf, &mut vect);
-                let ndarray = vec_to_ndarray(vect, shape);
+                let ndarray = vec_to_ndarray(vect, shape)?;
                 let data = ImageData::I16(ndarray);
                 Ok(data)
             },
             32 => {
                 let mut vect: Vec<i32> = vec![0; databuf.len() / 4];
                 pre_bytes_to_i32_vec(databuf, &mut vect);
-                let ndarray = vec_to_ndarray(vect, shape);
+                let ndarray = vec_to_ndarray(vect, shape)?;
                 let data = ImageData::I32(ndarray);
                 Ok(data)
             },
+            64 => {
+                let mut vect: Vec<i64> = vec![0; databuf.len() / 8];
+                pre_bytes_to_i64_vec(databuf, &mut vect);
+                let ndarray = vec_to_ndarray(vect, shape)?;
+                let data = ImageData::I64(ndarray);
+                Ok(data)
+            },
             -32 => {
                 let mut vect: Vec<f32> = vec![0.0; databuf.len() / 4];
                 pre_bytes_to_f32_vec(databuf, &mut vect);
-                let ndarray: ArrayBase<ndarray::OwnedRepr<f32>, ndarray::Dim<ndarray::IxDynImpl>> = vec_to_ndarray(vect, shape);
+                let ndarray: ArrayBase<ndarray::OwnedRepr<f32>, ndarray::Dim<ndarray::IxDynImpl>> = vec_to_ndarray(vect, shape)?;
                 let data = ImageData::F32(ndarray);
                 Ok(data)
             },
             -64 => {
                 let mut vect: Vec<f64> = vec![0.0; databuf.len() / 8];
                 pre_bytes_to_f64_vec(databuf, &mut vect);
-                let ndarray = vec_to_ndarray(vect, shape);
+                let ndarray = vec_to_ndarray(vect, shape)?;
                 let data = ImageData::F64(ndarray);
                 Ok(data)
             },
             _ => {
-                Err(std::io::Error::new(std::io::ErrorKind::Other, "Not implemented"))
+                Err(FitsError::InvalidBitpix(bitpix as i64))
             },
         }
     }
@@ -234,6 +611,10 @@ impl ImageParser {
                 let vect = array.clone().into_raw_vec();
                 vect.par_iter().flat_map(|&item| item.to_be_bytes().to_vec()).collect::<Vec<u8>>()
             },
+            ImageData::I64(array) => {
+                let vect = array.clone().into_raw_vec();
+                vect.par_iter().flat_map(|&item| item.to_be_bytes().to_vec()).collect::<Vec<u8>>()
+            },
             ImageData::F32(array) => {
                 let vect = array.clone().into_raw_vec();
                 vect.par_iter().flat_map(|&item| item.to_be_bytes().to_vec()).collect::<Vec<u8>>()
@@ -242,26 +623,49 @@ impl ImageParser {
                 let vect = array.clone().into_raw_vec();
                 vect.par_iter().flat_map(|&item| item.to_be_bytes().to_vec()).collect::<Vec<u8>>()
             },
+            ImageData::I8(_) | ImageData::U16(_) | ImageData::U32(_) | ImageData::U64(_) => {
+                ImageParser::offset_to_stored(data).map_or_else(Vec::new, |stored| ImageParser::ndarray_to_buffer_parallel(&stored))
+            },
             _ => vec![],
         }
     }
 
     /// Writes image metadata into the FITS header, updating fields like BITPIX and NAXIS.
-    pub fn write_image_header(header: &mut Header, data: &ImageData) {
+    ///
+    /// Missing `NAXISn` cards are inserted after the previous axis card. `I8`, `U16`, `U32` and
+    /// `U64` pixels also set `BSCALE = 1` and the `BZERO` offset of their type.
+    ///
+    /// # Returns
+    /// - `Result<(), FitsError>`: `FitsError::MissingKeyword` if `BITPIX` or `NAXIS` is missing.
+    pub fn write_image_header(header: &mut Header, data: &ImageData) -> Result<(), FitsError> {
         let shape = data.get_shape();
-        let mut naxis = shape.len();
         if shape.eq(&vec![0, 0]) {
-            naxis = 0;
+            return ImageParser::write_image_header_with_shape(header, &[], data);
         }
-        
-        
+        ImageParser::write_image_header_with_shape(header, &shape, data)
+    }
+
+    /// Writes image metadata into the FITS header for an image of `shape`, taking the type of
+    /// the pixels from `data`. See `write_image_header`.
+    pub fn write_image_header_with_shape(header: &mut Header, shape: &[usize], data: &ImageData) -> Result<(), FitsError> {
+        let naxis = shape.len();
         let bitpix = data.get_bitpix();
-        header["BITPIX"].value = CardValue::INT(bitpix as i64);
-        header["NAXIS"].value = CardValue::INT(naxis as i64);
+        header.get_mut_card("BITPIX")
+            .ok_or_else(|| FitsError::MissingKeyword("BITPIX".to_string()))?
+            .value = CardValue::INT(bitpix as i64);
+        header.get_mut_card("NAXIS")
+            .ok_or_else(|| FitsError::MissingKeyword("NAXIS".to_string()))?
+            .value = CardValue::INT(naxis as i64);
         
-        for i in 0..naxis {
+        for (i, &len) in shape.iter().enumerate().take(naxis) {
             let naxisn = format!("NAXIS{}", i+1);
-            header[naxisn.as_str()].value = CardValue::INT(shape[i] as i64);
+            match header.get_mut_card(&naxisn) {
+                Some(card) => card.value = CardValue::INT(len as i64),
+                None => {
+                    let previous = if i == 0 { "NAXIS".to_string() } else { format!("NAXIS{}", i) };
+                    header.add_card_after(&Card::new(naxisn, len.to_string(), None), &previous);
+                }
+            }
         }
 
         //if other NAXISn keywords are present, remove them
@@ -271,6 +675,16 @@ impl ImageParser {
                 header.remove(naxisn.as_str());
             }
         }
+
+        if let Some(bzero) = data.offset_bzero() {
+            // 2^63 does not fit in an i64 card.
+            let bzero = if bzero.abs() < i64::MAX as f64 { CardValue::INT(bzero as i64) } else { CardValue::FLOAT(bzero) };
+            set_cards_after_axes(header, naxis, vec![
+                ("BSCALE", CardValue::INT(1), "physical = BZERO + BSCALE * stored"),
+                ("BZERO", bzero, "offset of the unsigned integers"),
+            ]);
+        }
+        Ok(())
     }
 
     /// Writes image data to a buffer in FITS format, including padding to align to
@@ -311,6 +725,41 @@ impl ImageParser {
                     bytes_written += bytes.len();
                 }
             },
+            ImageData::I64(ndarray) => {
+                for &item in ndarray.iter() {
+                    let bytes: [u8; 8] = item.to_be_bytes();
+                    writer.write_all(&bytes)?;
+                    bytes_written += bytes.len();
+                }
+            },
+            ImageData::I8(ndarray) => {
+                for &item in ndarray.iter() {
+                    let bytes: [u8; 1] = [(item as u8) ^ 0x80];
+                    writer.write_all(&bytes)?;
+                    bytes_written += bytes.len();
+                }
+            },
+            ImageData::U16(ndarray) => {
+                for &item in ndarray.iter() {
+                    let bytes: [u8; 2] = (item ^ 0x8000).to_be_bytes();
+                    writer.write_all(&bytes)?;
+                    bytes_written += bytes.len();
+                }
+            },
+            ImageData::U32(ndarray) => {
+                for &item in ndarray.iter() {
+                    let bytes: [u8; 4] = (item ^ 0x8000_0000).to_be_bytes();
+                    writer.write_all(&bytes)?;
+                    bytes_written += bytes.len();
+                }
+            },
+            ImageData::U64(ndarray) => {
+                for &item in ndarray.iter() {
+                    let bytes: [u8; 8] = (item ^ (1 << 63)).to_be_bytes();
+                    writer.write_all(&bytes)?;
+                    bytes_written += bytes.len();
+                }
+            },
             ImageData::F32(ndarray) => {
                 for &item in ndarray.iter() {
                     let bytes: [u8; 4] = f32::to_be_bytes(item);
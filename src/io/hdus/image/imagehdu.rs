@@ -1,10 +1,16 @@
 use std::fs::File;
-use std::io::Result;
+use std::io::{Read, Seek, Write};
 
+use crate::io::errors::FitsError;
 use crate::io::Header;
-use crate::io::hdus::image::ImageData;
+use crate::io::hdus::image::{AxisSlice, ImageData, ImageScaling};
 
 use crate::io::hdus::image::image::ImageParser;
+use crate::io::hdus::image::mmap::MmapImageHDU;
+use crate::io::hdus::image::section::{read_section, slice_section};
+use crate::io::hdus::compressed::CompressionParams;
+use crate::io::hdus::compressed::compressed::{is_compressed_image, read_compressed_image, write_compressed_image};
+use ndarray::ArrayD;
 
 
 const MANDATORY_KEYWORDS: [&str; 3] = [
@@ -16,13 +22,17 @@ const MANDATORY_KEYWORDS: [&str; 3] = [
 pub struct ImageHDU{
     pub header: Header,
     pub data: ImageData,
+    pub compression: Option<CompressionParams>,
+    pub scaling: Option<ImageScaling>,
 }
 
 /// Represents an Image Header Data Unit (HDU) in a FITS file.
 ///
 /// This struct encapsulates the header and image data of a FITS file.
 /// It provides methods to read from and write to FITS files while ensuring
-/// compliance with the FITS standard.
+/// compliance with the FITS standard. Images read from a tile-compressed
+/// BINTABLE keep their compression parameters in `compression`. When `scaling`
+/// is set, the pixels are written as scaled integers.
 impl ImageHDU {
     /// Creates a new `ImageHDU` instance with the provided header and data.
     ///
@@ -36,6 +46,8 @@ impl ImageHDU {
         Self {
             header,
             data,
+            compression: None,
+            scaling: None,
         }
     }
 
@@ -45,49 +57,149 @@ impl ImageHDU {
     /// an `ImageHDU` instance. It ensures that the mandatory keywords appear in
     /// the correct order at the start of the header.
     ///
+    /// A BINTABLE with `ZIMAGE = T` is decompressed, and the image header is rebuilt
+    /// from `ZBITPIX` and `ZNAXISn`.
+    ///
+    /// Scaled pixels are converted to their physical values, see `ImageParser::apply_scaling`.
+    ///
     /// # Parameters:
-    /// - `f`: A mutable reference to a file object.
+    /// - `f`: A mutable reference to a file or any other reader/writer.
     ///
     /// # Returns:
     /// - `Ok(Self)`: An `ImageHDU` instance containing the header and data.
-    /// - `Err`: If reading from the file fails or the header is corrupted.
-    pub fn read_from_file(f: &mut File) -> Result<Self>  {
-        //TODO: Check for mandatory words
+    /// - `Err(FitsError)`: If reading from the file fails or the header is corrupted.
+    pub fn read_from_file<R: Read + Seek + ?Sized>(f: &mut R) -> Result<Self, FitsError>  {
+        let mut hdu = Self::read_from_file_raw(f)?;
+        hdu.data = ImageParser::apply_scaling(&mut hdu.header, std::mem::take(&mut hdu.data))?;
+        Ok(hdu)
+    }
 
+    /// Reads an Image HDU from a file, keeping the pixels as they are stored.
+    ///
+    /// `BSCALE`, `BZERO` and `BLANK` are left in the header and are not applied.
+    ///
+    /// # Parameters:
+    /// - `f`: A mutable reference to a file or any other reader/writer.
+    ///
+    /// # Returns:
+    /// - `Ok(Self)`: An `ImageHDU` instance containing the header and stored pixels.
+    /// - `Err(FitsError)`: If reading from the file fails or the header is corrupted.
+    pub fn read_from_file_raw<R: Read + Seek + ?Sized>(f: &mut R) -> Result<Self, FitsError>  {
         let mut header = Header::new();
         header.read_from_file(f)?;
+
+        if is_compressed_image(&header) {
+            let (header, data, compression) = read_compressed_image(f, &header)?;
+            let mut hdu = Self::new(header, data);
+            hdu.compression = Some(compression);
+            return Ok(hdu);
+        }
         
         if !header.are_mandatory_keywords_first(&MANDATORY_KEYWORDS) {
-            // TODO: Return a proper error
-            // Err(std::io::Error::new(std::io::ErrorKind::Other, "Header corrupted"));
-            panic!("Header corrupted");
+            return Err(FitsError::InvalidHeader(
+                format!("image extension header must start with {}", MANDATORY_KEYWORDS.join(", "))
+            ));
         }
 
-        let data: ImageData = ImageParser::read_from_buffer(f, &mut header)?;
+        let data: ImageData = ImageParser::read_from_buffer(f, &header)?;
         Ok(Self::new(header, data))
     }
 
+    /// Returns the mask of the undefined pixels of an integer image.
+    ///
+    /// # Returns:
+    /// - `Some(mask)`: `true` where the pixel equals `BLANK`.
+    /// - `None`: If the header has no `BLANK` or the pixels are floating point values, whose
+    ///   undefined pixels are NaN.
+    pub fn blank_mask(&self) -> Option<ArrayD<bool>> {
+        ImageParser::blank_mask(&self.header, &self.data)
+    }
+
+    /// Reads a section (cutout) of an Image HDU without loading the whole image.
+    ///
+    /// Only the rows covering the section are read from the file. Tile-compressed images are
+    /// decompressed whole and then sliced. The pixels are converted to their physical values,
+    /// as with `read_from_file`, and the cursor is moved to the end of the HDU.
+    ///
+    /// # Parameters:
+    /// - `f`: A mutable reference to a file or any other reader, positioned at the start of the HDU.
+    /// - `section`: One slice per axis, in the order of the arrays in `ImageData`.
+    ///
+    /// # Returns:
+    /// - `Ok(ImageData)`: The pixels of the section, with one axis per slice.
+    /// - `Err(FitsError)`: If the header is corrupted, or the section does not fit in the image.
+    pub fn read_section<R: Read + Seek + ?Sized>(f: &mut R, section: &[AxisSlice]) -> Result<ImageData, FitsError> {
+        let mut header = Header::new();
+        header.read_from_file(f)?;
+
+        if is_compressed_image(&header) {
+            let (header, data, _) = read_compressed_image(f, &header)?;
+            let data = slice_section(&header, &data, section)?;
+            return ImageParser::scale_physical(&header, data);
+        }
+
+        if !header.are_mandatory_keywords_first(&MANDATORY_KEYWORDS) {
+            return Err(FitsError::InvalidHeader(
+                format!("image extension header must start with {}", MANDATORY_KEYWORDS.join(", "))
+            ));
+        }
+
+        let data = read_section(f, &header, section)?;
+        ImageParser::scale_physical(&header, data)
+    }
+
+    /// Reads the header of an Image HDU and memory maps its data.
+    ///
+    /// The pixels are not copied into memory; rows, planes or regions are decoded
+    /// on demand from the memory map. The cursor is moved to the end of the HDU.
+    ///
+    /// # Parameters:
+    /// - `f`: A mutable reference to a file or any other reader/writer.
+    ///
+    /// # Returns:
+    /// - `Ok(MmapImageHDU)`: The header and the memory-mapped image data.
+    /// - `Err(FitsError)`: If reading from the file fails or the header is corrupted.
+    pub fn read_mmap(f: &mut File) -> Result<MmapImageHDU, FitsError> {
+        MmapImageHDU::read_from_file(f, &MANDATORY_KEYWORDS)
+    }
+
     /// Writes the Image HDU to a file.
     ///
     /// This function writes the header and image data to a file in the FITS format.
     /// It ensures that the mandatory keywords are in the correct order and updates
     /// the header with the correct `NAXISn` keywords based on the image shape.
     ///
+    /// When `scaling` is set, the pixels are stored as scaled integers and `BSCALE`,
+    /// `BZERO` and `BLANK` are written to the header.
+    ///
+    /// When `compression` is set, the image is written as a tile-compressed BINTABLE
+    /// instead, with the tile shape and algorithm of the parameters.
+    ///
     /// # Parameters:
-    /// - `f`: A mutable reference to a file object.
+    /// - `f`: A mutable reference to a file or any other reader/writer.
     ///
     /// # Returns:
     /// - `Ok(())`: If the HDU is successfully written to the file.
-    /// - `Err`: If writing to the file fails.
-    pub fn write_to_file(&mut self, mut f: &mut File) -> Result<()> {
+    /// - `Err(FitsError)`: If writing to the file fails.
+    pub fn write_to_file<W: Write + ?Sized>(&mut self, mut f: &mut W) -> Result<(), FitsError> {
         //TODO: This function should not repeat here and in primary hdu
         self.header.fix_header_w_mandatory_order(&MANDATORY_KEYWORDS);
 
+        let scaled = match &self.scaling {
+            Some(scaling) if !self.data.is_empty() => Some(ImageParser::scale_to_integers(&mut self.header, &self.data, scaling)?),
+            _ => None,
+        };
+        let data = scaled.as_ref().unwrap_or(&self.data);
+
         //Check for shape of self.data and write NAXISn keywords
-        ImageParser::write_image_header(&mut self.header, &self.data);
+        ImageParser::write_image_header(&mut self.header, data)?;
+
+        if let Some(params) = &self.compression {
+            return write_compressed_image(f, &self.header, data, params);
+        }
 
         self.header.write_to_buffer(&mut f)?;
-        ImageParser::ndarray_to_buffer(&self.data, f)?;
+        ImageParser::ndarray_to_buffer(data, f)?;
 
         Ok(())
     }
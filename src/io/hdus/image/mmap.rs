@@ -0,0 +1,216 @@
+//! Memory-mapped access to the data unit of primary and image HDUs.
+//!
+//! The pixels stay in the page cache and only the requested rows, planes or
+//! regions are decoded from big-endian into an `ImageData`.
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+
+use memmap::{Mmap, MmapOptions};
+
+use crate::io::errors::FitsError;
+use crate::io::header::Header;
+use crate::io::hdus::image::ImageData;
+use crate::io::hdus::image::image::ImageParser;
+use crate::io::hdus::image::utils::{get_shape, nbytes_from_bitpix};
+
+/// Image data backed by a memory map of the FITS file.
+///
+/// The axes follow the same order as the arrays in `ImageData`: axis `k` has length `NAXIS(k+1)`
+/// and the last axis is contiguous in the file.
+pub struct MmapImage {
+    mmap: Option<Mmap>,
+    shape: Vec<usize>,
+    bitpix: i32,
+}
+
+impl MmapImage {
+    /// Maps the data unit described by `header`.
+    ///
+    /// # Arguments
+    /// - `file` (&File): The FITS file.
+    /// - `header` (&Header): The header of the primary or image HDU.
+    /// - `data_offset` (u64): The byte offset of the data unit in the file.
+    ///
+    /// # Returns
+    /// - `Result<MmapImage, FitsError>`: The mapped image, or `FitsError::TruncatedData` if the
+    ///   file is shorter than the data unit.
+    pub fn new(file: &File, header: &Header, data_offset: u64) -> Result<Self, FitsError> {
+        let bitpix = header.get_int("BITPIX")? as i32;
+        let nbytes = nbytes_from_bitpix(bitpix)?;
+        let shape = if header.get_int("NAXIS")? == 0 { Vec::new() } else { get_shape(header)? };
+        let size = if shape.is_empty() { 0 } else { shape.iter().product::<usize>() * nbytes };
+
+        let file_len = file.metadata()?.len();
+        let available = file_len.saturating_sub(data_offset) as usize;
+        if available < size {
+            return Err(FitsError::TruncatedData { expected: size, found: available });
+        }
+
+        let mmap = if size == 0 {
+            None
+        } else {
+            // SAFETY: the map is read-only. As with any memory map, the file must not be
+            // truncated by another process while the map is alive.
+            Some(unsafe { MmapOptions::new().offset(data_offset).len(size).map(file)? })
+        };
+        Ok(Self { mmap, shape, bitpix })
+    }
+
+    /// Returns the shape of the image.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// Returns the BITPIX of the image.
+    pub fn bitpix(&self) -> i32 {
+        self.bitpix
+    }
+
+    /// Returns the raw big-endian bytes of the data unit, without copying them.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.mmap.as_deref().unwrap_or(&[])
+    }
+
+    /// Decodes the whole image into memory.
+    pub fn read_all(&self) -> Result<ImageData, FitsError> {
+        if self.shape.is_empty() {
+            return Ok(ImageData::EMPTY);
+        }
+        ImageParser::image_buffer_to_ndarray(self.as_bytes(), self.shape.clone(), self.bitpix)
+    }
+
+    /// Decodes one row, i.e. a run along the last axis.
+    ///
+    /// # Arguments
+    /// - `index` (&[usize]): The indices of the row along all axes but the last one.
+    ///
+    /// # Returns
+    /// - `Result<ImageData, FitsError>`: A 1-dimensional array with the pixels of the row.
+    pub fn read_row(&self, index: &[usize]) -> Result<ImageData, FitsError> {
+        let ndim = self.shape.len();
+        if ndim == 0 || index.len() != ndim - 1 {
+            return Err(FitsError::InvalidValue(format!("row index {:?} for shape {:?}", index, self.shape)));
+        }
+        let mut start = index.to_vec();
+        start.push(0);
+        let mut size = vec![1; ndim - 1];
+        size.push(self.shape[ndim - 1]);
+        self.read_block(&start, &size, vec![self.shape[ndim - 1]])
+    }
+
+    /// Decodes one plane, i.e. the last two axes at fixed indices of the other axes.
+    ///
+    /// # Arguments
+    /// - `index` (&[usize]): The indices of the plane along all axes but the last two.
+    ///
+    /// # Returns
+    /// - `Result<ImageData, FitsError>`: A 2-dimensional array with the pixels of the plane.
+    pub fn read_plane(&self, index: &[usize]) -> Result<ImageData, FitsError> {
+        let ndim = self.shape.len();
+        if ndim < 2 || index.len() != ndim - 2 {
+            return Err(FitsError::InvalidValue(format!("plane index {:?} for shape {:?}", index, self.shape)));
+        }
+        let mut start = index.to_vec();
+        start.extend([0, 0]);
+        let mut size = vec![1; ndim - 2];
+        size.extend_from_slice(&self.shape[ndim - 2..]);
+        self.read_block(&start, &size, self.shape[ndim - 2..].to_vec())
+    }
+
+    /// Decodes a rectangular region of the image.
+    ///
+    /// # Arguments
+    /// - `start` (&[usize]): The index of the first pixel of the region along each axis.
+    /// - `size` (&[usize]): The length of the region along each axis.
+    ///
+    /// # Returns
+    /// - `Result<ImageData, FitsError>`: An array of shape `size` with the pixels of the region.
+    pub fn read_region(&self, start: &[usize], size: &[usize]) -> Result<ImageData, FitsError> {
+        self.read_block(start, size, size.to_vec())
+    }
+
+    /// Copies the bytes of a region and decodes them into an array of shape `out_shape`.
+    fn read_block(&self, start: &[usize], size: &[usize], out_shape: Vec<usize>) -> Result<ImageData, FitsError> {
+        let ndim = self.shape.len();
+        let in_bounds = start.len() == ndim
+            && size.len() == ndim
+            && (0..ndim).all(|i| start[i] + size[i] <= self.shape[i]);
+        if ndim == 0 || !in_bounds {
+            return Err(FitsError::InvalidValue(
+                format!("region start {:?} size {:?} for shape {:?}", start, size, self.shape)
+            ));
+        }
+
+        let nbytes = nbytes_from_bitpix(self.bitpix)?;
+        let bytes = self.as_bytes();
+
+        // Element strides of each axis, the last axis being contiguous.
+        let mut strides = vec![1; ndim];
+        for i in (0..ndim - 1).rev() {
+            strides[i] = strides[i + 1] * self.shape[i + 1];
+        }
+
+        let run = size[ndim - 1] * nbytes;
+        let nruns: usize = size[..ndim - 1].iter().product();
+        let mut databuf = Vec::with_capacity(run * nruns);
+        let mut index = vec![0; ndim - 1];
+        for _ in 0..nruns {
+            let element: usize = (0..ndim).map(|i| {
+                let offset = if i < ndim - 1 { index[i] } else { 0 };
+                (start[i] + offset) * strides[i]
+            }).sum();
+            let begin = element * nbytes;
+            databuf.extend_from_slice(&bytes[begin..begin + run]);
+
+            // Advance the multi-index over all axes but the last one.
+            for i in (0..ndim - 1).rev() {
+                index[i] += 1;
+                if index[i] < size[i] {
+                    break;
+                }
+                index[i] = 0;
+            }
+        }
+
+        ImageParser::image_buffer_to_ndarray(&databuf, out_shape, self.bitpix)
+    }
+}
+
+/// Represents a primary or image HDU whose data is memory mapped.
+///
+/// # Fields
+/// - `header` (Header): The header of the HDU.
+/// - `data` (MmapImage): The memory-mapped pixel data.
+pub struct MmapImageHDU {
+    pub header: Header,
+    pub data: MmapImage,
+}
+
+impl MmapImageHDU {
+    /// Reads the header at the cursor and maps the data unit that follows it.
+    ///
+    /// # Arguments
+    /// - `f` (&mut File): The file, positioned at the start of the HDU.
+    /// - `mandatory_keywords` (&[&str]): The keywords that must start the header.
+    ///
+    /// # Returns
+    /// - `Result<MmapImageHDU, FitsError>`: The HDU. The cursor is moved past the padded data unit.
+    pub(crate) fn read_from_file(f: &mut File, mandatory_keywords: &[&str]) -> Result<Self, FitsError> {
+        let mut header = Header::new();
+        header.read_from_file(f)?;
+        if !header.are_mandatory_keywords_first(mandatory_keywords) {
+            return Err(FitsError::InvalidHeader(
+                format!("image header must start with {}", mandatory_keywords.join(", "))
+            ));
+        }
+
+        let data_offset = f.stream_position()?;
+        let data = MmapImage::new(f, &header, data_offset)?;
+
+        let size = data.as_bytes().len();
+        let padded_size = size.div_ceil(2880) * 2880;
+        f.seek(SeekFrom::Start(data_offset + padded_size as u64))?;
+        Ok(Self { header, data })
+    }
+}
@@ -1,6 +1,11 @@
 pub mod image;
 pub mod utils;
 
-pub use self::image::ImageData;
+pub use self::image::{ImageData, ImageScaling};
+pub use self::section::AxisSlice;
+pub use self::writer::ImageWriter;
 
-pub mod imagehdu;
\ No newline at end of file
+pub mod imagehdu;
+pub mod mmap;
+pub mod section;
+pub mod writer;
\ No newline at end of file
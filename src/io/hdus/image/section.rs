@@ -0,0 +1,139 @@
+//! Reading sections (cutouts) of primary and image HDUs directly from the file.
+//!
+//! Only the runs of pixels that cover the section are read, one seek per run along the last
+//! axis, so that small cutouts of large images do not load the whole data unit.
+
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use crate::io::errors::FitsError;
+use crate::io::header::Header;
+use crate::io::hdus::image::ImageData;
+use crate::io::hdus::image::image::ImageParser;
+use crate::io::hdus::image::utils::{get_shape, nbytes_from_bitpix};
+use crate::io::utils::read_exact_or_truncated;
+
+/// The pixels selected along one axis: `start, start + step, ...` up to `stop` excluded.
+///
+/// # Fields
+/// - `start` (usize): The index of the first pixel.
+/// - `stop` (usize): The index past the last pixel, at most the length of the axis.
+/// - `step` (usize): The distance between selected pixels, at least 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AxisSlice {
+    pub start: usize,
+    pub stop: usize,
+    pub step: usize,
+}
+
+impl AxisSlice {
+    /// Creates a slice from `start` to `stop` excluded, taking every `step`-th pixel.
+    pub fn new(start: usize, stop: usize, step: usize) -> Self {
+        Self { start, stop, step }
+    }
+
+    /// Creates a slice of the contiguous pixels from `start` to `stop` excluded.
+    pub fn range(start: usize, stop: usize) -> Self {
+        Self::new(start, stop, 1)
+    }
+
+    /// Returns the number of selected pixels.
+    pub fn len(&self) -> usize {
+        if self.step == 0 || self.stop <= self.start {
+            0
+        } else {
+            (self.stop - self.start).div_ceil(self.step)
+        }
+    }
+
+    /// Checks if the slice selects no pixel.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Reads a section of the data unit that starts at the cursor.
+///
+/// # Arguments
+/// - `f` (&mut R): The file or reader, positioned at the start of the data unit.
+/// - `header` (&Header): The header of the primary or image HDU.
+/// - `section` (&[AxisSlice]): One slice per axis, in the order of the arrays in `ImageData`:
+///   axis `k` has length `NAXIS(k+1)`.
+///
+/// # Returns
+/// - `Result<ImageData, FitsError>`: The stored pixels of the section, with one axis per slice.
+///   `FitsError::InvalidValue` if the number of slices does not match `NAXIS`, or if a slice is
+///   empty or out of bounds.
+///
+/// # Behavior
+/// - Seeks to and reads the pixels from the first to the last selected one of each run along the
+///   last axis, so a step along that axis still reads the pixels in between.
+/// - Moves the cursor to the end of the padded data unit.
+pub fn read_section<R: Read + Seek + ?Sized>(f: &mut R, header: &Header, section: &[AxisSlice]) -> Result<ImageData, FitsError> {
+    let bitpix = header.get_int("BITPIX")? as i32;
+    let nbytes = nbytes_from_bitpix(bitpix)?;
+    let shape = if header.get_int("NAXIS")? == 0 { Vec::new() } else { get_shape(header)? };
+    let ndim = shape.len();
+    let valid = ndim > 0
+        && section.len() == ndim
+        && section.iter().zip(&shape).all(|(slice, &len)| !slice.is_empty() && slice.stop <= len);
+    if !valid {
+        return Err(FitsError::InvalidValue(format!("section {:?} for shape {:?}", section, shape)));
+    }
+
+    let data_start = f.stream_position()?;
+    let out_shape: Vec<usize> = section.iter().map(AxisSlice::len).collect();
+
+    // Element strides of each axis, the last axis being contiguous.
+    let mut strides = vec![1; ndim];
+    for i in (0..ndim - 1).rev() {
+        strides[i] = strides[i + 1] * shape[i + 1];
+    }
+
+    let last = section[ndim - 1];
+    let run_len = (out_shape[ndim - 1] - 1) * last.step + 1;
+    let mut run = vec![0u8; run_len * nbytes];
+    let nruns: usize = out_shape[..ndim - 1].iter().product();
+    let mut databuf = Vec::with_capacity(nruns * out_shape[ndim - 1] * nbytes);
+    let mut index = vec![0; ndim - 1];
+    for _ in 0..nruns {
+        let element: usize = last.start + (0..ndim - 1)
+            .map(|i| (section[i].start + index[i] * section[i].step) * strides[i])
+            .sum::<usize>();
+        f.seek(SeekFrom::Start(data_start + (element * nbytes) as u64))?;
+        read_exact_or_truncated(f, &mut run)?;
+        if last.step == 1 {
+            databuf.extend_from_slice(&run);
+        } else {
+            for pixel in run.chunks_exact(nbytes).step_by(last.step) {
+                databuf.extend_from_slice(pixel);
+            }
+        }
+
+        // Advance the multi-index over all axes but the last one.
+        for i in (0..ndim - 1).rev() {
+            index[i] += 1;
+            if index[i] < out_shape[i] {
+                break;
+            }
+            index[i] = 0;
+        }
+    }
+
+    let size = shape.iter().product::<usize>() * nbytes;
+    f.seek(SeekFrom::Start(data_start + size.div_ceil(2880) as u64 * 2880))?;
+    ImageParser::image_buffer_to_ndarray(&databuf, out_shape, bitpix)
+}
+
+/// Takes a section of pixels already in memory, such as a decompressed image.
+///
+/// # Arguments
+/// - `header` (&Header): The header describing the stored pixels.
+/// - `data` (&ImageData): The stored pixels.
+/// - `section` (&[AxisSlice]): One slice per axis, see `read_section`.
+///
+/// # Returns
+/// - `Result<ImageData, FitsError>`: The stored pixels of the section.
+pub fn slice_section(header: &Header, data: &ImageData, section: &[AxisSlice]) -> Result<ImageData, FitsError> {
+    let mut cursor = Cursor::new(ImageParser::ndarray_to_buffer_parallel(data));
+    read_section(&mut cursor, header, section)
+}
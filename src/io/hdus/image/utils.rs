@@ -22,16 +22,18 @@
 use rayon::prelude::*;
 use ndarray::{ArrayD, IxDyn};
 
+use crate::io::errors::FitsError;
 use crate::io::header::Header;
 
-pub fn nbytes_from_bitpix(bitpix : i32) -> usize {
+pub fn nbytes_from_bitpix(bitpix : i32) -> Result<usize, FitsError> {
     match bitpix {
-        8 => 1,
-        16 => 2,
-        32 => 4,
-        -32 => 4,
-        -64 => 8,
-        _ => panic!("Unknown bitpix value"),
+        8 => Ok(1),
+        16 => Ok(2),
+        32 => Ok(4),
+        64 => Ok(8),
+        -32 => Ok(4),
+        -64 => Ok(8),
+        _ => Err(FitsError::InvalidBitpix(bitpix as i64)),
     }
 }
 
@@ -67,7 +69,7 @@ pub fn bytes_to_f64_vec(bytes : &[u8]) -> Vec<f64> {
         .collect()
 }
 
-pub fn pre_bytes_to_f64_vec(bytes: &Vec<u8>, output: &mut Vec<f64>) { // Preallocated vect
+pub fn pre_bytes_to_f64_vec(bytes: &[u8], output: &mut [f64]) { // Preallocated vect
     assert!(output.len() * 8 <= bytes.len());
     output.par_iter_mut()
         .enumerate()
@@ -80,7 +82,7 @@ pub fn pre_bytes_to_f64_vec(bytes: &Vec<u8>, output: &mut Vec<f64>) { // Preallo
         });
 }
 
-pub fn pre_bytes_to_f32_vec(bytes: &Vec<u8>, output: &mut Vec<f32>) {
+pub fn pre_bytes_to_f32_vec(bytes: &[u8], output: &mut [f32]) {
     assert!(output.len() * 4 <= bytes.len());
     output.par_iter_mut()
         .enumerate()
@@ -92,7 +94,7 @@ pub fn pre_bytes_to_f32_vec(bytes: &Vec<u8>, output: &mut Vec<f32>) {
         });
 }
 
-pub fn pre_bytes_to_u8_vec(bytes: &Vec<u8>, output: &mut Vec<u8>) {
+pub fn pre_bytes_to_u8_vec(bytes: &[u8], output: &mut [u8]) {
     assert!(output.len() <= bytes.len());
     output.par_iter_mut()
         .enumerate()
@@ -101,7 +103,7 @@ pub fn pre_bytes_to_u8_vec(bytes: &Vec<u8>, output: &mut Vec<u8>) {
         });
 }
 
-pub fn pre_bytes_to_i16_vec(bytes: &Vec<u8>, output: &mut Vec<i16>) {
+pub fn pre_bytes_to_i16_vec(bytes: &[u8], output: &mut [i16]) {
     assert!(output.len() * 2 <= bytes.len());
     output.par_iter_mut()
         .enumerate()
@@ -113,7 +115,7 @@ pub fn pre_bytes_to_i16_vec(bytes: &Vec<u8>, output: &mut Vec<i16>) {
         });
 }
 
-pub fn pre_bytes_to_i32_vec(bytes: &Vec<u8>, output: &mut Vec<i32>) {
+pub fn pre_bytes_to_i32_vec(bytes: &[u8], output: &mut [i32]) {
     assert!(output.len() * 4 <= bytes.len());
     output.par_iter_mut()
         .enumerate()
@@ -125,18 +127,50 @@ pub fn pre_bytes_to_i32_vec(bytes: &Vec<u8>, output: &mut Vec<i32>) {
         });
 }
 
-pub fn get_shape(header: &Header) -> Result<Vec<usize>, std::io::Error> {
+pub fn pre_bytes_to_i64_vec(bytes: &[u8], output: &mut [i64]) {
+    assert!(output.len() * 8 <= bytes.len());
+    output.par_iter_mut()
+        .enumerate()
+        .for_each(|(i, item)| {
+            let chunk = &bytes[i * 8..(i+1) * 8];
+            *item = i64::from_be_bytes([
+                chunk[0], chunk[1], chunk[2], chunk[3],
+                chunk[4], chunk[5], chunk[6], chunk[7]
+            ]);
+        });
+}
+
+pub fn get_shape(header: &Header) -> Result<Vec<usize>, FitsError> {
     let mut shape = Vec::new();
-    let naxis: usize = header["NAXIS"].value.as_int().unwrap_or(0) as usize;
+    let naxis = header.get_int("NAXIS")?;
+    if !(0..=999).contains(&naxis) {
+        return Err(FitsError::InvalidHeader(format!("NAXIS = {} is out of range", naxis)));
+    }
     for i in 1..=naxis {
         let key = format!("NAXIS{}", i);
-        let value: usize = header[&key].value.as_int().unwrap_or(0) as usize;
-        shape.push(value);
+        let value = header.get_int(&key)?;
+        if value < 0 {
+            return Err(FitsError::InvalidHeader(format!("{} = {} is negative", key, value)));
+        }
+        shape.push(value as usize);
     }
     Ok(shape)
 }
 
-pub fn vec_to_ndarray<T>(data: Vec<T>, shape: Vec<usize>) -> ArrayD<T> {
-    let shape_ix = IxDyn(&shape);
-    ArrayD::from_shape_vec(shape_ix, data).unwrap() // handle the error appropriately in your code
+/// Arranges decoded pixels into an array of the given shape.
+///
+/// # Returns
+/// - `Result<ArrayD<T>, FitsError>`: The array, `FitsError::TruncatedData` if there are fewer pixels
+///   than the shape holds, or `FitsError::InvalidValue` if there are more.
+pub fn vec_to_ndarray<T>(data: Vec<T>, shape: Vec<usize>) -> Result<ArrayD<T>, FitsError> {
+    let found = data.len();
+    ArrayD::from_shape_vec(IxDyn(&shape), data).map_err(|_| {
+        let expected = shape.iter().fold(1usize, |n, &len| n.saturating_mul(len));
+        let size = std::mem::size_of::<T>();
+        if found < expected {
+            FitsError::TruncatedData { expected: expected.saturating_mul(size), found: found * size }
+        } else {
+            FitsError::InvalidValue(format!("{} pixels for an image of shape {:?}", found, shape))
+        }
+    })
 }
\ No newline at end of file
@@ -0,0 +1,134 @@
+//! Incremental writing of primary and image HDUs that do not fit in memory.
+//!
+//! The header is written up front with the final `NAXISn`, then the pixels are appended block
+//! by block along the first axis of the image, the slowest varying one in the file.
+
+use std::io::Write;
+
+use crate::io::errors::FitsError;
+use crate::io::header::Header;
+use crate::io::hdus::image::ImageData;
+use crate::io::hdus::image::image::ImageParser;
+use crate::io::hdus::image::utils::nbytes_from_bitpix;
+
+const PRIMARY_KEYWORDS: [&str; 3] = ["SIMPLE", "BITPIX", "NAXIS"];
+const IMAGE_KEYWORDS: [&str; 3] = ["XTENSION", "BITPIX", "NAXIS"];
+
+/// Writes the data unit of a primary or image HDU block by block.
+///
+/// The axes follow the same order as the arrays in `ImageData`. Blocks are sub-arrays along the
+/// first axis: either arrays with the shape of the image but fewer indices along the first
+/// axis, or single slices with the first axis dropped, such as the planes of a cube.
+pub struct ImageWriter<W: Write> {
+    writer: W,
+    shape: Vec<usize>,
+    dtype: String,
+    nbytes: usize,
+    expected: usize,
+    written: usize,
+}
+
+impl<W: Write> ImageWriter<W> {
+    /// Writes the header of the HDU and prepares to receive its pixels.
+    ///
+    /// # Arguments
+    /// - `writer` (W): The file or writer, positioned where the HDU starts.
+    /// - `header` (Header): The header of the HDU, starting with `SIMPLE` for a primary HDU or
+    ///   `XTENSION` for an image extension. `BITPIX` and `NAXISn` are set from `shape` and `dtype`.
+    /// - `shape` (&[usize]): The final shape of the image.
+    /// - `dtype` (&str): The type of the pixels, as returned by `ImageData::get_dtype`.
+    ///
+    /// # Returns
+    /// - `Result<ImageWriter<W>, FitsError>`: The writer, `FitsError::MissingKeyword` if the header
+    ///   has neither `SIMPLE` nor `XTENSION`, `FitsError::UnsupportedDataType` for an unknown `dtype`.
+    pub fn new(mut writer: W, mut header: Header, shape: &[usize], dtype: &str) -> Result<Self, FitsError> {
+        let prototype = ImageData::zeros(dtype, &[0])?;
+        let nbytes = nbytes_from_bitpix(prototype.get_bitpix())?;
+        if header.contains_key("SIMPLE") {
+            header.fix_header_w_mandatory_order(&PRIMARY_KEYWORDS);
+        } else if header.contains_key("XTENSION") {
+            header.fix_header_w_mandatory_order(&IMAGE_KEYWORDS);
+        } else {
+            return Err(FitsError::MissingKeyword("SIMPLE or XTENSION".to_string()));
+        }
+
+        ImageParser::write_image_header_with_shape(&mut header, shape, &prototype)?;
+        header.write_to_buffer(&mut writer)?;
+
+        Ok(Self {
+            writer,
+            shape: shape.to_vec(),
+            dtype: dtype.to_string(),
+            nbytes,
+            expected: if shape.is_empty() { 0 } else { shape.iter().product() },
+            written: 0,
+        })
+    }
+
+    /// Returns the number of pixels written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Returns the number of pixels left to write.
+    pub fn remaining(&self) -> usize {
+        self.expected - self.written
+    }
+
+    /// Appends a block of pixels.
+    ///
+    /// # Arguments
+    /// - `block` (&ImageData): The next pixels along the first axis, of the type declared in `new`.
+    ///
+    /// # Returns
+    /// - `Result<(), FitsError>`: `FitsError::InvalidValue` if the block has another type, does not
+    ///   match the other axes of the image, or goes past the end of the image.
+    pub fn write_block(&mut self, block: &ImageData) -> Result<(), FitsError> {
+        if block.get_dtype() != self.dtype {
+            return Err(FitsError::InvalidValue(format!(
+                "block of type {} for an image of type {}", block.get_dtype(), self.dtype
+            )));
+        }
+
+        let block_shape = block.get_shape();
+        let ndim = self.shape.len();
+        let matches_axes = ndim > 0 && match block_shape.len() {
+            n if n == ndim => block_shape[1..] == self.shape[1..],
+            n if n + 1 == ndim => block_shape[..] == self.shape[1..],
+            _ => false,
+        };
+        if !matches_axes {
+            return Err(FitsError::InvalidValue(format!("block of shape {:?} for an image of shape {:?}", block_shape, self.shape)));
+        }
+
+        let npix: usize = block_shape.iter().product();
+        if npix > self.remaining() {
+            return Err(FitsError::InvalidValue(format!(
+                "block of {} pixels with only {} left to write", npix, self.remaining()
+            )));
+        }
+
+        self.writer.write_all(&ImageParser::ndarray_to_buffer_parallel(block))?;
+        self.written += npix;
+        Ok(())
+    }
+
+    /// Pads the data unit with zeros to a full FITS block and returns the writer.
+    ///
+    /// # Returns
+    /// - `Result<W, FitsError>`: The writer, positioned after the HDU. `FitsError::InvalidValue`
+    ///   if fewer pixels than declared were written, in which case nothing is padded.
+    pub fn finish(mut self) -> Result<W, FitsError> {
+        if self.written != self.expected {
+            return Err(FitsError::InvalidValue(format!(
+                "{} pixels written for an image of {} pixels", self.written, self.expected
+            )));
+        }
+        let remainder = (self.written * self.nbytes) % 2880;
+        if remainder != 0 {
+            self.writer.write_all(&vec![0; 2880 - remainder])?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
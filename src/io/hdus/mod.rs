@@ -2,6 +2,7 @@ pub mod table;
 pub mod bintable;
 
 pub mod image;
+pub mod compressed;
 pub mod utils;
 
 pub mod primaryhdu;
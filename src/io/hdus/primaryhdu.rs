@@ -1,11 +1,15 @@
 use std::fs::File;
-use std::io::{Result, Seek};
+use std::io::{Read, Seek, Write};
 
+use crate::io::errors::FitsError;
 use crate::io::Header;
-use crate::io::hdus::image::ImageData;
+use crate::io::hdus::image::{AxisSlice, ImageData, ImageScaling};
 
 use crate::io::hdus::image::image::ImageParser;
+use crate::io::hdus::image::mmap::MmapImageHDU;
+use crate::io::hdus::image::section::read_section;
 use crate::io::header::card::Card;
+use ndarray::ArrayD;
 
 
 const MANDATORY_KEYWORDS: [&str; 3] = [
@@ -18,9 +22,29 @@ const MANDATORY_KEYWORDS: [&str; 3] = [
 ///
 /// This struct encapsulates the header and image data of the Primary HDU.
 /// It provides functionality for creating, reading, and writing the Primary HDU.
+/// When `scaling` is set, the pixels are written as scaled integers.
 pub struct PrimaryHDU{
     pub header: Header,
     pub data: ImageData,
+    pub scaling: Option<ImageScaling>,
+}
+
+impl Default for PrimaryHDU {
+    /// Creates a default `PrimaryHDU` with minimal header information.
+    ///
+    /// # Returns
+    /// - `PrimaryHDU`: A default Primary HDU with "SIMPLE", "BITPIX", and "NAXIS" header keywords.
+    fn default() -> Self {
+        let mut header = Header::new();
+        header.add_card(&Card::new("SIMPLE".to_string(), "T".to_string(), Some("Primary HDU".to_string())));
+        header.add_card(&Card::new("BITPIX".to_string(), "8".to_string(), Some("Number of bits per data pixel".to_string())));
+        header.add_card(&Card::new("NAXIS".to_string(), "0".to_string(), Some("Number of data axes".to_string())));
+        Self {
+            header,
+            data: ImageData::new(),
+            scaling: None,
+        }
+    }
 }
 
 impl PrimaryHDU {
@@ -36,111 +60,171 @@ impl PrimaryHDU {
         Self {
             header,
             data,
-        }
-    }
-
-    /// Creates a default `PrimaryHDU` with minimal header information.
-    ///
-    /// # Returns
-    /// - `PrimaryHDU`: A default Primary HDU with "SIMPLE", "BITPIX", and "NAXIS" header keywords.
-    pub fn default() -> Self {
-        let mut header = Header::new();
-        header.add_card(&Card::new("SIMPLE".to_string(), "T".to_string(), Some("Primary HDU".to_string())));
-        header.add_card(&Card::new("BITPIX".to_string(), "8".to_string(), Some("Number of bits per data pixel".to_string())));
-        header.add_card(&Card::new("NAXIS".to_string(), "0".to_string(), Some("Number of data axes".to_string())));
-        Self {
-            header,
-            data: ImageData::new(),
+            scaling: None,
         }
     }
 
     /// Reads a `PrimaryHDU` from a file.
     ///
     /// # Arguments
-    /// - `f` (&mut File): The file handle to read from.
+    /// - `f` (&mut R): The file handle or reader to read from.
     ///
     /// # Returns
-    /// - `Result<PrimaryHDU>`: The Primary HDU read from the file.
+    /// - `Result<PrimaryHDU, FitsError>`: The Primary HDU read from the file.
     ///
     /// # Behavior
-    /// - Checks if the mandatory keywords are present and in order.
+    /// - Checks if the mandatory keywords are present and in order, returning
+    ///   `FitsError::InvalidHeader` otherwise.
     /// - Returns an empty `ImageData` if `NAXIS` is 0.
-    pub fn read_from_file(f: &mut File) -> Result<Self>  {
+    /// - Converts scaled pixels to their physical values, see `ImageParser::apply_scaling`.
+    pub fn read_from_file<R: Read + Seek + ?Sized>(f: &mut R) -> Result<Self, FitsError>  {
+        let mut hdu = Self::read_from_file_raw(f)?;
+        hdu.data = ImageParser::apply_scaling(&mut hdu.header, std::mem::take(&mut hdu.data))?;
+        Ok(hdu)
+    }
+
+    /// Reads a `PrimaryHDU` from a file, keeping the pixels as they are stored.
+    ///
+    /// # Arguments
+    /// - `f` (&mut R): The file handle or reader to read from.
+    ///
+    /// # Returns
+    /// - `Result<PrimaryHDU, FitsError>`: The Primary HDU, with `BSCALE`, `BZERO` and `BLANK`
+    ///   left in the header and not applied to the pixels.
+    pub fn read_from_file_raw<R: Read + Seek + ?Sized>(f: &mut R) -> Result<Self, FitsError>  {
         let mut header = Header::new();
         header.read_from_file(f)?;
         
         if !header.are_mandatory_keywords_first(&MANDATORY_KEYWORDS) {
-            // TODO: Return a proper error
-            // Err(std::io::Error::new(std::io::ErrorKind::Other, "Header corrupted"));
-            panic!("Header corrupted");
+            return Err(FitsError::InvalidHeader(
+                format!("primary header must start with {}", MANDATORY_KEYWORDS.join(", "))
+            ));
         }
 
-        if header["NAXIS"].value.as_int().unwrap_or(0) == 0 {
+        if header.get_int("NAXIS")? == 0 {
             //actual position after header
             Ok(Self::new(header, ImageData::EMPTY))
         }
         else {
-            let data: ImageData = ImageParser::read_from_buffer(f, &mut header)?;
+            let data: ImageData = ImageParser::read_from_buffer(f, &header)?;
             Ok(Self::new(header, data))
         }
     }
 
+    /// Returns the mask of the undefined pixels of an integer image.
+    ///
+    /// # Returns
+    /// - `Option<ArrayD<bool>>`: `true` where the pixel equals `BLANK`, or `None` if the header
+    ///   has no `BLANK` or the pixels are floating point values, whose undefined pixels are NaN.
+    pub fn blank_mask(&self) -> Option<ArrayD<bool>> {
+        ImageParser::blank_mask(&self.header, &self.data)
+    }
+
+    /// Reads a section (cutout) of the Primary HDU without loading the whole image.
+    ///
+    /// # Arguments
+    /// - `f` (&mut R): The file handle or reader, positioned at the start of the file.
+    /// - `section` (&[AxisSlice]): One slice per axis, in the order of the arrays in `ImageData`.
+    ///
+    /// # Returns
+    /// - `Result<ImageData, FitsError>`: The physical values of the pixels of the section.
+    ///
+    /// # Behavior
+    /// - Only the rows covering the section are read from the file.
+    /// - Returns `FitsError::InvalidValue` if the section does not fit in the image.
+    /// - The cursor is moved to the end of the Primary HDU.
+    pub fn read_section<R: Read + Seek + ?Sized>(f: &mut R, section: &[AxisSlice]) -> Result<ImageData, FitsError> {
+        let mut header = Header::new();
+        header.read_from_file(f)?;
+
+        if !header.are_mandatory_keywords_first(&MANDATORY_KEYWORDS) {
+            return Err(FitsError::InvalidHeader(
+                format!("primary header must start with {}", MANDATORY_KEYWORDS.join(", "))
+            ));
+        }
+
+        let data = read_section(f, &header, section)?;
+        ImageParser::scale_physical(&header, data)
+    }
+
+    /// Reads the header of a `PrimaryHDU` and memory maps its data.
+    ///
+    /// # Arguments
+    /// - `f` (&mut File): The file handle to read from.
+    ///
+    /// # Returns
+    /// - `Result<MmapImageHDU, FitsError>`: The header and the memory-mapped image data.
+    ///
+    /// # Behavior
+    /// - The pixels are not copied; rows, planes or regions are decoded on demand.
+    /// - The cursor is moved to the end of the Primary HDU.
+    pub fn read_mmap(f: &mut File) -> Result<MmapImageHDU, FitsError> {
+        MmapImageHDU::read_from_file(f, &MANDATORY_KEYWORDS)
+    }
+
     /// Calculates the byte position of the end of the Primary HDU in the file.
     ///
     /// # Arguments
-    /// - `f` (&mut File): The file handle.
+    /// - `f` (&mut R): The file handle or reader.
     ///
     /// # Returns
-    /// - `usize`: The byte position of the end of the Primary HDU.
+    /// - `Result<usize, FitsError>`: The byte position of the end of the Primary HDU.
     ///
     /// # Behavior
     /// - Calculates the size of the image data and adjusts to the next 2880-byte boundary.
-    pub fn get_end_byte_position(f: &mut File) -> usize {
-        let first_pos = f.stream_position().unwrap();
+    pub fn get_end_byte_position<R: Read + Seek + ?Sized>(f: &mut R) -> Result<usize, FitsError> {
+        let first_pos = f.stream_position()?;
 
         let mut header = Header::new();
-        header.read_from_file(f).unwrap();
+        header.read_from_file(f)?;
 
-        if header["NAXIS"].value.as_int().unwrap_or(0) == 0 {
+        if header.get_int("NAXIS")? == 0 {
             //actual position after header
-            return f.stream_position().unwrap() as usize;
+            return Ok(f.stream_position()? as usize);
         }
 
-        let current = f.stream_position().unwrap();
-        let image_size = ImageParser::calculate_image_bytes(&header);
+        let current = f.stream_position()?;
+        let image_size = ImageParser::calculate_image_bytes(&header)?;
         let mut end = current + image_size as u64;
 
-        if end % 2880 != 0{
+        if !end.is_multiple_of(2880) {
             end = end + 2880 - (end % 2880);
         }
         
-        f.seek(std::io::SeekFrom::Start(first_pos)).unwrap();
-        end as usize
+        f.seek(std::io::SeekFrom::Start(first_pos))?;
+        Ok(end as usize)
     }
 
     /// Writes the `PrimaryHDU` to a file.
     ///
     /// # Arguments
-    /// - `f` (&mut File): The file handle to write to.
+    /// - `f` (&mut W): The file or writer to write to.
     ///
     /// # Returns
-    /// - `Result<()>`: Indicates whether the operation was successful.
+    /// - `Result<(), FitsError>`: Indicates whether the operation was successful.
     ///
     /// # Behavior
     /// - Ensures the mandatory keywords are ordered correctly.
     /// - Writes the header and image data, if present.
-    pub fn write_to_file(&mut self, mut f: &mut File) -> Result<()> {
+    /// - Stores the pixels as scaled integers when `scaling` is set.
+    pub fn write_to_file<W: Write + ?Sized>(&mut self, mut f: &mut W) -> Result<(), FitsError> {
         self.header.fix_header_w_mandatory_order(&MANDATORY_KEYWORDS);
+
+        let scaled = match &self.scaling {
+            Some(scaling) if !self.data.is_empty() => Some(ImageParser::scale_to_integers(&mut self.header, &self.data, scaling)?),
+            _ => None,
+        };
+        let data = scaled.as_ref().unwrap_or(&self.data);
         
         //Check for shape of self.data and write NAXISn keywords
-        ImageParser::write_image_header(&mut self.header, &self.data);
+        ImageParser::write_image_header(&mut self.header, data)?;
 
         self.header.write_to_buffer(&mut f)?;
 
-        if self.data.get_shape()[0] == 0 {
+        if data.get_shape()[0] == 0 {
             return Ok(());
         }
-        ImageParser::ndarray_to_buffer(&self.data, f)?;
+        ImageParser::ndarray_to_buffer(data, f)?;
         Ok(())
     }
 
@@ -1,15 +1,17 @@
 use rayon::prelude::*;
 use polars::{prelude::NamedFrom, series::Series};
 
+use crate::io::errors::FitsError;
+
 /// Enum representing column data types in a binary table.
-/// Each variant corresponds to a specific data type.
+/// Each variant corresponds to a specific data type, `None` marking undefined values.
 #[derive(Debug, PartialEq)]
 pub enum ColumnDataBuffer {
-    I(Vec<i32>),
-    E(Vec<f32>),
-    D(Vec<f64>),
-    A(Vec<String>),
-    F(Vec<f32>),
+    I(Vec<Option<i32>>),
+    E(Vec<Option<f32>>),
+    D(Vec<Option<f64>>),
+    A(Vec<Option<String>>),
+    F(Vec<Option<f32>>),
 }
 
 impl ColumnDataBuffer {
@@ -25,11 +27,11 @@ impl ColumnDataBuffer {
         let tform = tform.trim();
         let tform_type = tform.chars().next().unwrap();
         match tform_type {
-            'I' => ColumnDataBuffer::I(vec![0; size as usize]),
-            'E' => ColumnDataBuffer::E(vec![0.0; size as usize]),
-            'D' => ColumnDataBuffer::D(vec![0.0; size as usize]),
-            'A' => ColumnDataBuffer::A(vec![String::new(); size as usize]),
-            'F' => ColumnDataBuffer::F(vec![0.0; size as usize]),
+            'I' => ColumnDataBuffer::I(vec![None; size as usize]),
+            'E' => ColumnDataBuffer::E(vec![None; size as usize]),
+            'D' => ColumnDataBuffer::D(vec![None; size as usize]),
+            'A' => ColumnDataBuffer::A(vec![None; size as usize]),
+            'F' => ColumnDataBuffer::F(vec![None; size as usize]),
             _ => panic!("Wrong data type"),
         }
     }
@@ -41,11 +43,11 @@ impl ColumnDataBuffer {
     /// or the maximum string length (for string type).
     pub fn max_len(&self) -> usize {
         match self {
-            ColumnDataBuffer::I(data) => data.par_iter().map(|x| x.to_string().len()).max().unwrap_or(0),
-            ColumnDataBuffer::E(data) => data.par_iter().map(|x| x.to_string().len()).max().unwrap_or(0),
-            ColumnDataBuffer::D(data) => data.par_iter().map(|x| x.to_string().len()).max().unwrap_or(0),
-            ColumnDataBuffer::A(data) => data.par_iter().map(|x| x.len()).max().unwrap_or(0),
-            ColumnDataBuffer::F(data) => data.par_iter().map(|x| x.to_string().len()).max().unwrap_or(0),
+            ColumnDataBuffer::I(data) => data.par_iter().flatten().map(|x| x.to_string().len()).max().unwrap_or(0),
+            ColumnDataBuffer::E(data) => data.par_iter().flatten().map(|x| x.to_string().len()).max().unwrap_or(0),
+            ColumnDataBuffer::D(data) => data.par_iter().flatten().map(|x| x.to_string().len()).max().unwrap_or(0),
+            ColumnDataBuffer::A(data) => data.par_iter().flatten().map(|x| x.len()).max().unwrap_or(0),
+            ColumnDataBuffer::F(data) => data.par_iter().flatten().map(|x| x.to_string().len()).max().unwrap_or(0),
         }
     }
 
@@ -119,49 +121,72 @@ impl ColumnDataBuffer {
     /// * `bytes` - A slice of bytes representing the value to write.
     /// * `data_type` - The data type of the value (`I`, `E`, `D`, `A`, `F`).
     /// * `idx` - The index at which to write the value.
+    /// * `tnull` - The string marking undefined values of the column (`TNULLn`), if any.
+    ///
+    /// # Returns
+    /// `FitsError::InvalidValue` if the field cannot be parsed as the column type.
+    ///
+    /// # Behavior
+    /// Fields equal to `tnull`, and blank numeric fields, are undefined values.
     ///
     /// # Panics
-    /// Panics if the data type does not match the buffer type or if parsing fails.
-    pub fn write_on_idx(&mut self, bytes : &[u8], data_type : char, idx : i64){
+    /// Panics if the data type does not match the buffer type.
+    pub fn write_on_idx(&mut self, bytes : &[u8], data_type : char, idx : i64, tnull : Option<&str>) -> Result<(), FitsError> {
         let string = String::from_utf8_lossy(bytes).trim_end().trim_start().to_string();
+        let invalid = || FitsError::InvalidValue(string.clone());
+        if tnull.is_some_and(|tnull| tnull.trim() == string) || (string.is_empty() && data_type != 'A') {
+            match self {
+                ColumnDataBuffer::I(data) => data[idx as usize] = None,
+                ColumnDataBuffer::E(data) | ColumnDataBuffer::F(data) => data[idx as usize] = None,
+                ColumnDataBuffer::D(data) => data[idx as usize] = None,
+                ColumnDataBuffer::A(data) => data[idx as usize] = None,
+            }
+            return Ok(());
+        }
         match data_type {
             'I' => {
                 // parse bytes to i32
                 match self {
-                    ColumnDataBuffer::I(data) => data[idx as usize] = string.parse::<i32>().unwrap(),
+                    ColumnDataBuffer::I(data) => data[idx as usize] = Some(string.parse::<i32>().map_err(|_| invalid())?),
                     _ => panic!("Wrong data type"),
                 }
             },
             'E' => {
                 // parse bytes to f32
                 match self {
-                    ColumnDataBuffer::E(data) => data[idx as usize] = string.parse::<f32>().unwrap(),
+                    ColumnDataBuffer::E(data) => data[idx as usize] = Some(parse_float::<f32>(&string).map_err(|_| invalid())?),
                     _ => panic!("Wrong data type"),
                 }
             },
             'D' => {
                 // parse bytes to f64
                 match self {
-                    ColumnDataBuffer::D(data) => data[idx as usize] = string.parse::<f64>().unwrap(),
+                    ColumnDataBuffer::D(data) => data[idx as usize] = Some(parse_float::<f64>(&string).map_err(|_| invalid())?),
                     _ => panic!("Wrong data type"),
                 }
             },
             'A' => {
                 // parse bytes to String
                 match self {
-                    ColumnDataBuffer::A(data) => data[idx as usize] = string,
+                    ColumnDataBuffer::A(data) => data[idx as usize] = Some(string.clone()),
                     _ => panic!("Wrong data type"),
                 }
             },
             'F' => {
                 // parse bytes to f32
                 match self {
-                    ColumnDataBuffer::F(data) => data[idx as usize] = string.parse::<f32>().unwrap(),
+                    ColumnDataBuffer::F(data) => data[idx as usize] = Some(parse_float::<f32>(&string).map_err(|_| invalid())?),
                     _ => panic!("Wrong data type"),
                 }
             },
             _ => panic!("Wrong data type"),
         }
+        Ok(())
     }
     //no need for max_len on bintable
+}
+
+/// Parses an ASCII table floating-point field, accepting the Fortran `D` exponent.
+fn parse_float<T: std::str::FromStr>(string: &str) -> Result<T, T::Err> {
+    string.replace('D', "E").parse::<T>()
 }
\ No newline at end of file
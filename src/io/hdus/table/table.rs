@@ -1,10 +1,11 @@
-use core::panic;
-use std::{fs::File, io::{Read, Write}};
+use std::io::{Read, Seek, Write};
 use crate::io::{
     Header, 
+    errors::FitsError,
     header::card::Card, 
     utils::pad_buffer_to_fits_block,
     utils::pad_read_buffer_to_fits_block,
+    utils::read_exact_or_truncated,
 };
 use crate::io::hdus::table::table_utils::*;
 
@@ -25,6 +26,7 @@ use crate::io::hdus::bintable::split_buffer;
 /// - `start_address` (usize): The starting address of the column data within a row.
 /// - `type_bytes` (usize): The number of bytes used to store the column data.
 /// - `char_type` (char): A character representing the data type of the column.
+/// - `tnull` (Option<String>): The string marking undefined values (`TNULLn`), if specified.
 #[derive(Debug)]
 pub struct Column {
     pub ttype: String, 
@@ -35,6 +37,7 @@ pub struct Column {
     pub start_address: usize,
     pub type_bytes : usize,
     pub char_type: char,
+    pub tnull: Option<String>,
 }
 
 impl Column {
@@ -50,20 +53,24 @@ impl Column {
     /// - `start_address` (usize): The starting address of the column data.
     ///
     /// # Returns
-    /// A new `Column` instance initialized with the provided attributes.
-    pub fn new(ttype: String, tform: String, tunit: Option<String>, tdisp: Option<String>, tbcol: Option<i32>, start_address: usize) -> Self {
-        let tform2 = tform.clone();
+    /// A new `Column` instance initialized with the provided attributes, without `tnull`.
+    ///
+    /// # Errors
+    /// `FitsError::InvalidTform` if `tform` cannot be parsed.
+    pub fn new(ttype: String, tform: String, tunit: Option<String>, tdisp: Option<String>, tbcol: Option<i32>, start_address: usize) -> Result<Self, FitsError> {
+        let (char_type, type_bytes) = get_tform_type_size(&tform)?;
         
-        Column {
+        Ok(Column {
             ttype,
             tform,
             tunit,
             tdisp,
             tbcol,
             start_address, 
-            type_bytes: get_tform_type_size(&tform2).1,
-            char_type: get_tform_type_size(&tform2).0,
-        }
+            type_bytes,
+            char_type,
+            tnull: None,
+        })
     }
 }
 
@@ -77,17 +84,20 @@ impl Column {
 /// - `char`: The data type (e.g., 'I', 'A').
 /// - `usize`: The number of bytes used to store the column data.
 ///
-/// # Panics
-/// If the format string is invalid or cannot be parsed.
-fn get_tform_type_size(tform: &str) -> (char, usize) {
+/// # Errors
+/// `FitsError::InvalidTform` if the format string is empty or the type is not one of `A`, `I`, `F`, `E`, `D`.
+fn get_tform_type_size(tform: &str) -> Result<(char, usize), FitsError> {
     let tform = tform.trim();
-    if tform.len() == 1 {
-        (tform.chars().next().unwrap(), 1)
-    } else {
-        let (type_char, size_str) = tform.split_at(1);
-        let size = size_str.split('.').next().unwrap().parse::<usize>().unwrap_or(1);
-        (type_char.chars().next().unwrap(), size)
+    let type_char = match tform.chars().next() {
+        Some(c @ ('A' | 'I' | 'F' | 'E' | 'D')) => c,
+        _ => return Err(FitsError::InvalidTform(tform.to_string())),
+    };
+    let size_str = &tform[1..];
+    if size_str.is_empty() {
+        return Ok((type_char, 1));
     }
+    let size = size_str.split('.').next().unwrap_or("").parse::<usize>().unwrap_or(1);
+    Ok((type_char, size))
 }
 
 /// Reads table metadata from a FITS header and generates a list of `Column` instances.
@@ -96,14 +106,15 @@ fn get_tform_type_size(tform: &str) -> (char, usize) {
 /// - `header` (&Header): The FITS header containing table information.
 ///
 /// # Returns
-/// `Result<Vec<Column>, String>`: A vector of `Column` instances or an error string if the header is invalid.
+/// `Result<Vec<Column>, FitsError>`: A vector of `Column` instances, or a `FitsError` if the header is invalid.
 ///
 /// # Behavior
-/// - Parses metadata like `TTYPE`, `TFORM`, and `TUNIT` for each field.
+/// - Parses metadata like `TTYPE`, `TFORM`, `TUNIT` and `TNULL` for each field.
 /// - Calculates start addresses and data type sizes.
-pub fn read_tableinfo_from_header(header: &Header) -> Result<Vec<Column>, String> {
+/// - Fields without a `TTYPEn` are named `col{n}`.
+pub fn read_tableinfo_from_header(header: &Header) -> Result<Vec<Column>, FitsError> {
     let mut columns: Vec<Column> = Vec::new();
-    let tfields = header["TFIELDS"].value.as_int().unwrap_or(0);
+    let tfields = header.get_int("TFIELDS")?;
 
     for i in 1..=tfields {
         let ttype = header.get_card(&format!("TTYPE{}", i));
@@ -112,34 +123,19 @@ pub fn read_tableinfo_from_header(header: &Header) -> Result<Vec<Column>, String
         let tdisp = header.get_card(&format!("TDISP{}", i));
         let tbcol = header.get_card(&format!("TBCOL{}", i));
 
-        if ttype.is_none() {
-            break;
-        }
-
-        let ttype = ttype.unwrap().value.to_string();
-        let tform = tform.unwrap().value.to_string();
+        let ttype = ttype.map_or_else(|| format!("col{}", i), |c| c.value.to_string());
+        let tform = tform
+            .ok_or_else(|| FitsError::MissingKeyword(format!("TFORM{}", i)))?
+            .value.to_string();
         let tunit = tunit.map(|c| c.value.to_string());
         let tdisp = tdisp.map(|c| c.value.to_string());
-        let tbcol = tbcol.map(|c| c.value.to_string().parse::<i32>().unwrap());
-
-        println!("ttype: {:?} ", ttype);
-        println!("tform: {:?} ", tform);
-        println!("tunit: {:?} ", tunit);
-        println!("tdisp: {:?} ", tdisp);
-        println!("tbcol: {:?} ", tbcol);
-        println!("-------");
+        let tbcol = tbcol
+            .and_then(|c| c.value.as_int())
+            .filter(|&tbcol| tbcol >= 1)
+            .ok_or_else(|| FitsError::MissingKeyword(format!("TBCOL{}", i)))? as i32;
 
-        let tform2 = tform.clone();
-        let column = Column {
-            ttype,
-            tform,
-            tunit,
-            tdisp,
-            tbcol,
-            start_address: tbcol.unwrap_or(0) as usize - 1,
-            type_bytes: get_tform_type_size(&tform2).1,
-            char_type: get_tform_type_size(&tform2).0,
-        };
+        let mut column = Column::new(ttype, tform, tunit, tdisp, Some(tbcol), tbcol as usize - 1)?;
+        column.tnull = header.get_card(&format!("TNULL{}", i)).map(|c| c.value.to_string());
 
         columns.push(column);
     }
@@ -152,15 +148,17 @@ pub fn read_tableinfo_from_header(header: &Header) -> Result<Vec<Column>, String
 /// # Arguments
 /// - `columns` (&mut Vec<Column>): The list of columns describing the table structure.
 /// - `nrows` (i64): The number of rows in the table.
-/// - `file` (&mut File): The file from which to read the table data.
+/// - `file` (&mut R): The file or reader from which to read the table data.
 ///
 /// # Returns
-/// `Result<DataFrame, std::io::Error>`: A DataFrame containing the table data or an I/O error.
+/// `Result<DataFrame, FitsError>`: A DataFrame containing the table data, or a `FitsError`
+/// if the data is truncated or a field cannot be parsed.
 ///
 /// # Behavior
 /// - Uses parallel processing to read and parse table data in chunks.
 /// - Converts binary data into a structured DataFrame.
-pub fn read_table_bytes_to_df(columns : &mut Vec<Column>, nrows: i64, file: &mut File) -> Result<DataFrame, std::io::Error> {
+/// - Blank numeric fields and fields equal to `TNULLn` are read as nulls.
+pub fn read_table_bytes_to_df<R: Read + Seek + ?Sized>(columns : &mut [Column], nrows: i64, file: &mut R) -> Result<DataFrame, FitsError> {
     let mut n_chunks: u16 = 1;
     let mut n_threads: u16 = num_cpus::get() as u16;
 
@@ -176,10 +174,10 @@ pub fn read_table_bytes_to_df(columns : &mut Vec<Column>, nrows: i64, file: &mut
     let limits = split_buffer(buffer_size, n_chunks, bytes_per_row as u16);
     
     let mut buffer = vec![0; buffer_size];
-    file.read_exact(&mut buffer)?;
+    read_exact_or_truncated(file, &mut buffer)?;
 
-    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads as usize).build().unwrap();
-    let results : Vec<Result<DataFrame, std::io::Error>> = pool.install(|| { 
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads as usize).build()?;
+    let results : Vec<Result<DataFrame, FitsError>> = pool.install(|| { 
         limits.into_par_iter().map(|(start, end)| {
             let local_buffer = &buffer[start..end];
             let nbuffer_rows = (end - start) / bytes_per_row;
@@ -189,15 +187,15 @@ pub fn read_table_bytes_to_df(columns : &mut Vec<Column>, nrows: i64, file: &mut
                 local_buf_cols.push(ColumnDataBuffer::new(&column.tform, nbuffer_rows as i32));
             });
 
-            (0..nbuffer_rows).for_each(|i| {
+            for i in 0..nbuffer_rows {
                 let row_start_idx = i * bytes_per_row;
                 let row = &local_buffer[row_start_idx..row_start_idx + bytes_per_row];
-                columns.iter().enumerate().for_each(|(j, column)| {
-                    let (data_type, size) = get_tform_type_size(&column.tform);
-                    let data = &row[column.start_address..column.start_address + column.type_bytes + 1];
-                    local_buf_cols[j].write_on_idx(data, data_type, i as i64);
-                });
-            });
+                for (j, column) in columns.iter().enumerate() {
+                    let start = column.start_address.min(row.len());
+                    let end = (column.start_address + column.type_bytes + 1).min(row.len());
+                    local_buf_cols[j].write_on_idx(&row[start..end], column.char_type, i as i64, column.tnull.as_deref())?;
+                }
+            }
 
             let df_cols : Vec<Series> = columns.iter().enumerate().map(|(i, column)| {
                 let buf_col = &local_buf_cols[i];
@@ -213,33 +211,39 @@ pub fn read_table_bytes_to_df(columns : &mut Vec<Column>, nrows: i64, file: &mut
     });
     drop(buffer);
 
-    let final_df = results[0].as_ref().unwrap().clone();
-    for i in 1..results.len() {
-        final_df.vstack(results[i].as_ref().unwrap());
+    let mut results = results.into_iter();
+    let mut final_df = match results.next() {
+        Some(df) => df?,
+        None => DataFrame::empty(),
+    };
+    for df in results {
+        final_df.vstack_mut(&df?)
+            .map_err(|e| FitsError::InvalidValue(format!("table chunks could not be stacked ({})", e)))?;
     }
 
     pad_read_buffer_to_fits_block(file, buffer_size)?;
-    Ok(final_df.to_owned())
+    Ok(final_df)
 }
 
-
 /// Converts a Polars DataFrame into a list of `Column` instances.
 ///
 /// # Arguments
 /// - `df` (&DataFrame): The DataFrame to be converted.
 ///
 /// # Returns
-/// `Result<Vec<Column>, std::io::Error>`: A vector of `Column` instances or an I/O error.
+/// `Result<Vec<Column>, FitsError>`: A vector of `Column` instances, or `FitsError::UnsupportedDataType`
+/// for a column type that cannot be written to an ASCII table.
 ///
 /// # Behavior
 /// - Maps DataFrame column types to FITS-compatible formats.
 /// - Calculates starting addresses and data type sizes.
-pub fn polars_to_columns(df: &DataFrame) -> Result<Vec<Column>, std::io::Error> {
+pub fn polars_to_columns(df: &DataFrame) -> Result<Vec<Column>, FitsError> {
     let mut start_address : usize = 0;
-    let mut sum_to_address : usize = 0;
+    let mut sum_to_address : usize;
     let mut max_length = None;
+    let mut columns : Vec<Column> = Vec::new();
 
-    let columns : Vec<Column> = df.get_columns().iter().map(|series| {
+    for series in df.get_columns() {
         let ttype = series.name().to_string();
         let tform = match series.dtype() {
             DataType::Int32 => {
@@ -256,7 +260,7 @@ pub fn polars_to_columns(df: &DataFrame) -> Result<Vec<Column>, std::io::Error>
             },
             DataType::String => {
                 let data = &series.str().unwrap();
-                let mut lmax_length = data.iter().map(|item| item.unwrap_or("").len()).max().unwrap();
+                let mut lmax_length = data.iter().map(|item| item.unwrap_or("").len()).max().unwrap_or(0);
                 if lmax_length % 2 != 0 {
                     lmax_length += 1_usize;
                 }
@@ -264,7 +268,9 @@ pub fn polars_to_columns(df: &DataFrame) -> Result<Vec<Column>, std::io::Error>
                 max_length = Some(lmax_length);
                 format!("A{}", lmax_length)
             },
-            _ => panic!("Unsupported data type"),
+            other => {
+                return Err(FitsError::UnsupportedDataType(format!("{} in column {}", other, ttype)));
+            },
         };
         let mut column = Column::new(
             ttype, 
@@ -273,16 +279,15 @@ pub fn polars_to_columns(df: &DataFrame) -> Result<Vec<Column>, std::io::Error>
             None, 
             Some(start_address as i32 + 1),
             start_address
-        );
+        )?;
 
-        if max_length.is_some() {
-            column.type_bytes = max_length.unwrap();
-            max_length = None;
+        if let Some(length) = max_length.take() {
+            column.type_bytes = length;
         }
 
         start_address += sum_to_address;
-        column
-    }).collect();
+        columns.push(column);
+    }
 
     Ok(columns)
 }
@@ -290,35 +295,28 @@ pub fn polars_to_columns(df: &DataFrame) -> Result<Vec<Column>, std::io::Error>
 /// Calculates the total number of bytes required to store a single row of the table.
 ///
 /// # Arguments
-/// - `columns` (&Vec<Column>): The list of columns in the table.
+/// - `columns` (&[Column]): The list of columns in the table.
 ///
 /// # Returns
 /// `usize`: The total number of bytes in a single row.
 ///
 /// # Behavior
 /// - Adds the sizes of all columns, including padding if necessary.
-pub fn calculate_number_of_bytes_of_row(columns: &Vec<Column>) -> usize {
-    let mut bytes = 0;
-    for column in columns.iter() {
-        let (_, size) = get_tform_type_size(&column.tform);
-        println!("column {:?} ", column.ttype);
-        println!("size {:?} ", size);
-        bytes += size + 1;
-    }
-    bytes
+pub fn calculate_number_of_bytes_of_row(columns: &[Column]) -> usize {
+    columns.iter().map(|column| column.type_bytes + 1).sum()
 }
 
 /// Populates a FITS header with metadata for a table based on its columns.
 ///
 /// # Arguments
 /// - `header` (&mut Header): The FITS header to be updated.
-/// - `columns` (&Vec<Column>): The list of columns describing the table structure.
+/// - `columns` (&[Column]): The list of columns describing the table structure.
 /// - `nrows` (i64): The number of rows in the table.
 ///
 /// # Behavior
 /// - Adds metadata like `BITPIX`, `TFIELDS`, `NAXIS`, and `NAXISn`.
 /// - Includes details for each column, such as `TTYPE` and `TFORM`.
-pub fn create_table_on_header(header: &mut Header, columns: &Vec<Column>, nrows : i64) {
+pub fn create_table_on_header(header: &mut Header, columns: &[Column], nrows : i64) {
     clear_table_on_header(header);
     let tfields = columns.len();
     let num_bytes = calculate_number_of_bytes_of_row(columns);
@@ -351,15 +349,18 @@ pub fn create_table_on_header(header: &mut Header, columns: &Vec<Column>, nrows
 /// # Arguments
 /// - `columns` (Vec<Column>): The list of columns describing the table structure.
 /// - `df` (&DataFrame): The DataFrame containing the table data.
-/// - `file` (&mut File): The file to which the binary buffer will be written.
+/// - `file` (&mut W): The file or writer to which the binary buffer will be written.
 ///
 /// # Returns
-/// `Result<(), std::io::Error>`: Returns `Ok(())` on success or an I/O error.
+/// `Result<(), FitsError>`: Returns `Ok(())` on success, `FitsError::InvalidValue` if a column is
+/// missing from the DataFrame, does not have the type of its TFORM or has a value wider than its
+/// field, or an I/O error.
 ///
 /// # Behavior
 /// - Converts DataFrame rows into binary format based on column types.
+/// - Null values are written as blank fields.
 /// - Pads the buffer to a FITS-compliant block size before writing.
-pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Result<(), std::io::Error> {
+pub fn df_to_buffer<W: Write + ?Sized>(columns: Vec<Column>, df: &DataFrame, file: &mut W) -> Result<(), FitsError> {
     //buffer should be written in utf8
     let nrows = df.height();
     let bytes_per_row = calculate_number_of_bytes_of_row(&columns);
@@ -375,120 +376,58 @@ pub fn df_to_buffer(columns: Vec<Column>, df: &DataFrame, file: &mut File) -> Re
     }
 
     let limits = split_buffer(nrows, n_chunks, bytes_per_row as u16);
-    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads as usize).build().unwrap();
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads as usize).build()?;
 
     let bufs : Vec<Vec<u8>> = pool.install(|| {
-        limits.into_iter().map(|(start, end)| {
+        limits.into_iter().map(|(start, end)| -> Result<Vec<u8>, FitsError> {
             let nbuffer_rows = end - start;
             let local_df = &df.slice(start as i64, nbuffer_rows);
             let mut local_buffer = vec![0x00; nbuffer_rows * bytes_per_row];
 
-            columns.iter().for_each(|column| {
-                let series = &local_df.column(&column.ttype).unwrap();
-                let (_, size) = get_tform_type_size(&column.tform);
-
-                match series.dtype() {
-                    
-                    DataType::Int32 => {
-                        series.i32()
-                            .expect("Expected an i32 series")
-                            .into_iter()
-                            .enumerate()
-                            .for_each(|(j, item)| {
-                                let item = item.unwrap();
-                                let mut string = item.to_string();
-                                while string.len() <= column.type_bytes {
-                                    string.push(' ');
-                                }
-
-                                let row_start_add = j * bytes_per_row;
-                                let col_start_add = row_start_add + column.start_address;
-                                local_buffer[col_start_add..col_start_add + column.type_bytes + 1].copy_from_slice(string.as_bytes());
-                            });
+            for column in columns.iter() {
+                let series = local_df.column(&column.ttype)
+                    .map_err(|_| FitsError::InvalidValue(format!("column {} is missing from the DataFrame", column.ttype)))?;
+                let mismatch = || FitsError::InvalidValue(format!("{} in column {} with TFORM {}", series.dtype(), column.ttype, column.tform));
+                let size = column.type_bytes;
+
+                let fields : Vec<Option<String>> = match (column.char_type, series.dtype()) {
+                    ('I', DataType::Int32) => {
+                        series.i32().map_err(|_| mismatch())?.into_iter().map(|item| item.map(|item| item.to_string())).collect()
                     },
-                    DataType::Float32 => {
-                        series.f32()
-                            .expect("Expected a f32 series")
-                            .into_iter()
-                            .enumerate()
-                            .for_each(|(j, item)| {
-                                let item = item.unwrap();
-                                let mut string = format_scientific(item as f64, size);
-                                while string.len() <= column.type_bytes {
-                                    string.push(' ');
-                                }
-                                println!("column.start_address: {:?} ", column.start_address);
-                                println!("column.type_bytes: {:?} ", column.type_bytes);
-                                println!("column.ttype: {:?} ", column.ttype);
-                                println!("column.tform: {:?} ", column.tform);
-                                println!("buffer len {:?} ", string.len());
-
-                                let row_start_add = j * bytes_per_row;
-                                let col_start_add = row_start_add + column.start_address;
-                                local_buffer[col_start_add..col_start_add + column.type_bytes + 1].copy_from_slice(string.as_bytes());
-                            });
+                    ('E' | 'F' | 'D', DataType::Float32) => {
+                        series.f32().map_err(|_| mismatch())?.into_iter().map(|item| item.map(|item| format_scientific(item as f64, size))).collect()
                     },
-                    DataType::Float64 => {
-                        series.f64()
-                            .expect("Expected a f64 series")
-                            .into_iter()
-                            .enumerate()
-                            .for_each(|(j, item)| {
-                                let item = item.unwrap();
-                                let mut string = format_scientific(item, size);
-                                while string.len() <= column.type_bytes {
-                                    string.push(' ');
-                                }
-
-                                println!("column.start_address: {:?} ", column.start_address);
-                                println!("column.type_bytes: {:?} ", column.type_bytes);
-                                println!("column.ttype: {:?} ", column.ttype);
-                                println!("column.tform: {:?} ", column.tform);
-                                println!("buffer len {:?} ", string.len());
-
-                                let row_start_add = j * bytes_per_row;
-                                let col_start_add = row_start_add + column.start_address;
-                                local_buffer[col_start_add..col_start_add + column.type_bytes + 1].copy_from_slice(string.as_bytes());
-                            });
+                    ('E' | 'F' | 'D', DataType::Float64) => {
+                        series.f64().map_err(|_| mismatch())?.into_iter().map(|item| item.map(|item| format_scientific(item, size))).collect()
                     },
-                    DataType::String => {
-                        series.cast(&DataType::String).unwrap().str()
-                            .expect("Expected a string series")
-                            .into_iter()
-                            .enumerate()
-                            .for_each(|(j, item)| {
-                                let item = item.unwrap_or("");
-                                let mut buffer = item.as_bytes().to_vec();
-                                
-                                while buffer.len() <= column.type_bytes {
-                                    buffer.push(b' ');
-                                }
-
-                                println!("column.start_address: {:?} ", column.start_address);
-                                println!("column.type_bytes: {:?} ", column.type_bytes);
-                                println!("column.ttype: {:?} ", column.ttype);
-                                println!("column.tform: {:?} ", column.tform);
-                                println!("buffer len {:?} ", buffer.len());
-
-                                let row_start_add = j * bytes_per_row;
-                                let col_start_add = row_start_add + column.start_address;
-                                local_buffer[col_start_add..col_start_add + column.type_bytes + 1].copy_from_slice(&buffer);
-                            });
+                    ('A', DataType::String) => {
+                        series.str().map_err(|_| mismatch())?.into_iter().map(|item| item.map(|item| item.to_string())).collect()
                     },
-                    _ => panic!("Unsupported data type"),
+                    _ => return Err(mismatch()),
+                };
+
+                for (j, field) in fields.into_iter().enumerate() {
+                    let mut buffer = field.unwrap_or_default().into_bytes();
+                    if buffer.len() > column.type_bytes + 1 {
+                        return Err(FitsError::InvalidValue(format!(
+                            "{} does not fit in column {} with TFORM {}", String::from_utf8_lossy(&buffer), column.ttype, column.tform
+                        )));
+                    }
+                    buffer.resize(column.type_bytes + 1, b' ');
+
+                    let row_start_add = j * bytes_per_row;
+                    let col_start_add = row_start_add + column.start_address;
+                    local_buffer[col_start_add..col_start_add + column.type_bytes + 1].copy_from_slice(&buffer);
                 }
-            });
-            local_buffer.to_owned()
-        }).collect()
-    });
+            }
+            Ok(local_buffer)
+        }).collect::<Result<_, FitsError>>()
+    })?;
 
     let mut bytes_written = 0;
-    bufs.into_iter().for_each(|buf| {
-        bytes_written += file.write(&buf).unwrap();
-    });
-
-    if bytes_written < nrows * bytes_per_row {
-        panic!("Error writing to file");
+    for buf in bufs {
+        file.write_all(&buf)?;
+        bytes_written += buf.len();
     }
 
     pad_buffer_to_fits_block(file, bytes_written)?;
@@ -50,17 +50,21 @@ where
 
 
 pub fn clear_table_on_header(header: &mut Header) {
-    if header.get_card("TFIELDS").is_some(){
-        let tfields = header["TFIELDS"].value.as_int().unwrap_or(0);
+    if let Ok(tfields) = header.get_value("TFIELDS") {
+        let tfields = tfields.as_int().unwrap_or(0);
         for i in 1..=tfields {
             header.remove(&format!("TTYPE{}", i));
             header.remove(&format!("TFORM{}", i));
             header.remove(&format!("TUNIT{}", i));
             header.remove(&format!("TDISP{}", i));
             header.remove(&format!("TBCOL{}", i));
+            header.remove(&format!("TSCAL{}", i));
+            header.remove(&format!("TZERO{}", i));
+            header.remove(&format!("TNULL{}", i));
         }
         header.remove("XTENSION");
         header.remove("PCOUNT");
+        header.remove("THEAP");
         header.remove("GCOUNT");
         header.remove("BITPIX");
         header.remove("TFIELDS");
@@ -1,7 +1,7 @@
-use std::fs::File;
-use std::io::Result;
+use std::io::{Read, Seek, Write};
 
 use polars::frame::DataFrame;
+use crate::io::errors::FitsError;
 use crate::io::hdus::table::table::*;
 
 use crate::io::header::card::Card;
@@ -58,39 +58,43 @@ impl TableHDU {
     /// Reads a FITS table HDU from a file and constructs a `TableHDU` instance.
     ///
     /// # Arguments
-    /// - `f` (&mut File): The file to read from.
+    /// - `f` (&mut R): The file or reader to read from.
     ///
     /// # Returns
-    /// `Result<TableHDU, std::io::Error>`: A `TableHDU` instance containing the header and table data, or an I/O error.
+    /// `Result<TableHDU, FitsError>`: A `TableHDU` instance containing the header and table data, or a `FitsError`.
     ///
     /// # Behavior
     /// - Reads the header and validates mandatory keywords.
     /// - Extracts column metadata and reads table bytes into a DataFrame.
-    pub fn read_from_file(f: &mut File) -> Result<Self>  {
-        //TODO: Check for mandatory words
+    pub fn read_from_file<R: Read + Seek + ?Sized>(f: &mut R) -> Result<Self, FitsError>  {
         let mut header = Header::new();
         header.read_from_file(f)?;
-        let mut columns = read_tableinfo_from_header(&header).unwrap();
-        let df = read_table_bytes_to_df(&mut columns, header["NAXIS2"].value.as_int().unwrap_or(0), f);
-        Ok(Self::new(header, df?))
+        if !header.are_mandatory_keywords_first(&MANDATORY_KEYWORDS) {
+            return Err(FitsError::InvalidHeader(
+                format!("ASCII table header must start with {}", MANDATORY_KEYWORDS.join(", "))
+            ));
+        }
+        let mut columns = read_tableinfo_from_header(&header)?;
+        let df = read_table_bytes_to_df(&mut columns, header.get_int("NAXIS2")?, f)?;
+        Ok(Self::new(header, df))
     }
 
     /// Writes the `TableHDU` instance to a file.
     ///
     /// # Arguments
-    /// - `f` (&mut File): The file to write to.
+    /// - `f` (&mut W): The file or writer to write to.
     ///
     /// # Returns
-    /// `Result<(), std::io::Error>`: Returns `Ok(())` on success or an I/O error.
+    /// `Result<(), FitsError>`: Returns `Ok(())` on success or a `FitsError`.
     ///
     /// # Behavior
     /// - Ensures mandatory keywords are in the correct order.
     /// - Converts the table data into FITS-compatible binary format.
     /// - Writes the header and table data to the file.
-    pub fn write_to_file(&mut self, mut f: &mut File) -> Result<()> {
+    pub fn write_to_file<W: Write + ?Sized>(&mut self, mut f: &mut W) -> Result<(), FitsError> {
         //TODO: This function should not repeat here and in primary hdu
         self.header.fix_header_w_mandatory_order(&MANDATORY_KEYWORDS);
-        let columns = polars_to_columns(&self.data).unwrap();
+        let columns = polars_to_columns(&self.data)?;
         create_table_on_header(&mut self.header, &columns, self.data.height() as i64);
         self.header.fix_header_w_mandatory_order(&MANDATORY_KEYWORDS);
         self.header.write_to_buffer(&mut f)?;
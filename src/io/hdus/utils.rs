@@ -1,10 +1,12 @@
 use std::io::{self, Read, Seek, SeekFrom};
-use std::fs::File;
+
+use crate::io::errors::FitsError;
+use crate::io::header::Header;
 
 /// Checks if there is more data to read in the file.
 ///
 /// # Arguments
-/// - `file` (&mut File): A mutable reference to the file to check.
+/// - `file` (&mut R): A mutable reference to the file or reader to check.
 ///
 /// # Returns
 /// - `io::Result<bool>`: 
@@ -16,7 +18,7 @@ use std::fs::File;
 /// - Saves the current position of the file cursor.
 /// - Attempts to read a single byte to determine if there is more data.
 /// - Restores the file cursor to its original position.
-pub fn buffer_has_more_data(file: &mut File) -> io::Result<bool> {
+pub fn buffer_has_more_data<R: Read + Seek + ?Sized>(file: &mut R) -> io::Result<bool> {
     let current_pos = file.stream_position()?; // Save current position
 
     let mut buffer = [0; 1]; // Small buffer to attempt reading
@@ -25,4 +27,57 @@ pub fn buffer_has_more_data(file: &mut File) -> io::Result<bool> {
     file.seek(SeekFrom::Start(current_pos))?; // Restore original position
 
     Ok(bytes_read != 0) // If bytes_read is 0, we're at EOF
-}
\ No newline at end of file
+}
+
+/// Computes the size of the data unit described by a header, without the block padding.
+///
+/// # Arguments
+/// - `header` (&Header): The header of the HDU.
+///
+/// # Returns
+/// - `Result<usize, FitsError>`: The number of data bytes, or `FitsError::MissingKeyword` if
+///   `BITPIX`, `NAXIS` or one of the `NAXISn` keywords is missing.
+///
+/// # Behavior
+/// - Applies the general formula of the standard: `|BITPIX| / 8 * GCOUNT * (PCOUNT + NAXIS1 * ... * NAXISn)`.
+/// - `PCOUNT` and `GCOUNT` default to 0 and 1 when absent (e.g. in a primary HDU).
+/// - A header with `NAXIS = 0` has no data.
+pub fn data_size_from_header(header: &Header) -> Result<usize, FitsError> {
+    let bitpix = header.get_int("BITPIX")?;
+    let naxis = header.get_int("NAXIS")?;
+    if naxis <= 0 {
+        return Ok(0);
+    }
+
+    let mut nelements: usize = 1;
+    for i in 1..=naxis {
+        nelements = nelements.checked_mul(get_size(header, &format!("NAXIS{}", i))?)
+            .ok_or_else(|| FitsError::InvalidHeader(format!("NAXIS{} overflows the data size", i)))?;
+    }
+    let pcount = get_size_or(header, "PCOUNT", 0)?;
+    let gcount = get_size_or(header, "GCOUNT", 1)?;
+
+    (bitpix.unsigned_abs() as usize / 8)
+        .checked_mul(gcount)
+        .and_then(|size| size.checked_mul(pcount.checked_add(nelements)?))
+        .ok_or_else(|| FitsError::InvalidHeader("data size overflows".to_string()))
+}
+
+/// Reads a size or count keyword, such as `NAXISn` or `PCOUNT`.
+///
+/// # Arguments
+/// - `header` (&Header): The header holding the keyword.
+/// - `keyword` (&str): The keyword to read.
+///
+/// # Returns
+/// - `Result<usize, FitsError>`: The value, `FitsError::MissingKeyword` if the keyword is absent,
+///   or `FitsError::InvalidHeader` if it is negative.
+pub fn get_size(header: &Header, keyword: &str) -> Result<usize, FitsError> {
+    let value = header.get_int(keyword)?;
+    usize::try_from(value).map_err(|_| FitsError::InvalidHeader(format!("{} = {} is negative", keyword, value)))
+}
+
+/// Reads an optional size or count keyword, using `default` when it is absent.
+pub fn get_size_or(header: &Header, keyword: &str, default: usize) -> Result<usize, FitsError> {
+    if header.contains_key(keyword) { get_size(header, keyword) } else { Ok(default) }
+}
@@ -46,18 +46,19 @@ impl CardValue {
         }
     }
 
-    /// Converts the value to its string representation.
-    pub fn to_string(&self) -> String {
+}
+
+/// Converts the value to its string representation.
+impl std::fmt::Display for CardValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            CardValue::INT(value) => value.to_string(),
-            CardValue::FLOAT(value) => value.to_string(),
-            CardValue::STRING(value) => value.clone(),
-            CardValue::LOGICAL(value) => value.to_string(),
-            CardValue::EMPTY => "".to_string(),
+            CardValue::INT(value) => write!(f, "{}", value),
+            CardValue::FLOAT(value) => write!(f, "{}", value),
+            CardValue::STRING(value) => write!(f, "{}", value),
+            CardValue::LOGICAL(value) => write!(f, "{}", value),
+            CardValue::EMPTY => Ok(()),
         }
     }
-
-
 }
 
 fn check_type(s: &str) -> CardValue {
@@ -144,7 +145,7 @@ impl Card {
     }
 
     /// Writes a formatted string to a writer. The string is truncated to 80 characters and padded with spaces.
-    fn write_formatted_string<W: Write>(&self, writer: &mut W, mut string: String, bytes_count: &mut i32) -> std::io::Result<()> {
+    fn write_formatted_string<W: Write + ?Sized>(&self, writer: &mut W, mut string: String, bytes_count: &mut i32) -> std::io::Result<()> {
         string.truncate(80);
         string.push_str(&" ".repeat(80 - string.len()));
         *bytes_count += 80;
@@ -156,7 +157,7 @@ impl Card {
     /// # Arguments
     /// - `writer`: The writer to write the card to.
     /// - `bytes_count`: A counter to track the number of bytes written.
-    pub fn write_to<W: Write>(&self, writer: &mut W, bytes_count: &mut i32) -> std::io::Result<()> {
+    pub fn write_to<W: Write + ?Sized>(&self, writer: &mut W, bytes_count: &mut i32) -> std::io::Result<()> {
         if self.keyword == "COMMENT" || self.keyword == "HISTORY" || self.value == CardValue::EMPTY {
             self.write_formatted_string(writer, format!("{:<80}", self.keyword), bytes_count)
         } else {
@@ -189,7 +190,7 @@ impl Card {
     /// - `writer`: The writer to write the card to.
     /// - `keyword_string`: The formatted keyword string.
     /// - `bytes_count`: A counter to track the number of bytes written.
-    fn write_string_card<W: Write>(&self, writer: &mut W, keyword_string: String, bytes_count: &mut i32) -> std::io::Result<()> {
+    fn write_string_card<W: Write + ?Sized>(&self, writer: &mut W, keyword_string: String, bytes_count: &mut i32) -> std::io::Result<()> {
         if self.keyword.is_empty() {
             return Ok(());
         }
@@ -224,7 +225,7 @@ impl Card {
     }
     
 
-    fn write_other_card<W: Write>(&self, writer: &mut W, keyword_string: String, bytes_count: &mut i32) -> std::io::Result<()> {
+    fn write_other_card<W: Write + ?Sized>(&self, writer: &mut W, keyword_string: String, bytes_count: &mut i32) -> std::io::Result<()> {
         // using unwrap_or with an empty string as default
         if self.keyword.is_empty() {
             return Ok(());
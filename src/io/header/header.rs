@@ -1,33 +1,13 @@
-use std::fs::File;
-use std::io::Read;
-use std::io::{Error, ErrorKind};
-use std::ops::{Index, IndexMut};
-
+use crate::io::errors::FitsError;
 use crate::io::header::card::{Card, CardValue};
-use crate::io::utils::pad_buffer_to_fits_block;
+use crate::io::utils::{pad_buffer_to_fits_block, read_exact_or_truncated};
 
-use std::io::Write;
+use std::io::{Read, Write};
+#[derive(Debug, Clone)]
 pub struct Header {
     cards: Vec<Card>,
 }
 
-impl Index<&str> for Header {
-    type Output = Card;
-    fn index(&self, card_name: &str) -> &Self::Output {
-        if let Some(card) = self.get_card(card_name){
-            return card;
-        }{
-            panic!("Card {} not found", card_name);
-        }
-    }
-}
-
-impl IndexMut<&str> for Header {
-    fn index_mut(&mut self, card_name: &str) -> &mut Card {
-        self.get_mut_card(card_name).expect("Card not found")
-    }
-}
-
 impl Default for Header {
     fn default() -> Self {
         Self::new()
@@ -101,25 +81,47 @@ impl Header {
         self.cards.iter_mut().find(|card| card.keyword == card_name)
     }
 
-    // pub fn get_value(&self, keyword: &str) -> Result<&str, Error> {
-    //     self.cards.iter().find(|card| card.keyword == keyword).map_or(
-    //         Err(Error::new(ErrorKind::Other, format!("{} keyword not found", keyword))),
-    //         |card| {
-    //             match &card.value {
-    //                 Some(value) => Ok(value.unwrap().to_string()),
-    //                 None => Err(Error::new(ErrorKind::Other, format!("{} keyword has no value", keyword))),
-    //             }
-    //         }
-    //     )
-    // }
-
-    // pub fn parse_header_value<T: std::str::FromStr>(&self, keyword: &str) -> Result<T, std::io::Error> {
-    //     let value_str = self.get_value(keyword)?;
-    //     value_str.parse::<T>().map_err(|_| {
-    //         let err_msg = format!("Failed to parse {} as {}", keyword, std::any::type_name::<T>());
-    //         std::io::Error::new(std::io::ErrorKind::Other, err_msg)
-    //     })
-    // }
+    /// Retrieves the value of a keyword.
+    ///
+    /// # Returns
+    /// - `Result<&CardValue, FitsError>`: The card value, or `FitsError::MissingKeyword` if the keyword is not present.
+    pub fn get_value(&self, keyword: &str) -> Result<&CardValue, FitsError> {
+        self.get_card(keyword)
+            .map(|card| &card.value)
+            .ok_or_else(|| FitsError::MissingKeyword(keyword.to_string()))
+    }
+
+    /// Retrieves the value of a keyword as an integer.
+    ///
+    /// # Returns
+    /// - `Result<i64, FitsError>`: The integer value, `FitsError::MissingKeyword` if the keyword is
+    ///   not present, or `FitsError::InvalidValue` if its value is not an integer.
+    pub fn get_int(&self, keyword: &str) -> Result<i64, FitsError> {
+        let value = self.get_value(keyword)?;
+        value.as_int()
+            .ok_or_else(|| FitsError::InvalidValue(format!("{keyword} = {value}")))
+    }
+
+    /// Retrieves the value of a keyword as a float. Integer values are converted.
+    ///
+    /// # Returns
+    /// - `Result<f64, FitsError>`: The float value, `FitsError::MissingKeyword` if the keyword is
+    ///   not present, or `FitsError::InvalidValue` if its value is not a number.
+    pub fn get_float(&self, keyword: &str) -> Result<f64, FitsError> {
+        let value = self.get_value(keyword)?;
+        value.as_float()
+            .or_else(|| value.as_int().map(|v| v as f64))
+            .ok_or_else(|| FitsError::InvalidValue(format!("{keyword} = {value}")))
+    }
+
+    /// Retrieves the value of a keyword as a string, without the trailing spaces.
+    ///
+    /// # Returns
+    /// - `Result<String, FitsError>`: The string value, or `FitsError::MissingKeyword` if the keyword
+    ///   is not present.
+    pub fn get_string(&self, keyword: &str) -> Result<String, FitsError> {
+        Ok(self.get_value(keyword)?.to_string().trim_end().to_string())
+    }
 
     /// Removes a card from the header.
     pub fn remove(&mut self, keyword: &str) -> Option<Card> {
@@ -142,19 +144,19 @@ impl Header {
     }
 
     /// Returns an iterator over the cards in the header.
-    pub fn iter(&self) -> std::slice::Iter<Card> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Card> {
         self.cards.iter()
     }
 
     /// Returns a mutable iterator over the cards in the header.
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<Card> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Card> {
         self.cards.iter_mut()
     }
 
     /// Pretty prints the header.
     pub fn pretty_print(&self) {
         for card in &self.cards {
-            println!("{} = {} / {}", card.keyword, card.value.to_string(), card.comment.as_ref().unwrap_or(&String::new()));
+            println!("{} = {} / {}", card.keyword, card.value, card.comment.as_ref().unwrap_or(&String::new()));
         }
     }
 
@@ -163,18 +165,22 @@ impl Header {
         for card in &self.cards {
             println!("----------------------------------------");
             println!("Keyword: {}", card.keyword);
-            println!("Value: {}", card.value.to_string());
+            println!("Value: {}", card.value);
             println!("Comment: {}", card.comment.as_ref().unwrap_or(&String::new()));
         }
     }
     
-    /// Reads the header from a file.
-    pub fn read_from_file(&mut self, file: &mut File) -> std::io::Result<()>{
+    /// Reads the header from a file or any other `Read` source.
+    ///
+    /// # Returns
+    /// - `Result<(), FitsError>`: `FitsError::TruncatedData` if the file ends before the `END` card,
+    ///   or `FitsError::InvalidHeader` for a misplaced `CONTINUE` card.
+    pub fn read_from_file<R: Read + ?Sized>(&mut self, file: &mut R) -> Result<(), FitsError> {
         let mut last_card : Card = Card::default();
         
         'outer: loop {
             let mut buffer= [0; 2880];
-            let _ = file.read(&mut buffer[..])?;
+            read_exact_or_truncated(file, &mut buffer[..])?;
             
             for card in buffer.chunks(80) {
                 let card_str = String::from_utf8_lossy(card).trim_end().to_string();
@@ -191,7 +197,7 @@ impl Header {
                 
                 if card_str.contains("CONTINUE  ") {
                     if last_card.value == CardValue::EMPTY {
-                        return Err(Error::new(ErrorKind::Other, "CONTINUE card without previous card"));
+                        return Err(FitsError::InvalidHeader("CONTINUE card without previous card".to_string()));
                     }
                     
                     let value = last_card.value.to_string();
@@ -199,7 +205,7 @@ impl Header {
                         Card::continue_card(&mut last_card, card_str);
                     }
                     else{
-                        return Err(Error::new(ErrorKind::Other, "CONTINUE card without previous card"))
+                        return Err(FitsError::InvalidHeader("CONTINUE card without previous card".to_string()))
                     }
                 }
                 else {
@@ -213,7 +219,7 @@ impl Header {
     } // read_from_buffer
 
     /// Writes the header to a file.
-    pub fn write_to_buffer<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+    pub fn write_to_buffer<W: Write + ?Sized>(&self, writer: &mut W) -> std::io::Result<()> {
         let mut bytes_count = 0;
         for card in &self.cards {
             card.write_to(writer, &mut bytes_count)?;
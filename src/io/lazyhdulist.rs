@@ -0,0 +1,299 @@
+use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use polars::frame::DataFrame;
+
+use crate::io::errors::FitsError;
+use crate::io::header::Header;
+use crate::io::hdulist::HDU;
+
+use crate::io::hdus::image::ImageData;
+use crate::io::hdus::image::image::ImageParser;
+use crate::io::hdus::image::mmap::MmapImage;
+use crate::io::hdus::primaryhdu::PrimaryHDU;
+use crate::io::hdus::image::imagehdu::ImageHDU;
+use crate::io::hdus::table::tablehdu::TableHDU;
+use crate::io::hdus::bintable::bintablehdu::BinTableHDU;
+use crate::io::hdus::table::table as asciitable;
+use crate::io::hdus::bintable::bintable;
+
+use crate::io::hdus::compressed::compressed::{is_compressed_image, image_header_from_compressed, read_compressed_image, CompressionParams};
+use crate::io::hdus::utils::{buffer_has_more_data, data_size_from_header};
+use crate::io::utils::is_gzip_stream;
+
+/// The type of an HDU, as given by its position and `XTENSION` keyword.
+///
+/// A tile-compressed image (a BINTABLE with `ZIMAGE = T`) is an `Image`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HDUKind {
+    Primary,
+    Image,
+    Table,
+    BinTable,
+}
+
+/// The decoded data unit of an HDU.
+///
+/// # Variants
+/// - `Image`: The data of a primary or image HDU.
+/// - `Table`: The data of an ASCII or binary table HDU.
+pub enum HDUData {
+    Image(ImageData),
+    Table(DataFrame),
+}
+
+/// Represents an HDU whose header has been parsed but whose data is only decoded on demand.
+///
+/// # Fields
+/// - `header` (Header): The parsed header of the HDU.
+/// - `kind` (HDUKind): The type of the HDU.
+pub struct LazyHDU {
+    pub header: Header,
+    pub kind: HDUKind,
+    index: usize,
+    header_offset: u64,
+    data_offset: u64,
+    data_size: usize,
+    file: Arc<Mutex<File>>,
+    data: OnceLock<HDUData>,
+}
+
+impl Debug for LazyHDU {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<{:?} HDU at byte offset {} ({} data bytes{})>",
+            self.kind,
+            self.header_offset,
+            self.data_size,
+            if self.is_loaded() { ", loaded" } else { "" }
+        )
+    }
+}
+
+impl LazyHDU {
+    /// Returns the byte offset of the start of the HDU header in the file.
+    pub fn header_offset(&self) -> u64 {
+        self.header_offset
+    }
+
+    /// Returns the byte offset of the start of the HDU data in the file.
+    pub fn data_offset(&self) -> u64 {
+        self.data_offset
+    }
+
+    /// Returns the size of the data unit in bytes, without the block padding.
+    pub fn data_size(&self) -> usize {
+        self.data_size
+    }
+
+    /// Returns `true` if the data of the HDU has already been decoded.
+    pub fn is_loaded(&self) -> bool {
+        self.data.get().is_some()
+    }
+
+    /// Returns the data of the HDU, decoding it from the file on the first call.
+    ///
+    /// # Returns
+    /// - `Result<&HDUData, FitsError>`: The decoded data, or a `FitsError` carrying the
+    ///   index and byte offset of the HDU.
+    ///
+    /// # Behavior
+    /// - The decoded data is cached, so later calls do not read the file again.
+    /// - Scaled image pixels are converted to their physical values, as `HDUList::fromfile` does.
+    pub fn data(&self) -> Result<&HDUData, FitsError> {
+        if let Some(data) = self.data.get() {
+            return Ok(data);
+        }
+        let data = self.load().map_err(|e| e.at(self.index, self.header_offset))?;
+        Ok(self.data.get_or_init(|| data))
+    }
+
+    /// Memory maps the data of a primary or image HDU instead of decoding it.
+    ///
+    /// # Returns
+    /// - `Result<MmapImage, FitsError>`: The memory-mapped image, or `FitsError::InvalidHeader`
+    ///   if the HDU is a table.
+    ///
+    /// # Behavior
+    /// - The pixels are decoded as they are stored; `BSCALE`, `BZERO` and `BLANK` are not applied.
+    pub fn mmap_image(&self) -> Result<MmapImage, FitsError> {
+        let result = match self.kind {
+            HDUKind::Image if is_compressed_image(&self.header) => {
+                Err(FitsError::UnsupportedDataType("memory map of a tile-compressed image".to_string()))
+            },
+            HDUKind::Primary | HDUKind::Image => {
+                let f = self.file.lock().unwrap_or_else(|e| e.into_inner());
+                MmapImage::new(&f, &self.header, self.data_offset)
+            },
+            _ => Err(FitsError::InvalidHeader(format!("{:?} HDU has no image data", self.kind))),
+        };
+        result.map_err(|e| e.at(self.index, self.header_offset))
+    }
+
+    /// Converts the lazy HDU into a fully decoded `HDU`.
+    ///
+    /// # Returns
+    /// - `Result<HDU, FitsError>`: The `HDU`, decoding its data if it was not accessed yet.
+    pub fn into_hdu(mut self) -> Result<HDU, FitsError> {
+        let data = match self.data.take() {
+            Some(data) => data,
+            None => self.load().map_err(|e| e.at(self.index, self.header_offset))?,
+        };
+        let hdu = match (self.kind, data) {
+            (HDUKind::Primary, HDUData::Image(data)) => HDU::Primary(PrimaryHDU::new(physical_header(self.header, &data)?, data)),
+            (HDUKind::Image, HDUData::Image(data)) if is_compressed_image(&self.header) => {
+                let header = physical_header(image_header_from_compressed(&self.header)?, &data)?;
+                let mut hdu = ImageHDU::new(header, data);
+                hdu.compression = Some(CompressionParams::from_header(&self.header)?);
+                HDU::Image(hdu)
+            },
+            (HDUKind::Image, HDUData::Image(data)) => HDU::Image(ImageHDU::new(physical_header(self.header, &data)?, data)),
+            (HDUKind::Table, HDUData::Table(data)) => HDU::Table(TableHDU::new(self.header, data)),
+            (HDUKind::BinTable, HDUData::Table(data)) => HDU::BinTable(BinTableHDU::new(self.header, data)),
+            _ => unreachable!("data is decoded according to the HDU kind"),
+        };
+        Ok(hdu)
+    }
+
+    /// Decodes the data unit from the file.
+    fn load(&self) -> Result<HDUData, FitsError> {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let f: &mut File = &mut guard;
+        f.seek(SeekFrom::Start(self.data_offset))?;
+
+        match self.kind {
+            HDUKind::Image if is_compressed_image(&self.header) => {
+                let (header, data, _) = read_compressed_image(f, &self.header)?;
+                Ok(HDUData::Image(ImageParser::scale_physical(&header, data)?))
+            },
+            HDUKind::Primary | HDUKind::Image => {
+                if self.header.get_int("NAXIS")? == 0 {
+                    return Ok(HDUData::Image(ImageData::EMPTY));
+                }
+                let data = ImageParser::read_from_buffer(f, &self.header)?;
+                Ok(HDUData::Image(ImageParser::scale_physical(&self.header, data)?))
+            },
+            HDUKind::Table => {
+                let mut columns = asciitable::read_tableinfo_from_header(&self.header)?;
+                let df = asciitable::read_table_bytes_to_df(&mut columns, self.header.get_int("NAXIS2")?, f)?;
+                Ok(HDUData::Table(df))
+            },
+            HDUKind::BinTable => {
+                let mut columns = bintable::read_tableinfo_from_header(&self.header)?;
+                let df = bintable::read_table_bytes_to_df(&mut columns, &self.header, f)?;
+                Ok(HDUData::Table(df))
+            },
+        }
+    }
+}
+
+/// Removes the scaling keywords from the header of an image decoded to its physical values.
+fn physical_header(mut header: Header, data: &ImageData) -> Result<Header, FitsError> {
+    if ImageParser::is_scaled(&header)? {
+        ImageParser::strip_scaling_keywords(&mut header, data);
+    }
+    Ok(header)
+}
+
+/// Represents a list of HDUs read with `HDUList::open`.
+///
+/// Only the headers are parsed when the file is opened; the data of each HDU is decoded
+/// the first time `LazyHDU::data` is called on it.
+pub struct LazyHDUList {
+    pub hdus: Vec<LazyHDU>,
+}
+
+impl Debug for LazyHDUList {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.hdus.iter()).finish()
+    }
+}
+
+impl LazyHDUList {
+    /// Scans a FITS file, parsing every header and recording where each data unit starts.
+    ///
+    /// # Arguments
+    /// - `filename` (&str): The path to the file to read from.
+    ///
+    /// # Returns
+    /// - `Result<LazyHDUList, FitsError>`: The list of HDUs, or a `FitsError` carrying the
+    ///   index and byte offset of the HDU whose header could not be read.
+    ///
+    /// # Behavior
+    /// - The data units are skipped using the size given by `BITPIX`, `NAXISn`, `PCOUNT` and `GCOUNT`.
+    /// - The file is kept open so the data can be decoded later.
+    /// - Gzip-compressed files cannot be accessed by offset and are rejected with
+    ///   `FitsError::UnsupportedDataType`; read them with `HDUList::fromfile` instead.
+    pub fn open(filename: &str) -> Result<Self, FitsError> {
+        let file = Arc::new(Mutex::new(File::open(filename)?));
+        let mut guard = file.lock().unwrap_or_else(|e| e.into_inner());
+        let f: &mut File = &mut guard;
+        if is_gzip_stream(f)? {
+            return Err(FitsError::UnsupportedDataType(format!("{} is gzip-compressed and cannot be opened lazily", filename)));
+        }
+        let mut hdus = Vec::new();
+        loop {
+            let index = hdus.len();
+            let header_offset = f.stream_position()?;
+            let hdu = scan_hdu(f, &file, index, header_offset).map_err(|e| e.at(index, header_offset))?;
+            hdus.push(hdu);
+
+            if !buffer_has_more_data(f)? {
+                break;
+            }
+        }
+        drop(guard);
+        Ok(Self { hdus })
+    }
+
+    /// Returns the number of HDUs in the file.
+    pub fn len(&self) -> usize {
+        self.hdus.len()
+    }
+
+    /// Returns `true` if the file contains no HDU.
+    pub fn is_empty(&self) -> bool {
+        self.hdus.is_empty()
+    }
+}
+
+/// Parses the header of the HDU at the cursor and moves the cursor past its data.
+fn scan_hdu(f: &mut File, file: &Arc<Mutex<File>>, index: usize, header_offset: u64) -> Result<LazyHDU, FitsError> {
+    let mut header = Header::new();
+    header.read_from_file(f)?;
+    let data_offset = f.stream_position()?;
+
+    let kind = if index == 0 {
+        HDUKind::Primary
+    } else if header.contains_key("XTENSION") {
+        let mut hdu_type = header.get_value("XTENSION")?.to_string();
+        hdu_type.retain(|c| !c.is_whitespace());
+        match hdu_type.as_str() {
+            "IMAGE" => HDUKind::Image,
+            "TABLE" => HDUKind::Table,
+            "BINTABLE" if is_compressed_image(&header) => HDUKind::Image,
+            "BINTABLE" => HDUKind::BinTable,
+            _ => return Err(FitsError::UnsupportedXtension(hdu_type)),
+        }
+    } else {
+        return Err(FitsError::MissingKeyword("XTENSION".to_string()));
+    };
+
+    let data_size = data_size_from_header(&header)?;
+    let padded_size = data_size.div_ceil(2880) * 2880;
+    f.seek(SeekFrom::Start(data_offset + padded_size as u64))?;
+
+    Ok(LazyHDU {
+        header,
+        kind,
+        index,
+        header_offset,
+        data_offset,
+        data_size,
+        file: Arc::clone(file),
+        data: OnceLock::new(),
+    })
+}
@@ -1,16 +1,21 @@
 mod utils;
+pub mod errors;
 pub mod header;
 pub mod hdus;
 pub mod hdulist;
+pub mod lazyhdulist;
 pub mod getdata;
+pub mod scan;
 
 pub use self::getdata::get_data;
+pub use self::scan::scan_fits;
 pub use self::header::Header;
+pub use self::errors::FitsError;
 
 pub mod compression_algorithms {
-    use std::os::raw::c_char;
+    use std::os::raw::{c_char, c_long};
 
-    extern {
+    extern "C" {
         // ----- ricecomp.c -----
         // - compress
         pub fn fits_rcomp(a: *mut i32, nx: i32, c: *mut u8, clen: i32, nblock: i32) -> i32;
@@ -23,15 +28,21 @@ pub mod compression_algorithms {
         
     
         // ----- quantize.c -----
-        pub fn fits_quantize_float(row: i64, fdata: *mut f32, nxpix: i64, nypix: i64, nullcheck: i32, in_null_value: f32, qlevel: f32, dither_method: i32, idata: *mut i32, bscale: *mut f64, bzero: *mut f64, iminval: *mut i32, imaxval: *mut i32) -> i32;
+        pub fn fits_quantize_float(row: c_long, fdata: *mut f32, nxpix: c_long, nypix: c_long, nullcheck: i32, in_null_value: f32, qlevel: f32, dither_method: i32, idata: *mut i32, bscale: *mut f64, bzero: *mut f64, iminval: *mut i32, imaxval: *mut i32) -> i32;
+        pub fn fits_quantize_double(row: c_long, fdata: *mut f64, nxpix: c_long, nypix: c_long, nullcheck: i32, in_null_value: f64, qlevel: f32, dither_method: i32, idata: *mut i32, bscale: *mut f64, bzero: *mut f64, iminval: *mut i32, imaxval: *mut i32) -> i32;
+        pub fn fits_img_stats_int(array: *mut i32, nx: c_long, ny: c_long, nullcheck: i32, nullvalue: i32, ngoodpix: *mut c_long, minvalue: *mut i32, maxvalue: *mut i32, mean: *mut f64, sigma: *mut f64, noise1: *mut f64, noise2: *mut f64, noise3: *mut f64, noise5: *mut f64, status: *mut i32) -> i32;
+
+        // ----- fits_randoms.c -----
+        pub static mut fits_rand_value: *mut f32;
+        pub fn fits_init_randoms() -> i32;
         
         // ----- pliocomp.c -----
         pub fn pl_p2li(pxsrc: *mut i32, xs: i32, lldst: *mut i16, npix: i32) -> i32;
         pub fn pl_l2pi(ll_src: *mut i16, xs: i32, px_dst: *mut i32, npix: i32) -> i32;
         
         // ----- hcompress.c -----
-        pub fn fits_hcompress(a: *mut i32, ny: i32, nx: i32, scale: i32, output: *mut c_char, nbytes: *mut i64, status: *mut i32) -> i32;
-        pub fn fits_hcompress64(a: *mut i64, ny: i32, nx: i32, scale: i32, output: *mut c_char, nbytes: *mut i64, status: *mut i32) -> i32;
+        pub fn fits_hcompress(a: *mut i32, ny: i32, nx: i32, scale: i32, output: *mut c_char, nbytes: *mut c_long, status: *mut i32) -> i32;
+        pub fn fits_hcompress64(a: *mut i64, ny: i32, nx: i32, scale: i32, output: *mut c_char, nbytes: *mut c_long, status: *mut i32) -> i32;
         
         // ----- hdecompress.c -----
         pub fn fits_hdecompress64(input: *mut c_char, smooth: i32, a: *mut i64, ny: *mut i32, nx: *mut i32, scale: *mut i32, status: *mut i32) -> i32;
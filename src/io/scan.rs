@@ -0,0 +1,164 @@
+//! Lazy scanning of binary tables with Polars.
+//!
+//! `scan_fits` returns a `LazyFrame` whose source is a binary table HDU. Polars pushes the
+//! selected columns, the filters and the leading row limit of the query down to the scan, which
+//! reads the table in batches with `TableBatches`, so that only the selected columns of one batch
+//! are decoded at a time and only the rows passing the filters are kept.
+
+use std::any::Any;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::sync::Arc;
+
+use polars::prelude::*;
+
+use crate::io::errors::FitsError;
+use crate::io::header::Header;
+use crate::io::hdus::bintable::bintable::{ColumnRef, TableBatches, TableProjection};
+use crate::io::lazyhdulist::{HDUKind, LazyHDUList};
+
+/// The number of rows decoded at a time by the scan.
+const SCAN_BATCH_ROWS: usize = 65536;
+
+/// The source of a `LazyFrame` created by `scan_fits`.
+///
+/// # Fields
+/// - `filename` (String): The path to the FITS file.
+/// - `header` (Header): The header of the binary table.
+/// - `data_offset` (u64): The byte offset of the data unit of the table in the file.
+/// - `schema` (SchemaRef): The schema of all the columns of the table.
+struct FitsScan {
+    filename: String,
+    header: Header,
+    data_offset: u64,
+    schema: SchemaRef,
+}
+
+impl FitsScan {
+    /// Opens the file positioned at the start of the data unit of the table.
+    fn open(&self) -> Result<File, FitsError> {
+        let mut f = File::open(&self.filename)?;
+        f.seek(SeekFrom::Start(self.data_offset))?;
+        Ok(f)
+    }
+
+    /// Reads the rows of the table that pass the predicate, keeping the projected columns.
+    fn read(&self, args: &AnonymousScanArgs) -> Result<DataFrame, FitsError> {
+        let nrows = self.header.get_int("NAXIS2")? as usize;
+
+        // The predicate may use columns that are not part of the output.
+        let mut names: Option<Vec<String>> = args.with_columns.as_ref().map(|columns| columns.to_vec());
+        if let (Some(names), Some(predicate)) = (names.as_mut(), &args.predicate) {
+            let mut seen: HashSet<String> = names.iter().cloned().collect();
+            for expr in predicate {
+                if let Expr::Column(name) = expr {
+                    if seen.insert(name.to_string()) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+
+        let mut projection = match &names {
+            Some(names) => TableProjection::columns(names.iter().map(|name| ColumnRef::Name(name.clone()))),
+            None => TableProjection::default(),
+        };
+        // Without a predicate, the row limit maps onto the rows read from the file.
+        if let (Some(n), None) = (args.n_rows, &args.predicate) {
+            projection = projection.with_rows(0..n.min(nrows));
+        }
+
+        let mut f = self.open()?;
+        let batches = TableBatches::with_projection(&mut f, self.header.clone(), SCAN_BATCH_ROWS, &projection)?;
+        let mut df = batches.empty_frame()?;
+        let polars_err = |e: PolarsError| FitsError::InvalidValue(format!("scan of {} failed ({})", self.filename, e));
+        for batch in batches {
+            let mut batch = batch?;
+            if let Some(predicate) = &args.predicate {
+                batch = batch.lazy().filter(predicate.clone()).collect().map_err(polars_err)?;
+            }
+            df.vstack_mut(&batch).map_err(polars_err)?;
+            if args.n_rows.is_some_and(|n| df.height() >= n) {
+                break;
+            }
+        }
+
+        if let Some(n) = args.n_rows {
+            df = df.head(Some(n));
+        }
+        if let Some(columns) = &args.with_columns {
+            df = df.select(columns.iter()).map_err(polars_err)?;
+        }
+        Ok(df)
+    }
+}
+
+impl AnonymousScan for FitsScan {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn scan(&self, scan_opts: AnonymousScanArgs) -> PolarsResult<DataFrame> {
+        self.read(&scan_opts).map_err(|e| polars_err!(ComputeError: "{}", e))
+    }
+
+    fn schema(&self, _infer_schema_length: Option<usize>) -> PolarsResult<SchemaRef> {
+        Ok(self.schema.clone())
+    }
+
+    fn allows_predicate_pushdown(&self) -> bool {
+        true
+    }
+
+    fn allows_projection_pushdown(&self) -> bool {
+        true
+    }
+
+    fn allows_slice_pushdown(&self) -> bool {
+        true
+    }
+}
+
+/// Scans a binary table HDU of a FITS file into a Polars `LazyFrame`.
+///
+/// # Arguments
+/// - `filename` (&str): The path to the FITS file.
+/// - `hdu` (usize): The index of the binary table HDU, the primary HDU being 0.
+///
+/// # Returns
+/// - `Result<LazyFrame, FitsError>`: The lazy frame of the table. `FitsError::InvalidValue` if the
+///   file has no HDU `hdu`, `FitsError::UnsupportedDataType` if the HDU is not a binary table.
+///
+/// # Behavior
+/// - Only the headers are read here; the data is read when the query is collected.
+/// - Only the columns used by the query are decoded, one batch of rows at a time, and the
+///   filters are applied to each batch before the next one is read.
+/// - A row limit such as `head(n)` stops the scan once `n` rows are found.
+pub fn scan_fits(filename: &str, hdu: usize) -> Result<LazyFrame, FitsError> {
+    let hdulist = LazyHDUList::open(filename)?;
+    let table = hdulist.hdus.get(hdu).ok_or_else(|| {
+        FitsError::InvalidValue(format!("HDU {} of a file with {} HDUs", hdu, hdulist.len()))
+    })?;
+    if table.kind != HDUKind::BinTable {
+        return Err(FitsError::UnsupportedDataType(format!("HDU {} is a {:?} HDU, not a binary table", hdu, table.kind)));
+    }
+
+    let mut scan = FitsScan {
+        filename: filename.to_string(),
+        header: table.header.clone(),
+        data_offset: table.data_offset(),
+        schema: Arc::new(Schema::new()),
+    };
+    let mut f = scan.open()?;
+    let schema = TableBatches::new(&mut f, scan.header.clone(), 1)?.empty_frame()?.schema();
+    scan.schema = Arc::new(schema);
+
+    let args = ScanArgsAnonymous {
+        schema: Some(scan.schema.clone()),
+        name: "scan_fits",
+        ..Default::default()
+    };
+    LazyFrame::anonymous_scan(Arc::new(scan), args)
+        .map_err(|e| FitsError::InvalidValue(format!("scan of {} failed ({})", filename, e)))
+}
@@ -1,7 +1,11 @@
-use std::{fs::File, io::{Seek, Write}};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::io::errors::FitsError;
 
 /// Writes the remainder of to reach `FITS_BLOCK_SIZE`.
-pub fn pad_buffer_to_fits_block<W: Write>(writer: &mut W, current_size: usize) -> std::io::Result<()> {
+pub fn pad_buffer_to_fits_block<W: Write + ?Sized>(writer: &mut W, current_size: usize) -> std::io::Result<()> {
     const FITS_BLOCK_SIZE: usize = 2880;
     let remainder = current_size % FITS_BLOCK_SIZE;
     if remainder > 0 {
@@ -13,7 +17,7 @@ pub fn pad_buffer_to_fits_block<W: Write>(writer: &mut W, current_size: usize) -
 }
 
 /// Reads the remainder of to reach `FITS_BLOCK_SIZE`.
-pub fn pad_read_buffer_to_fits_block(file: &mut File, current_size: usize) -> std::io::Result<()> {
+pub fn pad_read_buffer_to_fits_block<R: Seek + ?Sized>(file: &mut R, current_size: usize) -> std::io::Result<()> {
     const FITS_BLOCK_SIZE: usize = 2880;
     let remainder = current_size % FITS_BLOCK_SIZE;
     if remainder > 0 {
@@ -23,4 +27,57 @@ pub fn pad_read_buffer_to_fits_block(file: &mut File, current_size: usize) -> st
     } else {
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Reads exactly `buf.len()` bytes, reporting a short read as `FitsError::TruncatedData`.
+///
+/// # Arguments
+/// - `reader` (&mut R): The source to read from.
+/// - `buf` (&mut [u8]): The buffer to fill.
+///
+/// # Returns
+/// - `Result<(), FitsError>`: `Ok(())` if the buffer was filled.
+pub fn read_exact_or_truncated<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> Result<(), FitsError> {
+    let mut found = 0;
+    while found < buf.len() {
+        match reader.read(&mut buf[found..]) {
+            Ok(0) => return Err(FitsError::TruncatedData { expected: buf.len(), found }),
+            Ok(n) => found += n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(FitsError::Io(e)),
+        }
+    }
+    Ok(())
+}
+
+/// The two magic bytes that start a gzip stream.
+pub const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// Checks if the stream starts with the gzip magic bytes, leaving the cursor where it was.
+pub fn is_gzip_stream<R: Read + Seek + ?Sized>(reader: &mut R) -> Result<bool, FitsError> {
+    let start = reader.stream_position()?;
+    let mut magic = [0u8; 2];
+    let mut found = 0;
+    while found < magic.len() {
+        match reader.read(&mut magic[found..])? {
+            0 => break,
+            n => found += n,
+        }
+    }
+    reader.seek(SeekFrom::Start(start))?;
+    Ok(found == magic.len() && magic == GZIP_MAGIC)
+}
+
+/// Decompresses a whole gzip stream, such as a `.fits.gz` file, into memory.
+///
+/// # Arguments
+/// - `reader` (&mut R): The source, positioned at the start of the gzip stream.
+///
+/// # Returns
+/// - `Result<Vec<u8>, FitsError>`: The decompressed bytes. Files made of several concatenated
+///   gzip members are decompressed as one stream, as `gunzip` does.
+pub fn gunzip_stream<R: Read + ?Sized>(reader: &mut R) -> Result<Vec<u8>, FitsError> {
+    let mut bytes = Vec::new();
+    MultiGzDecoder::new(reader).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
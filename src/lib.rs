@@ -1,3 +1,6 @@
+// The `hdus::image::image`, `hdus::table::table`, ... layout is part of the public API.
+#![allow(clippy::module_inception)]
+
 pub mod io;
 
 pub use self::io::hdulist::HDUList as fits;
\ No newline at end of file
@@ -22,12 +22,11 @@ mod tablehdu_tests {
         //let testfile = common::get_testdata_path("/Users/gustavo/Downloads/SPLUS_DR4_stparam_SPHINX_v1.fits");
         let mut f: File = File::open(testfile)?;
         
-        let end_pos = PrimaryHDU::get_end_byte_position(&mut f);
+        let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
         
         //Seek end_pos 
         f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
 
-        let buffer = vec![0; 2880];
 
         let mut header = Header::new();
         header.read_from_file(&mut f)?;
@@ -61,7 +60,7 @@ mod tablehdu_tests {
         let testfile = common::get_testdata_path("EUVEngc4151imgx.fits");
         let mut f: File = File::open(testfile)?;
     
-        let end_pos = PrimaryHDU::get_end_byte_position(&mut f);
+        let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
         
         //Seek end_pos 
         f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
@@ -86,7 +85,7 @@ mod tablehdu_tests {
         let testfile = common::get_testdata_path("test_array_lenght.fits");
         let mut f: File = File::open(testfile)?;
     
-        let end_pos = PrimaryHDU::get_end_byte_position(&mut f);
+        let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
         
         //Seek end_pos 
         f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
@@ -106,5 +105,542 @@ mod tablehdu_tests {
         Ok(())
     }
 
-}
+    #[test]
+    pub fn read_unnamed_columns() -> Result<()> {
+        use astrors::io::header::card::Card;
+
+        // TTYPEn is optional, the fields after an unnamed one are still read.
+        let mut header = Header::new();
+        for (key, value) in [("TFIELDS", "3"), ("TFORM1", "J"), ("TTYPE2", "FLUX"), ("TFORM2", "E"), ("TFORM3", "2I")] {
+            header.add_card(&Card::new(key.to_string(), value.to_string(), None));
+        }
+        let columns = read_tableinfo_from_header(&header)?;
+        let names: Vec<&str> = columns.iter().map(|c| c.ttype.as_str()).collect();
+        assert_eq!(names, vec!["col1", "FLUX", "col3"]);
+        assert_eq!(columns[2].start_address, 8);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn write_mismatched_columns() -> Result<()> {
+        use polars::prelude::*;
+
+        // Columns that do not describe the DataFrame are reported instead of panicking.
+        let df = df!(
+            "ID" => [1i32, 2],
+            "NAME" => ["a", "b"],
+            "FLUX" => [vec![1.0f32, 2.0], vec![3.0, 4.0]].iter().map(|v| Series::new("", v)).collect::<Vec<_>>()
+        ).unwrap();
+        let columns = polars_to_columns(&df).unwrap();
+        let others = [
+            df!("ID" => [1i64, 2], "NAME" => ["a", "b"], "FLUX" => df.column("FLUX").unwrap().clone()).unwrap(),
+            df!("ID" => [1i32, 2], "NAME" => ["too long", "b"], "FLUX" => df.column("FLUX").unwrap().clone()).unwrap(),
+            df!("ID" => [1i32, 2], "NAME" => ["a", "b"], "FLUX" => [vec![1.0f32, 2.0, 3.0], vec![]].iter().map(|v| Series::new("", v)).collect::<Vec<_>>()).unwrap(),
+            df!("ID" => [1i32, 2], "LABEL" => ["a", "b"], "FLUX" => df.column("FLUX").unwrap().clone()).unwrap(),
+            df!("ID" => [1i32, 2], "NAME" => [1.0f32, 2.0], "FLUX" => df.column("FLUX").unwrap().clone()).unwrap(),
+        ];
+        for other in &others {
+            assert!(df_to_buffer(columns.clone(), other, &mut Vec::new()).is_err());
+        }
+        df_to_buffer(columns, &df, &mut Vec::new())?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn read_bintable_batches() -> Result<()> {
+        use astrors::io::hdulist::{HDUList, HDU};
+        use polars::prelude::*;
+
+        // Batches of the test catalogue add up to the whole table.
+        let testfile = common::get_testdata_path("EUVEngc4151imgx.fits");
+        let mut f: File = File::open(&testfile)?;
+        let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
+        f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
+        let whole = BinTableHDU::read_from_file(&mut f)?;
+
+        f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
+        let batches = BinTableHDU::read_batches(&mut f, 2)?;
+        assert_eq!(batches.nrows(), whole.data.height());
+        let mut batched = DataFrame::empty();
+        for batch in batches {
+            let batch = batch?;
+            assert!(batch.height() <= 2);
+            batched.vstack_mut(&batch).unwrap();
+        }
+        assert!(batched.equals_missing(&whole.data));
+
+        // The cursor ends at the next HDU, even with a partial last batch.
+        let df = df!(
+            "ID" => (0..2500i64).collect::<Vec<_>>(),
+            "FLUX" => (0..2500).map(|i| i as f64 * 0.25).collect::<Vec<_>>()
+        ).unwrap();
+        let mut hdu_list = HDUList::new();
+        hdu_list.add_hdu(HDU::Primary(PrimaryHDU::default()));
+        hdu_list.add_hdu(HDU::BinTable(BinTableHDU::new_data(df.clone())));
+        hdu_list.add_hdu(HDU::BinTable(BinTableHDU::new_data(df.head(Some(3)))));
+        let bytes = hdu_list.to_bytes()?;
+
+        let mut cursor = std::io::Cursor::new(&bytes);
+        PrimaryHDU::read_from_file(&mut cursor)?;
+        let heights: Vec<usize> = BinTableHDU::read_batches(&mut cursor, 1000)?
+            .map(|batch| batch.map(|df| df.height()))
+            .collect::<std::result::Result<_, _>>()?;
+        assert_eq!(heights, vec![1000, 1000, 500]);
+        let last = BinTableHDU::read_from_file(&mut cursor)?;
+        assert!(last.data.equals(&df.head(Some(3))));
+
+        let mut cursor = std::io::Cursor::new(&bytes);
+        PrimaryHDU::read_from_file(&mut cursor)?;
+        assert!(BinTableHDU::read_batches(&mut cursor, 0).is_err());
+
+        // Negative sizes in the header are rejected rather than wrapped.
+        for keyword in ["NAXIS2  =", "PCOUNT  ="] {
+            let mut corrupted = bytes.clone();
+            let card = corrupted.windows(9).position(|w| w == keyword.as_bytes()).unwrap();
+            corrupted[card + 10..card + 30].copy_from_slice(format!("{:>20}", -8).as_bytes());
+            assert!(HDUList::from_bytes(&corrupted).is_err());
+            let mut cursor = std::io::Cursor::new(&corrupted);
+            PrimaryHDU::read_from_file(&mut cursor)?;
+            assert!(BinTableHDU::read_batches(&mut cursor, 1000).is_err());
+        }
+
+        // So are sizes whose sum overflows, even when no row is read.
+        let mut corrupted = bytes.clone();
+        for (keyword, value) in [("NAXIS2  =", 1u64 << 59), ("PCOUNT  =", i64::MAX as u64)] {
+            let card = corrupted.windows(9).position(|w| w == keyword.as_bytes()).unwrap();
+            corrupted[card + 10..card + 30].copy_from_slice(format!("{:>20}", value).as_bytes());
+        }
+        let mut cursor = std::io::Cursor::new(&corrupted);
+        PrimaryHDU::read_from_file(&mut cursor)?;
+        assert!(BinTableHDU::read_batches_projected(&mut cursor, 1000, &TableProjection::default().with_rows(0..0)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn read_bintable_projection() -> Result<()> {
+        use astrors::io::hdulist::{HDUList, HDU};
+        use polars::prelude::*;
+
+        let df = df!(
+            "ID" => (0..500i64).collect::<Vec<_>>(),
+            "RA" => (0..500).map(|i| i as f64 * 0.5).collect::<Vec<_>>(),
+            "MAG" => (0..500).map(|i| i as f32 / 10.0).collect::<Vec<_>>(),
+            "NAME" => (0..500).map(|i| format!("SRC{:04}", i)).collect::<Vec<_>>()
+        ).unwrap();
+        let mut hdu_list = HDUList::new();
+        hdu_list.add_hdu(HDU::Primary(PrimaryHDU::default()));
+        hdu_list.add_hdu(HDU::BinTable(BinTableHDU::new_data(df.clone())));
+        hdu_list.add_hdu(HDU::BinTable(BinTableHDU::new_data(df.head(Some(2)))));
+        let bytes = hdu_list.to_bytes()?;
+        let table_start = {
+            let mut cursor = std::io::Cursor::new(&bytes);
+            PrimaryHDU::read_from_file(&mut cursor)?;
+            cursor.position()
+        };
+
+        // Columns by name or index, in the requested order, for a range of rows.
+        let mut cursor = std::io::Cursor::new(&bytes);
+        cursor.set_position(table_start);
+        let projection = TableProjection {
+            columns: Some(vec!["NAME".into(), 1.into()]),
+            rows: Some(100..110),
+        };
+        let table = BinTableHDU::read_projected(&mut cursor, &projection)?;
+        let expected = df.select(["NAME", "RA"]).unwrap().slice(100, 10);
+        assert!(table.data.equals(&expected));
+        let next = BinTableHDU::read_from_file(&mut cursor)?;
+        assert_eq!(next.data.height(), 2);
+
+        // Batches over a range, and an empty range keeping the schema.
+        cursor.set_position(table_start);
+        let heights: Vec<usize> = BinTableHDU::read_batches_projected(&mut cursor, 40, &TableProjection::columns(["MAG"]).with_rows(250..350))?
+            .map(|batch| batch.map(|df| df.height()))
+            .collect::<std::result::Result<_, _>>()?;
+        assert_eq!(heights, vec![40, 40, 20]);
+        cursor.set_position(table_start);
+        let empty = BinTableHDU::read_projected(&mut cursor, &TableProjection::columns([3usize, 0]).with_rows(500..500))?;
+        assert_eq!(empty.data.height(), 0);
+        assert_eq!(empty.data.get_column_names(), vec!["NAME", "ID"]);
+
+        // Unknown or repeated columns and rows past the end are errors.
+        for projection in [
+            TableProjection::columns(["DEC"]),
+            TableProjection::columns([4usize]),
+            TableProjection::columns(["ID", "ID"]),
+            TableProjection::default().with_rows(490..501),
+        ] {
+            cursor.set_position(table_start);
+            assert!(BinTableHDU::read_projected(&mut cursor, &projection).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn read_variable_length_arrays() -> Result<()> {
+        use common::card;
+        use astrors::io::hdulist::{HDUList, HDU};
+        use polars::prelude::*;
+
+        // Rows of FLUX (1PE), COUNTS (1QJ), LABEL (1PA) and ID (J), the heap starting after a gap.
+        let flux: Vec<Vec<f32>> = vec![vec![1.5, 2.5], vec![], vec![3.0]];
+        let counts: Vec<Vec<i32>> = vec![vec![], vec![7, -8, 9], vec![42]];
+        let labels = ["ab", "hello", ""];
+        let (row_size, gap) = (36, 12);
+        let mut rows = Vec::new();
+        let mut heap = Vec::new();
+        for i in 0..3 {
+            rows.extend((flux[i].len() as u32).to_be_bytes());
+            rows.extend((heap.len() as u32).to_be_bytes());
+            heap.extend(flux[i].iter().flat_map(|v| v.to_be_bytes()));
+            rows.extend((counts[i].len() as u64).to_be_bytes());
+            rows.extend((heap.len() as u64).to_be_bytes());
+            heap.extend(counts[i].iter().flat_map(|v| v.to_be_bytes()));
+            rows.extend((labels[i].len() as u32).to_be_bytes());
+            rows.extend((heap.len() as u32).to_be_bytes());
+            heap.extend(labels[i].bytes());
+            rows.extend((i as i32).to_be_bytes());
+        }
+        let cards = [
+            card("XTENSION", "'BINTABLE'"), card("BITPIX", "8"), card("NAXIS", "2"),
+            card("NAXIS1", &row_size.to_string()), card("NAXIS2", "3"),
+            card("PCOUNT", &(gap + heap.len()).to_string()), card("GCOUNT", "1"), card("TFIELDS", "4"),
+            card("TTYPE1", "'FLUX'"), card("TFORM1", "'1PE(2)'"),
+            card("TTYPE2", "'COUNTS'"), card("TFORM2", "'1QJ(3)'"),
+            card("TTYPE3", "'LABEL'"), card("TFORM3", "'1PA(5)'"),
+            card("TTYPE4", "'ID'"), card("TFORM4", "'J'"),
+            card("THEAP", &(3 * row_size + gap).to_string()),
+        ];
+        let mut data = rows;
+        data.extend(vec![0; gap]);
+        data.extend(&heap);
+
+        let mut bytes = common::raw_bintable(&cards, data);
+        let table_start = 2880;
+        let next = df!("X" => [1i32, 2]).unwrap();
+        BinTableHDU::new_data(next.clone()).write_to_file(&mut bytes)?;
+
+        let flux_series = Series::new("FLUX", flux.iter().map(|v| Series::new("", v)).collect::<Vec<_>>());
+        let counts_series = Series::new("COUNTS", counts.iter().map(|v| Series::new("", v)).collect::<Vec<_>>());
+        let expected = DataFrame::new(vec![
+            flux_series,
+            counts_series.cast(&DataType::List(Box::new(DataType::Int32))).unwrap(),
+            Series::new("LABEL", labels),
+            Series::new("ID", [0i32, 1, 2]),
+        ]).unwrap();
+
+        // Whole reads go on to the next HDU after the heap.
+        let hdu_list = HDUList::from_bytes(&bytes).unwrap();
+        match (&hdu_list.hdus[1], &hdu_list.hdus[2]) {
+            (HDU::BinTable(table), HDU::BinTable(last)) => {
+                assert!(table.data.equals(&expected));
+                assert!(last.data.equals(&next));
+            },
+            _ => panic!("expected two binary tables"),
+        }
+
+        // Batches and projections only read the arrays of their rows.
+        let mut cursor = std::io::Cursor::new(&bytes);
+        cursor.set_position(table_start);
+        let mut batched = DataFrame::empty();
+        for batch in BinTableHDU::read_batches(&mut cursor, 2)? {
+            batched.vstack_mut(&batch?).unwrap();
+        }
+        assert!(batched.equals(&expected));
+        assert!(BinTableHDU::read_from_file(&mut cursor)?.data.equals(&next));
 
+        cursor.set_position(table_start);
+        let projection = TableProjection::columns(["COUNTS"]).with_rows(1..3);
+        let table = BinTableHDU::read_projected(&mut cursor, &projection)?;
+        assert!(table.data.equals(&expected.select(["COUNTS"]).unwrap().slice(1, 2)));
+
+        // A descriptor pointing past the heap is an error.
+        let mut corrupted = bytes.clone();
+        let descriptor = table_start as usize + 2880 + 4;
+        corrupted[descriptor..descriptor + 4].copy_from_slice(&1000u32.to_be_bytes());
+        assert!(HDUList::from_bytes(&corrupted).is_err());
+
+        // So are Q descriptors whose size or end overflows, whole or in batches.
+        let counts = table_start as usize + 2880 + 8;
+        for (count, offset) in [(u64::MAX, 0), (1, u64::MAX)] {
+            let mut corrupted = bytes.clone();
+            corrupted[counts..counts + 8].copy_from_slice(&count.to_be_bytes());
+            corrupted[counts + 8..counts + 16].copy_from_slice(&offset.to_be_bytes());
+            assert!(HDUList::from_bytes(&corrupted).is_err());
+            let mut cursor = std::io::Cursor::new(&corrupted);
+            cursor.set_position(table_start);
+            assert!(BinTableHDU::read_batches(&mut cursor, 2)?.any(|batch| batch.is_err()));
+        }
+
+        // The tiles of a compressed image are variable-length byte arrays.
+        let testfile = common::get_testdata_path("test_comp_image.fits");
+        let mut f: File = File::open(testfile)?;
+        let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
+        f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
+        let tiles = BinTableHDU::read_from_file(&mut f)?;
+        let column = tiles.data.column("COMPRESSED_DATA").unwrap();
+        assert_eq!(column.dtype(), &DataType::List(Box::new(DataType::UInt8)));
+        assert!(column.list().unwrap().into_iter().all(|tile| tile.is_some_and(|t| !t.is_empty())));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn write_variable_length_arrays() -> Result<()> {
+        use astrors::io::hdulist::{HDUList, HDU};
+        use polars::prelude::*;
+
+        let long = "x".repeat(MAX_FIXED_STRING_LENGTH + 1);
+        let df = DataFrame::new(vec![
+            Series::new("FLUX", vec![
+                Series::new("", [1.5f64, 2.5]), Series::new("", [0.0f64; 0]), Series::new("", [3.0f64, 4.0, 5.0]),
+            ]),
+            Series::new("COUNTS", vec![Some(Series::new("", [7i16])), None, Some(Series::new("", [1i16, -2]))]),
+            Series::new("FLAGS", vec![
+                Series::new("", [true, false]), Series::new("", [false]), Series::new("", [true, true, false]),
+            ]),
+            Series::new("FIXED", vec![
+                Series::new("", [1i32, 2]), Series::new("", [3i32, 4]), Series::new("", [5i32, 6]),
+            ]),
+            Series::new("NOTE", ["short", long.as_str(), ""]),
+            Series::new("ID", [0i64, 1, 2]),
+        ]).unwrap();
+
+        let mut hdu_list = HDUList::new();
+        hdu_list.add_hdu(HDU::Primary(PrimaryHDU::default()));
+        hdu_list.add_hdu(HDU::BinTable(BinTableHDU::new_data(df.clone())));
+        hdu_list.add_hdu(HDU::BinTable(BinTableHDU::new_data(df.select(["ID"]).unwrap())));
+        let bytes = hdu_list.to_bytes()?;
+
+        let hdu_list = HDUList::from_bytes(&bytes).unwrap();
+        let table = match &hdu_list.hdus[1] {
+            HDU::BinTable(table) => table,
+            _ => panic!("expected a binary table"),
+        };
+        let tforms: Vec<String> = (1..=6).map(|i| table.header.get_string(&format!("TFORM{}", i)).unwrap()).collect();
+        assert_eq!(tforms, vec!["1PD(3)", "1PI(2)", "1PL(3)", "2J", format!("1PA({})", long.len()).as_str(), "K"]);
+        let heap_size = 8 * 5 + 2 * 3 + 6 + 5 + long.len();
+        assert_eq!(table.header.get_int("PCOUNT").unwrap(), heap_size as i64);
+        assert_eq!(table.header.get_int("THEAP").unwrap(), 3 * (8 * 4 + 8 + 8));
+
+        // The null list comes back as an empty list.
+        let mut expected = df.clone();
+        expected.replace("COUNTS", Series::new("COUNTS", vec![
+            Series::new("", [7i16]), Series::new("", [0i16; 0]), Series::new("", [1i16, -2]),
+        ])).unwrap();
+        assert!(table.data.equals(&expected));
+        match &hdu_list.hdus[2] {
+            HDU::BinTable(last) => assert!(last.data.equals(&df.select(["ID"]).unwrap())),
+            _ => panic!("expected a binary table"),
+        }
+
+        // Fixed-length lists and short strings keep the fixed-width layout.
+        let fixed = df.select(["FIXED", "ID"]).unwrap();
+        let columns = polars_to_columns(&fixed).unwrap();
+        assert_eq!(columns.iter().map(|c| c.tform.as_str()).collect::<Vec<_>>(), vec!["2J", "K"]);
+        let mut hdu = BinTableHDU::new_data(fixed);
+        let mut buffer = Vec::new();
+        hdu.write_to_file(&mut buffer)?;
+        assert_eq!(hdu.header.get_int("PCOUNT").unwrap(), 0);
+        assert!(!hdu.header.contains_key("THEAP"));
+
+        Ok(())
+    }
+
+    /// A data frame written as a binary table and what must be found in the file and read back.
+    struct ColumnsCase {
+        name: &'static str,
+        df: polars::prelude::DataFrame,
+        header: Option<Header>,
+        /// The data frame read back, `df` if not given.
+        expected: Option<polars::prelude::DataFrame>,
+        tforms: Vec<&'static str>,
+        /// Numeric header values, `None` for keywords that must not be written.
+        cards: Vec<(&'static str, Option<f64>)>,
+        /// Bytes expected at offsets of the data unit.
+        fields: Vec<(usize, Vec<u8>)>,
+    }
+
+    #[test]
+    pub fn column_types_round_trip() -> Result<()> {
+        use astrors::io::header::card::Card;
+        use polars::prelude::*;
+
+        fn complex<T: Copy>(name: &str, values: &[(T, T)]) -> Series where Series: NamedFrom<Vec<T>, [T]> {
+            let re = Series::new("re", values.iter().map(|v| v.0).collect::<Vec<T>>());
+            let im = Series::new("im", values.iter().map(|v| v.1).collect::<Vec<T>>());
+            StructChunked::new(name, &[re, im]).unwrap().into_series()
+        }
+
+        // The boolean lists declared as bit arrays in the header are written as X columns.
+        let mut bits = Header::new();
+        for (key, value) in [("TFIELDS", "2"), ("TTYPE1", "FLAGS"), ("TFORM1", "12X"), ("TTYPE2", "MASK"), ("TFORM2", "1PX(10)")] {
+            bits.add_card(&Card::new(key.to_string(), value.to_string(), None));
+        }
+        let flags = [
+            vec![true, false, true, false, false, false, false, false, true, true, true, true],
+            vec![false; 12],
+        ];
+
+        let nulls = DataFrame::new(vec![
+            Series::new("ID", [Some(i32::MIN), None, Some(3)]),
+            Series::new("COUNTS", [Some(0u16), Some(7), None]),
+            Series::new("LEVEL", [None, Some(-128i8), Some(127)]),
+            Series::new("FLUX", [Some(1.5f64), None, Some(f64::INFINITY)]),
+            Series::new("FLAG", [Some(true), None, Some(false)]),
+            Series::new("PAIR", [Some(Series::new("", [Some(1i16), None])), Some(Series::new("", [Some(i16::MIN), Some(2)])), None]),
+            Series::new("SPECTRUM", [Some(Series::new("", [Some(1.0f32), None])), None, Some(Series::new("", [2.0f32]))]),
+            Series::new("POSITION", [None, Some(Series::new("", [0.5f64, -0.5])), Some(Series::new("", [Some(1.0f64), None]))]),
+            Series::new("MASK", [Some(Series::new("", [true, false, true])), None, Some(Series::new("", [false, false, true]))]),
+        ]).unwrap();
+        // Null rows of variable-length columns are read back as empty arrays.
+        let mut empty_spectrum = nulls.clone();
+        empty_spectrum.replace("SPECTRUM", Series::new("SPECTRUM", [Series::new("", [Some(1.0f32), None]), Series::new("", Vec::<f32>::new()), Series::new("", [2.0f32])])).unwrap();
+        let null_row = 4 + 2 + 1 + 8 + 1 + 4 + 8 + 16 + 3;
+
+        let cases = [
+            // The real part comes first, big-endian.
+            ColumnsCase {
+                name: "scalar, fixed-length and variable-length complex columns",
+                df: DataFrame::new(vec![
+                    complex("VIS", &[(1.5f32, -2.0f32), (0.25, 4.0)]),
+                    Series::new("PAIR", vec![
+                        complex("", &[(1.0f64, 2.0f64), (3.0, 4.0)]), complex("", &[(-1.0f64, 0.5f64), (0.0, -8.0)]),
+                    ]),
+                    Series::new("SPECTRUM", vec![
+                        complex("", &[(1.0f32, 1.0f32)]), complex("", &[(2.0f32, -2.0f32), (3.0, 0.0), (4.0, 4.5)]),
+                    ]),
+                ]).unwrap(),
+                header: None,
+                expected: None,
+                tforms: vec!["C", "2M", "1PC(3)"],
+                cards: vec![],
+                fields: vec![(0, 1.5f32.to_be_bytes().to_vec()), (4, (-2.0f32).to_be_bytes().to_vec()), (8, 1.0f64.to_be_bytes().to_vec())],
+            },
+            // Bits are packed most significant first, signed bytes are stored offset by 128.
+            ColumnsCase {
+                name: "bit and byte columns",
+                df: DataFrame::new(vec![
+                    Series::new("FLAGS", flags.iter().map(|row| Series::new("", row)).collect::<Vec<Series>>()),
+                    Series::new("MASK", vec![Series::new("", &[true, true, false]), Series::new("", vec![true; 10])]),
+                    Series::new("COUNTS", &[0u8, 255]),
+                    Series::new("OFFSET", &[-128i8, 127]),
+                    Series::new("PAIR", vec![Series::new("", &[-1i8, 1]), Series::new("", &[0i8, -100])]),
+                ]).unwrap(),
+                header: Some(bits),
+                expected: None,
+                tforms: vec!["12X", "1PX(10)", "B", "B", "2B"],
+                cards: vec![("TZERO3", None), ("TZERO4", Some(-128.0)), ("TZERO5", Some(-128.0))],
+                fields: vec![(0, vec![0xA0, 0xF0]), (2 + 8 + 1, vec![0, 127, 129])],
+            },
+            // Unsigned columns are written with the offset that makes them fit the signed types,
+            // 0u16 is stored as i16::MIN.
+            ColumnsCase {
+                name: "unsigned columns",
+                df: DataFrame::new(vec![
+                    Series::new("U16", [0u16, u16::MAX]),
+                    Series::new("U32", [1u32, u32::MAX]),
+                    Series::new("U64", [u64::MAX, 1 << 63]),
+                    Series::new("PAIR", [Series::new("", [0u16, 1]), Series::new("", [40000u16, 2])]),
+                    Series::new("RAGGED", [Series::new("", [u64::MAX]), Series::new("", [1u64, 2])]),
+                ]).unwrap(),
+                header: None,
+                expected: None,
+                tforms: vec!["I", "J", "K", "2I", "1PK(2)"],
+                cards: vec![
+                    ("TZERO1", Some(32768.0)), ("TZERO2", Some(2147483648.0)), ("TZERO3", Some(9223372036854775808.0)),
+                    ("TZERO4", Some(32768.0)), ("TZERO5", Some(9223372036854775808.0)),
+                ],
+                fields: vec![(0, i16::MIN.to_be_bytes().to_vec())],
+            },
+            // The smallest stored value not taken by a column marks its nulls. Null rows keep
+            // fixed-width columns fixed: their elements are all TNULLn, NaN or undefined logicals,
+            // the null bytes between T and F.
+            ColumnsCase {
+                name: "null values",
+                df: nulls,
+                header: None,
+                expected: Some(empty_spectrum),
+                tforms: vec!["J", "I", "B", "D", "L", "2I", "1PE(2)", "2D", "3L"],
+                cards: vec![
+                    ("TNULL1", Some(i32::MIN as f64 + 1.0)), ("TNULL2", Some(-32767.0)), ("TNULL3", Some(1.0)), ("TNULL4", None),
+                    ("TNULL5", None), ("TNULL6", Some(-32767.0)), ("TNULL7", None), ("TNULL8", None), ("TNULL9", None),
+                ],
+                fields: vec![
+                    (2 * null_row + 16, vec![0x80, 0x01, 0x80, 0x01]),
+                    (28, [f64::NAN.to_be_bytes(), f64::NAN.to_be_bytes()].concat()),
+                    (null_row + 44, vec![0, 0, 0]),
+                    (15, vec![b'T']), (null_row + 15, vec![0]), (2 * null_row + 15, vec![b'F']),
+                ],
+            },
+        ];
+
+        for case in cases {
+            let bytes = common::bintable_bytes(&case.df, case.header);
+            let table = common::read_bintable(&bytes);
+
+            let tforms: Vec<String> = (1..=case.tforms.len()).map(|i| table.header.get_string(&format!("TFORM{}", i)).unwrap()).collect();
+            assert_eq!(tforms, case.tforms, "{}", case.name);
+            for (keyword, value) in case.cards {
+                assert_eq!(table.header.get_float(keyword).ok(), value, "{}: {}", case.name, keyword);
+            }
+
+            let expected = case.expected.unwrap_or(case.df);
+            assert!(table.data.equals_missing(&expected), "{}", case.name);
+            assert_eq!(table.data.dtypes(), expected.dtypes(), "{}", case.name);
+
+            let data_start = 2 * 2880;
+            for (offset, field) in case.fields {
+                assert_eq!(&bytes[data_start + offset..][..field.len()], field.as_slice(), "{}: bytes at {}", case.name, offset);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn write_non_complex_struct_columns() {
+        use polars::prelude::*;
+
+        // Structs that are not pairs of floats are rejected, in scalar and array columns alike.
+        let text = StructChunked::new("TEXT", &[Series::new("re", ["a"]), Series::new("im", ["b"])]).unwrap().into_series();
+        for series in [text.clone(), Series::new("TEXT", [text.clone()]), Series::new("TEXT", [text.clone(), text.head(Some(0))])] {
+            let mut hdu = BinTableHDU::new_data(DataFrame::new(vec![series]).unwrap());
+            assert!(hdu.write_to_file(&mut Vec::new()).is_err());
+        }
+    }
+
+    #[test]
+    pub fn read_scaled_and_unsigned_columns() -> Result<()> {
+        use common::card;
+        use polars::prelude::*;
+
+        // TEMP (I) is scaled, IDS (2J) holds unsigned integers with the standard offset.
+        let temp: [i16; 2] = [-4, 100];
+        let ids: [[i32; 2]; 2] = [[i32::MIN, -1], [0, i32::MAX]];
+        let mut data = Vec::new();
+        for i in 0..2 {
+            data.extend(temp[i].to_be_bytes());
+            data.extend(ids[i].iter().flat_map(|v| v.to_be_bytes()));
+        }
+        let cards = [
+            card("XTENSION", "'BINTABLE'"), card("BITPIX", "8"), card("NAXIS", "2"),
+            card("NAXIS1", "10"), card("NAXIS2", "2"), card("PCOUNT", "0"), card("GCOUNT", "1"), card("TFIELDS", "2"),
+            card("TTYPE1", "'TEMP'"), card("TFORM1", "'I'"), card("TSCAL1", "0.5"), card("TZERO1", "10.0"),
+            card("TTYPE2", "'IDS'"), card("TFORM2", "'2J'"), card("TZERO2", "2147483648"),
+        ];
+        let table = common::read_bintable(&common::raw_bintable(&cards, data));
+        let expected = DataFrame::new(vec![
+            Series::new("TEMP", [8.0f64, 60.0]),
+            Series::new("IDS", [Series::new("", [0u32, 2147483647]), Series::new("", [2147483648u32, u32::MAX])]),
+        ]).unwrap();
+        assert!(table.data.equals(&expected));
+        assert_eq!(table.data.dtypes(), expected.dtypes());
+
+        Ok(())
+    }
+
+}
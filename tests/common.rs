@@ -1,5 +1,17 @@
+#![allow(dead_code)]
+
 use std::path::{Path, PathBuf};
 
+use astrors::io::Header;
+use astrors::io::header::card::Card;
+use astrors::io::hdulist::{HDUList, HDU};
+use astrors::io::hdus::bintable::bintablehdu::BinTableHDU;
+use astrors::io::hdus::compressed::CompressionParams;
+use astrors::io::hdus::image::ImageData;
+use astrors::io::hdus::image::imagehdu::ImageHDU;
+use astrors::io::hdus::primaryhdu::PrimaryHDU;
+use polars::prelude::DataFrame;
+
 
 pub fn get_testdata_path( filename : &str ) -> PathBuf {
     Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("data").join(filename)
@@ -7,4 +19,94 @@ pub fn get_testdata_path( filename : &str ) -> PathBuf {
 
 pub fn get_outtestdata_path( filename : &str ) -> PathBuf {
     Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("data").join("outputs").join(filename)
-}
\ No newline at end of file
+}
+
+/// Path of a scratch file in the system temporary directory, unique to the test process.
+pub fn get_temp_path( filename : &str ) -> PathBuf {
+    std::env::temp_dir().join(format!("astrors-{}-{}", std::process::id(), filename))
+}
+
+/// Formats a header card with its value right-aligned in the fixed-format columns.
+pub fn card(key: &str, value: &str) -> String {
+    format!("{:<80}", format!("{:<8}= {:>20}", key, value))
+}
+
+/// Pads `bytes` with `fill` up to a whole number of 2880-byte blocks.
+pub fn padded(mut bytes: Vec<u8>, fill: u8) -> Vec<u8> {
+    bytes.resize(bytes.len().div_ceil(2880) * 2880, fill);
+    bytes
+}
+
+/// Builds a file holding a default primary HDU and a binary table written by hand from its
+/// header `cards` (without `END`) and its `data` unit, rows and heap included.
+pub fn raw_bintable(cards: &[String], data: Vec<u8>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    PrimaryHDU::default().write_to_file(&mut bytes).unwrap();
+    let mut header = cards.concat();
+    header.push_str(&format!("{:<80}", "END"));
+    bytes.extend(padded(header.into_bytes(), b' '));
+    bytes.extend(padded(data, 0));
+    bytes
+}
+
+/// Writes `df` as the binary table following a default primary HDU, with the cards of `header`
+/// if given, and returns the bytes of the file.
+pub fn bintable_bytes(df: &DataFrame, header: Option<Header>) -> Vec<u8> {
+    let table = match header {
+        Some(header) => BinTableHDU::new(header, df.clone()),
+        None => BinTableHDU::new_data(df.clone()),
+    };
+    let mut hdu_list = HDUList::new();
+    hdu_list.add_hdu(HDU::Primary(PrimaryHDU::default()));
+    hdu_list.add_hdu(HDU::BinTable(table));
+    hdu_list.to_bytes().unwrap()
+}
+
+/// Reads back the binary table following the primary HDU of `bytes`.
+pub fn read_bintable(bytes: &[u8]) -> BinTableHDU {
+    match HDUList::from_bytes(bytes).unwrap().hdus.remove(1) {
+        HDU::BinTable(table) => table,
+        _ => panic!("expected a binary table"),
+    }
+}
+
+/// Writes `df` as a binary table and reads it back.
+pub fn round_trip_bintable(df: &DataFrame, header: Option<Header>) -> BinTableHDU {
+    read_bintable(&bintable_bytes(df, header))
+}
+/// Builds the header of an image extension with the given `BITPIX`, followed by the extra `cards`.
+pub fn image_header(bitpix: i32, cards: &[(&str, &str)]) -> Header {
+    let mut header = Header::new();
+    let bitpix = bitpix.to_string();
+    for (keyword, value) in [("XTENSION", "IMAGE"), ("BITPIX", bitpix.as_str()), ("NAXIS", "0")].iter().chain(cards) {
+        header.add_card(&Card::new(keyword.to_string(), value.to_string(), None));
+    }
+    header
+}
+
+/// Writes `data` as an image extension compressed with `params`, with the extra header `cards`,
+/// and reads it back.
+pub fn round_trip_compressed_image(data: ImageData, params: CompressionParams, cards: &[(&str, &str)]) -> std::io::Result<ImageHDU> {
+    let mut image = ImageHDU::new(image_header(data.get_bitpix(), cards), data);
+    image.compression = Some(params);
+
+    let mut hdu_list = HDUList::new();
+    hdu_list.add_hdu(HDU::Primary(PrimaryHDU::default()));
+    hdu_list.add_hdu(HDU::Image(image));
+    match HDUList::from_bytes(&hdu_list.to_bytes()?)?.hdus.remove(1) {
+        HDU::Image(image) => Ok(image),
+        _ => panic!("compressed image should be read as an image HDU"),
+    }
+}
+
+/// Computes the FITS `DATASUM` of a data unit: the 32-bit ones' complement sum of its big-endian
+/// words, after padding to a whole block.
+pub fn datasum(bytes: &[u8]) -> u32 {
+    let mut padded = bytes.to_vec();
+    padded.resize(bytes.len().div_ceil(2880) * 2880, 0);
+    let mut sum: u64 = padded.chunks_exact(4).map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]) as u64).sum();
+    while sum >> 32 != 0 {
+        sum = (sum & 0xffff_ffff) + (sum >> 32);
+    }
+    sum as u32
+}
@@ -1,6 +1,11 @@
 
+use astrors::fits;
+use astrors::io::hdulist::HDU;
 use astrors::io::hdus::bintable::bintablehdu::BinTableHDU;
+use astrors::io::hdus::compressed::{CompressionParams, CompressionType, QuantizeMethod};
+use astrors::io::hdus::image::ImageData;
 use astrors::io::hdus::primaryhdu::PrimaryHDU;
+use ndarray::{ArrayD, IxDyn};
 
 use std::fs::File;
 use std::io::{Result, Seek};
@@ -14,7 +19,7 @@ fn test_read_fits() -> Result<()> {
 
     let mut f: File = File::open(file)?;
 
-    let end_pos = PrimaryHDU::get_end_byte_position(&mut f);
+    let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
     //Seek end_pos 
     f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
 
@@ -26,6 +31,375 @@ fn test_read_fits() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn read_rice_compressed_image() -> Result<()> {
+    let file = common::get_testdata_path("0.1_0.1_300_R_swp_splus.fits.fz");
+    let hdu_list = fits::fromfile(file.to_str().unwrap())?;
+    assert_eq!(hdu_list.hdus.len(), 2);
+
+    let image = match &hdu_list.hdus[1] {
+        HDU::Image(image) => image,
+        _ => panic!("compressed image should be read as an image HDU"),
+    };
+
+    assert_eq!(image.header.get_int("BITPIX")?, -32);
+    assert_eq!(image.header.get_int("NAXIS1")?, 300);
+    assert_eq!(image.header.get_int("NAXIS2")?, 300);
+    assert!(!image.header.contains_key("ZIMAGE"));
+    assert!(!image.header.contains_key("TFORM1"));
+    assert_eq!(image.header.get_string("FILTER")?, "R");
+
+    let compression = image.compression.as_ref().unwrap();
+    assert_eq!(compression.algorithm, CompressionType::Rice1);
+    assert_eq!(compression.tile, vec![300, 1]);
+
+    // Reference values, as bits, decoded with the fits_rdecomp of CFITSIO and the ZSCALE/ZZERO of each row.
+    match &image.data {
+        ImageData::F32(array) => {
+            assert_eq!(array.shape(), &[300, 300]);
+            assert_eq!(array[[0, 0]].to_bits(), 0xbdaea176);
+            assert_eq!(array[[0, 299]].to_bits(), 0xbe02f919);
+            assert_eq!(array[[150, 150]].to_bits(), 0xbe110945);
+            assert_eq!(array[[299, 0]].to_bits(), 0x3e8f9329);
+            assert_eq!(array[[299, 299]].to_bits(), 0);
+            let bytes: Vec<u8> = array.iter().flat_map(|v| v.to_be_bytes()).collect();
+            assert_eq!(common::datasum(&bytes), 1092342725);
+        },
+        _ => panic!("expected a float image"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn read_dithered_compressed_image() -> Result<()> {
+    use astrors::io::hdulist::HDUList;
+
+    // The tiles of the test image, restored as if they had been dithered from ZDITHER0 = 17.
+    // Reference values computed with the random sequence and unquantization of CFITSIO.
+    let file = common::get_testdata_path("0.1_0.1_300_R_swp_splus.fits.fz");
+    let bytes = std::fs::read(file)?;
+    // Cards are replaced in the header of the compressed image, which starts at the 4th block.
+    let replace_card = |bytes: &mut Vec<u8>, keyword: &str, card: &str| {
+        let position = bytes.chunks_exact(80).enumerate().skip(3 * 36)
+            .find(|(_, c)| c.starts_with(keyword.as_bytes()) && c[8] == b'=').unwrap().0;
+        bytes[position * 80..(position + 1) * 80].copy_from_slice(format!("{:<80}", card).as_bytes());
+    };
+
+    for (method, name) in [(QuantizeMethod::SubtractiveDither1, "SUBTRACTIVE_DITHER_1"), (QuantizeMethod::SubtractiveDither2, "SUBTRACTIVE_DITHER_2")] {
+        let mut dithered = bytes.clone();
+        replace_card(&mut dithered, "ZQUANTIZ", &format!("ZQUANTIZ= '{}'", name));
+        replace_card(&mut dithered, "X01TILE", &format!("ZDITHER0= {:>20}", 17));
+        let mut hdu_list = HDUList::from_bytes(&dithered)?;
+        let image = match hdu_list.hdus.remove(1) {
+            HDU::Image(image) => image,
+            _ => panic!("compressed image should be read as an image HDU"),
+        };
+
+        let compression = image.compression.as_ref().unwrap();
+        assert_eq!(compression.quantize_method, method);
+        assert_eq!(compression.dither_seed, 17);
+        match &image.data {
+            ImageData::F32(array) => {
+                assert_eq!(array[[0, 0]].to_bits(), 0xbdac2ea4);
+                assert_eq!(array[[0, 299]].to_bits(), 0xbe084aba);
+                assert_eq!(array[[150, 150]].to_bits(), 0xbe126b2f);
+                assert_eq!(array[[299, 0]].to_bits(), 0x3e8edf72);
+                assert_eq!(array[[299, 299]].to_bits(), 0xbb32f317);
+                let bytes: Vec<u8> = array.iter().flat_map(|v| v.to_be_bytes()).collect();
+                assert_eq!(common::datasum(&bytes), 3290407988);
+            },
+            _ => panic!("expected a float image"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn read_corrupted_tile_descriptor() -> Result<()> {
+    use astrors::io::hdulist::HDUList;
+
+    let file = common::get_testdata_path("0.1_0.1_300_R_swp_splus.fits.fz");
+    let mut bytes = std::fs::read(file)?;
+    assert!(HDUList::from_bytes(&bytes).is_ok());
+
+    // The rows start after the 3 blocks of the primary header and the 4 blocks of the table header.
+    // A count of 0xFFFFFFFF, -1 if read as signed, points past the heap.
+    let data_start = 7 * 2880;
+    bytes[data_start..data_start + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert!(HDUList::from_bytes(&bytes).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn read_compressed_image_lazy() -> Result<()> {
+    use astrors::io::hdulist::HDUList;
+    use astrors::io::lazyhdulist::{HDUData, HDUKind};
+
+    let file = common::get_testdata_path("test_comp_image.fits");
+    let hdu_list = HDUList::open(file.to_str().unwrap())?;
+    assert_eq!(hdu_list.hdus[1].kind, HDUKind::Image);
+
+    match hdu_list.hdus[1].data()? {
+        HDUData::Image(ImageData::F32(array)) => assert_eq!(array.shape(), &[300, 300]),
+        _ => panic!("expected a float image"),
+    }
+
+    Ok(())
+}
+
+/// Returns the absolute difference of each pixel of `image` to `expected`, 0 between two NaN
+/// pixels and infinite between a NaN and a number, or `None` if the type or shape changed.
+fn pixel_differences(image: &ImageData, expected: &ImageData) -> Option<Vec<f64>> {
+    fn pixels(data: &ImageData) -> Vec<f64> {
+        match data {
+            ImageData::U8(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::I8(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::I16(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::U16(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::I32(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::U32(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::I64(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::U64(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::F32(array) => array.iter().map(|&v| v as f64).collect(),
+            ImageData::F64(array) => array.iter().copied().collect(),
+            ImageData::EMPTY => Vec::new(),
+        }
+    }
+
+    if std::mem::discriminant(image) != std::mem::discriminant(expected) || image.get_shape() != expected.get_shape() {
+        return None;
+    }
+    let differences = pixels(image).into_iter().zip(pixels(expected)).map(|(a, b)| match (a.is_nan(), b.is_nan()) {
+        (true, true) => 0.0,
+        (false, false) => (a - b).abs(),
+        _ => f64::INFINITY,
+    });
+    Some(differences.collect())
+}
+
+/// A smooth background with pseudo-random noise, a NaN pixel at (3, 7) and a constant last row.
+fn noisy_floats() -> ArrayD<f32> {
+    let mut state = 12345u32;
+    ArrayD::from_shape_fn(IxDyn(&[64, 64]), |i| {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        match (i[0], i[1]) {
+            (3, 7) => f32::NAN,
+            (63, _) => 2.5,
+            (x, y) => 100.0 + (x * y) as f32 * 0.01 + (state >> 16) as f32 / 6553.6,
+        }
+    })
+}
+
+/// An integer image of 45 by 33 pixels, the last tile of 16 rows holds a single row.
+fn short_ints() -> ArrayD<i16> {
+    ArrayD::from_shape_fn(IxDyn(&[45, 33]), |i| ((i[0] * 37 + i[1] * 11) % 500) as i16 - 250)
+}
+
+/// An integer image with noise of the given amplitude.
+fn noisy_ints(amplitude: i32) -> ArrayD<i32> {
+    ArrayD::from_shape_fn(IxDyn(&[64, 40]), |i| 1000 + ((i[0] * 7919 + i[1] * 104_729) % 97) as i32 * amplitude)
+}
+
+/// Compression parameters quantizing floating point pixels in steps of 0.01 with `method`.
+fn quantized(algorithm: CompressionType, tile: &[usize], method: QuantizeMethod) -> CompressionParams {
+    let mut params = CompressionParams::new(algorithm, tile.to_vec());
+    params.quantize_level = -0.01;
+    params.quantize_method = method;
+    params.dither_seed = 42;
+    params
+}
+
+/// Compression parameters storing floating point pixels as they are.
+fn unquantized(algorithm: CompressionType, tile: &[usize]) -> CompressionParams {
+    let mut params = CompressionParams::new(algorithm, tile.to_vec());
+    params.quantize_method = QuantizeMethod::NoQuantize;
+    params
+}
+
+#[test]
+fn write_rice_compressed_image() -> Result<()> {
+    use astrors::io::hdulist::HDUList;
+
+    let testfile = common::get_testdata_path("WFPC2u57_2.fits");
+    let mut hdu_list = fits::fromfile(testfile.to_str().unwrap())?;
+    let original = fits::fromfile(testfile.to_str().unwrap())?;
+
+    for hdu in hdu_list.hdus.iter_mut() {
+        // Floating point images need quantization, only the integer extensions are compressed.
+        match hdu {
+            HDU::Image(image) if image.data.get_bitpix() > 0 => {
+                image.compression = Some(CompressionParams::new(CompressionType::Rice1, vec![37, 23, 1]));
+            },
+            _ => {},
+        }
+    }
+    let written = hdu_list.to_bytes()?;
+    assert_eq!(written.len() % 2880, 0);
+
+    let reread = HDUList::from_bytes(&written)?;
+    assert_eq!(reread.hdus.len(), original.hdus.len());
+    let mut compared = 0;
+    for (hdu, expected) in reread.hdus.iter().zip(&original.hdus) {
+        if let (HDU::Image(image), HDU::Image(expected)) = (hdu, expected) {
+            if image.compression.is_none() {
+                continue;
+            }
+            assert_eq!(image.compression.as_ref().unwrap().tile, vec![37, 23, 1]);
+            assert_eq!(image.header.get_int("BITPIX")?, expected.header.get_int("BITPIX")?);
+            let differences = pixel_differences(&image.data, &expected.data).unwrap();
+            assert!(differences.iter().all(|&d| d == 0.0));
+            compared += 1;
+        }
+    }
+    assert_eq!(compared, 3);
+
+    Ok(())
+}
+
+/// A named image, the parameters to compress it with and the largest pixel error allowed.
+type RoundTripCase = (&'static str, fn() -> ImageData, CompressionParams, f64);
+
+#[test]
+fn compressed_image_round_trip() -> Result<()> {
+    // Each image is written with the given parameters and must be read back within the tolerance.
+    // Quantizing in steps of 0.01 restores pixels within half a step.
+    let mut cases: Vec<RoundTripCase> = vec![
+        ("RICE_1 bytes", || ImageData::U8(ArrayD::from_shape_fn(IxDyn(&[45, 31, 3]), |i| ((i[0] * 7 + i[1] * 3 + i[2]) % 256) as u8)),
+            CompressionParams::new(CompressionType::Rice1, vec![16, 16, 1]), 0.0),
+        ("RICE_1 ints", || ImageData::I32(ArrayD::from_shape_fn(IxDyn(&[45, 31, 3]), |i| (i[0] as i32 - 20) * 100_003 + i[1] as i32 * i[2] as i32)),
+            CompressionParams::new(CompressionType::Rice1, vec![16, 16, 1]), 0.0),
+        ("HCOMPRESS_1 shorts", || ImageData::I16(short_ints()), CompressionParams::new(CompressionType::Hcompress1, vec![]), 0.0),
+        // Masks are mostly zero with a few flagged regions, some with values beyond 16 bits.
+        ("PLIO_1 mask", || ImageData::I32(ArrayD::from_shape_fn(IxDyn(&[60, 50]), |i| match (i[0], i[1]) {
+            (10..=20, 5..=30) => 4,
+            (30..=32, _) => 70_000,
+            (x, y) if (x + y) % 17 == 0 => 1,
+            _ => 0,
+        })), CompressionParams::new(CompressionType::Plio1, vec![]), 0.0),
+        ("PLIO_1 byte cube", || ImageData::U8(ArrayD::from_shape_fn(IxDyn(&[33, 7, 2]), |i| ((i[0] / 4 + i[2]) % 3) as u8)),
+            CompressionParams::new(CompressionType::Plio1, vec![]), 0.0),
+        ("RICE_1 floats without dithering", || ImageData::F32(noisy_floats()), quantized(CompressionType::Rice1, &[64, 1], QuantizeMethod::NoDither), 0.0051),
+        ("RICE_1 dithered floats", || ImageData::F32(noisy_floats()), quantized(CompressionType::Rice1, &[64, 1], QuantizeMethod::SubtractiveDither1), 0.0051),
+        ("RICE_1 dithered floats keeping zeros", || ImageData::F32(noisy_floats()), quantized(CompressionType::Rice1, &[64, 1], QuantizeMethod::SubtractiveDither2), 0.0051),
+        ("GZIP_2 quantized floats", || ImageData::F32(ArrayD::from_shape_fn(IxDyn(&[16, 16]), |i| (i[0] * 16 + i[1]) as f32 * 0.123)),
+            quantized(CompressionType::Gzip2, &[16, 4], QuantizeMethod::SubtractiveDither1), 0.0051),
+    ];
+    for (name, algorithm) in [("GZIP_1", CompressionType::Gzip1), ("GZIP_2", CompressionType::Gzip2)] {
+        let params = CompressionParams::new(algorithm, vec![10, 7]);
+        cases.extend([
+            (name, (|| ImageData::U8(ArrayD::from_shape_fn(IxDyn(&[23, 16]), |i| (i[0] * 16 + i[1]) as u8))) as fn() -> ImageData, params.clone(), 0.0),
+            (name, || ImageData::I16(ArrayD::from_shape_fn(IxDyn(&[23, 16]), |i| (i[0] as i16 - 11) * 1500 + i[1] as i16)), params.clone(), 0.0),
+            (name, || ImageData::I32(ArrayD::from_shape_fn(IxDyn(&[23, 16, 2]), |i| (i[0] * i[1]) as i32 * 100_003 - i[2] as i32)), params, 0.0),
+            // Without quantization the floating point pixels are restored exactly, NaN included.
+            (name, || ImageData::F32(ArrayD::from_shape_fn(IxDyn(&[23, 16]), |i| match (i[0], i[1]) {
+                (5, 5) => f32::NAN,
+                (x, y) => (x as f32).sin() * 1e3 + y as f32 / 7.0,
+            })), unquantized(algorithm, &[10, 7]), 0.0),
+            (name, || ImageData::F64(ArrayD::from_shape_fn(IxDyn(&[23, 16]), |i| (i[0] as f64).exp() / (i[1] as f64 + 1.0))), unquantized(algorithm, &[10, 7]), 0.0),
+        ]);
+    }
+
+    for (name, data, params, tolerance) in cases {
+        let image = common::round_trip_compressed_image(data(), params.clone(), &[("OBJECT", name)])?;
+        assert_eq!(image.header.get_string("OBJECT")?, name);
+
+        let compression = image.compression.as_ref().unwrap();
+        assert_eq!(compression.algorithm, params.algorithm, "{}", name);
+        if image.data.get_bitpix() < 0 {
+            assert_eq!(compression.quantize_method, params.quantize_method, "{}", name);
+        }
+        if matches!(params.quantize_method, QuantizeMethod::SubtractiveDither1 | QuantizeMethod::SubtractiveDither2) && image.data.get_bitpix() < 0 {
+            assert_eq!(compression.dither_seed, 42, "{}", name);
+        }
+        let differences = pixel_differences(&image.data, &data()).unwrap_or_else(|| panic!("{}: the image type or shape changed", name));
+        let largest = differences.into_iter().fold(0.0, f64::max);
+        assert!(largest <= tolerance, "{}: pixels differ by up to {}", name, largest);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn write_invalid_compressed_image() {
+    let mut zero_seed = CompressionParams::new(CompressionType::Rice1, vec![]);
+    zero_seed.dither_seed = 0;
+
+    let cases = [
+        ("HCOMPRESS_1 tiles must be 2-dimensional", ImageData::I16(ArrayD::from_elem(IxDyn(&[8, 8, 2]), 1)), CompressionParams::new(CompressionType::Hcompress1, vec![8, 8, 2])),
+        ("PLIO_1 line lists cannot hold negative values", ImageData::I16(ArrayD::from_elem(IxDyn(&[4, 4]), -1)), CompressionParams::new(CompressionType::Plio1, vec![])),
+        ("ZDITHER0 must be between 1 and 10000", ImageData::F32(noisy_floats()), zero_seed),
+        ("only GZIP can store unquantized floats", ImageData::F32(noisy_floats()), unquantized(CompressionType::Rice1, &[])),
+    ];
+    for (reason, data, params) in cases {
+        assert!(common::round_trip_compressed_image(data, params, &[]).is_err(), "{}", reason);
+    }
+}
+
+#[test]
+fn hcompress_lossy_round_trip() -> Result<()> {
+    use astrors::io::hdus::compressed::hcompress::hcompress_scale;
+
+    let mean = |differences: Vec<f64>| differences.iter().sum::<f64>() / differences.len() as f64;
+
+    // Without a tile shape the image is compressed in blocks of 16 rows.
+    let image = common::round_trip_compressed_image(ImageData::I16(short_ints()), CompressionParams::new(CompressionType::Hcompress1, vec![]), &[])?;
+    assert_eq!(image.compression.as_ref().unwrap().tile, vec![45, 16]);
+
+    // A non-zero scale is lossy, the error stays of the order of the scale.
+    let mut params = CompressionParams::new(CompressionType::Hcompress1, vec![32, 20]);
+    params.hcomp_scale = 4.0;
+    params.hcomp_smooth = true;
+    let image = common::round_trip_compressed_image(ImageData::I32(noisy_ints(1)), params, &[])?;
+    let compression = image.compression.as_ref().unwrap();
+    assert_eq!(compression.hcomp_scale, 4.0);
+    assert!(compression.hcomp_smooth);
+    let mean_error = mean(pixel_differences(&image.data, &ImageData::I32(noisy_ints(1))).unwrap());
+    assert!(mean_error < 4.0, "mean error {}", mean_error);
+
+    // A negative scale is relative to the noise of each tile and is written as given.
+    let mut errors = Vec::new();
+    for amplitude in [1, 20] {
+        let mut params = CompressionParams::new(CompressionType::Hcompress1, vec![32, 20]);
+        params.hcomp_scale = -2.5;
+        let image = common::round_trip_compressed_image(ImageData::I32(noisy_ints(amplitude)), params, &[])?;
+        assert_eq!(image.compression.as_ref().unwrap().hcomp_scale, -2.5);
+        errors.push(mean(pixel_differences(&image.data, &ImageData::I32(noisy_ints(amplitude))).unwrap()));
+    }
+    assert!(errors[1] > errors[0] * 5.0, "mean errors {:?}", errors);
+
+    // The scale of a tile follows its noise, positive scales are used as they are.
+    let tile: Vec<i32> = noisy_ints(1).iter().take(32 * 20).copied().collect();
+    let scaled: Vec<i32> = tile.iter().map(|v| v * 10).collect();
+    assert_eq!(hcompress_scale(&tile, 32, 20, 2.6, None)?, 3);
+    let (quiet, loud) = (hcompress_scale(&tile, 32, 20, -1.0, None)?, hcompress_scale(&scaled, 32, 20, -1.0, None)?);
+    assert!(quiet > 0 && (loud - quiet * 10).abs() <= 10, "scales {} and {}", quiet, loud);
+
+    Ok(())
+}
+
+#[test]
+fn quantized_float_exact_pixels() -> Result<()> {
+    // With a noise-based level, the constant last row has no noise to quantize and is kept exactly.
+    let params = CompressionParams::new(CompressionType::Rice1, vec![64, 1]);
+    let image = common::round_trip_compressed_image(ImageData::F32(noisy_floats()), params, &[])?;
+    let differences = pixel_differences(&image.data, &ImageData::F32(noisy_floats())).unwrap();
+    assert!(differences[63 * 64..].iter().all(|&d| d == 0.0));
+
+    // Pixels exactly equal to zero are preserved by SUBTRACTIVE_DITHER_2.
+    let zeros = || ArrayD::from_shape_fn(IxDyn(&[8, 32]), |i| if i[1] % 3 == 0 { 0.0 } else { (i[0] * 32 + i[1]) as f64 * 0.37 });
+    let params = quantized(CompressionType::Rice1, &[32, 8], QuantizeMethod::SubtractiveDither2);
+    let image = common::round_trip_compressed_image(ImageData::F64(zeros()), params, &[])?;
+    let differences = pixel_differences(&image.data, &ImageData::F64(zeros())).unwrap();
+    for (difference, expected) in differences.iter().zip(zeros().iter()) {
+        assert!(*difference <= if *expected == 0.0 { 0.0 } else { 0.0051 });
+    }
+
+    Ok(())
+}
+
 #[test]
 fn compress_random_image() -> Result<()> {
     
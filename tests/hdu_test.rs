@@ -99,7 +99,7 @@ mod hdu_tests {
         let mut primary_hdu = PrimaryHDU::read_from_file(&mut f)?;
         
         let outfile = common::get_outtestdata_path("test_modify.fits");
-        let f_out: File = File::create(&outfile)?;
+        File::create(&outfile)?;
 
         let mut hdus = HDUList::new();
 
@@ -126,6 +126,139 @@ mod hdu_tests {
         Ok(())
     }
 
+    #[test]
+    fn read_truncated_fits() -> Result<()> {
+        use astrors::io::FitsError;
+
+        let testfile = common::get_testdata_path("WFPC2u57_2.fits");
+        let bytes = std::fs::read(testfile)?;
+
+        // Keep the primary HDU header and only part of its data.
+        let err = HDUList::from_bytes(&bytes[..2880 * 3]).unwrap_err();
+        assert_eq!(err.hdu_index(), Some(0));
+        assert_eq!(err.offset(), Some(0));
+        assert!(matches!(err.kind(), FitsError::TruncatedData { .. }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_unsupported_xtension() -> Result<()> {
+        use astrors::io::FitsError;
+        use astrors::io::hdulist::ReadMode;
+
+        let testfile = common::get_testdata_path("WFPC2u57_2.fits");
+        let mut bytes = std::fs::read(testfile)?;
+
+        // Turn the first IMAGE extension into an unsupported one.
+        let offset = 665280;
+        bytes[offset + 10..offset + 20].copy_from_slice(b"'FOOBAR  '");
+
+        let err = HDUList::from_reader_with_mode(&mut std::io::Cursor::new(&bytes), ReadMode::Strict).unwrap_err();
+        assert_eq!(err.hdu_index(), Some(1));
+        assert_eq!(err.offset(), Some(offset as u64));
+        assert!(matches!(err.kind(), FitsError::UnsupportedXtension(_)));
+
+        let (hdu_list, warnings) = HDUList::from_reader_with_mode(&mut std::io::Cursor::new(&bytes), ReadMode::Lenient)?;
+        assert_eq!(hdu_list.hdus.len(), 4);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].hdu_index(), Some(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_lazy() -> Result<()> {
+        use astrors::io::lazyhdulist::{HDUData, HDUKind};
+
+        let testfile = common::get_testdata_path("WFPC2u57_2.fits");
+        let hdu_list = HDUList::open(testfile.to_str().unwrap())?;
+
+        assert_eq!(hdu_list.len(), 5);
+        assert_eq!(hdu_list.hdus[0].kind, HDUKind::Primary);
+        assert_eq!(hdu_list.hdus[1].kind, HDUKind::Image);
+        assert_eq!(hdu_list.hdus[1].header_offset(), 665280);
+        assert!(hdu_list.hdus.iter().all(|hdu| !hdu.is_loaded()));
+
+        let eager = fits::fromfile(testfile.to_str().unwrap())?;
+        let lazy_hdu = &hdu_list.hdus[1];
+        match (lazy_hdu.data()?, &eager.hdus[1]) {
+            (HDUData::Image(lazy), HDU::Image(hdu)) => {
+                assert_eq!(lazy.get_shape(), hdu.data.get_shape());
+                assert_eq!(lazy_hdu.data_size(), lazy.get_shape().iter().product::<usize>() * (lazy.get_bitpix().unsigned_abs() as usize / 8));
+            },
+            _ => panic!("expected image data"),
+        }
+        assert!(lazy_hdu.is_loaded());
+        assert!(!hdu_list.hdus[2].is_loaded());
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_lazy_bintable() -> Result<()> {
+        use astrors::io::lazyhdulist::HDUData;
+
+        let testfile = common::get_testdata_path("EUVEngc4151imgx.fits");
+        let hdu_list = HDUList::open(testfile.to_str().unwrap())?;
+        let eager = fits::fromfile(testfile.to_str().unwrap())?;
+        assert_eq!(hdu_list.len(), eager.hdus.len());
+
+        match (hdu_list.hdus[1].data()?, &eager.hdus[1]) {
+            (HDUData::Table(lazy), HDU::BinTable(hdu)) => assert!(lazy.equals_missing(&hdu.data)),
+            _ => panic!("expected table data"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_write_bytes() -> Result<()> {
+        for name in ["WFPC2u57_2.fits", "EUVEngc4151imgx.fits"] {
+            let testfile = common::get_testdata_path(name);
+            let bytes = std::fs::read(&testfile)?;
+
+            let mut hdu_list = HDUList::from_bytes(&bytes)?;
+            let eager = fits::fromfile(testfile.to_str().unwrap())?;
+            assert_eq!(hdu_list.hdus.len(), eager.hdus.len());
+
+            let written = hdu_list.to_bytes()?;
+            assert_eq!(written.len() % 2880, 0);
+
+            let reread = HDUList::from_bytes(&written)?;
+            assert_eq!(reread.hdus.len(), eager.hdus.len());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_write_gzip() -> Result<()> {
+        use astrors::io::hdulist::HDUList;
+
+        let testfile = common::get_testdata_path("WFPC2u57_2.fits");
+        let mut hdu_list = fits::fromfile(testfile.to_str().unwrap())?;
+        let plain = hdu_list.to_bytes()?;
+
+        let outfile = common::get_temp_path("WFPC2u57_2.fits.gz");
+        hdu_list.write_to_gzip(outfile.to_str().unwrap())?;
+        let gzipped = std::fs::read(&outfile)?;
+        assert_eq!(&gzipped[..2], &[0x1F, 0x8B]);
+        assert!(gzipped.len() < plain.len());
+
+        // Files and in-memory payloads are decompressed transparently.
+        let mut reread = fits::fromfile(outfile.to_str().unwrap())?;
+        assert_eq!(reread.hdus.len(), hdu_list.hdus.len());
+        assert_eq!(reread.to_bytes()?, plain);
+        let mut from_bytes = HDUList::from_bytes(&gzipped)?;
+        assert_eq!(from_bytes.to_bytes()?, plain);
+
+        assert!(HDUList::open(outfile.to_str().unwrap()).is_err());
+        std::fs::remove_file(&outfile)?;
+
+        Ok(())
+    }
+
     #[test]
     fn test() -> Result<()>{
         // let mut hdu_list = fits::fromfile("/Users/gustavo/Downloads/SPLUS_DR4_stparam_SPHINX_v1.fits");
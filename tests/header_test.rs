@@ -46,7 +46,7 @@ mod header_tests {
         // header.pretty_print_advanced();
 
 
-        let card = &mut header["CTYPE1"];
+        let card = header.get_mut_card("CTYPE1").unwrap();
         card.set_value("TESTANDO".to_string());
         card.set_comment("TESTANDO COMMENT".to_string());
 
@@ -55,7 +55,7 @@ mod header_tests {
         println!("Len after remove {}", header.len());
         println!("Header empty {}", header.is_empty());
         header.add_card(&Card::new("KEYWORD".to_string(), "1.394122".to_string(), None));
-        println!("Float value {}", header["KEYWORD"].value.as_float().unwrap());
+        println!("Float value {}", header.get_float("KEYWORD").unwrap());
         println!("Len after newcard {}", header.len());
         println!();
         header.add_card_on_index(&Card::new("KEYWORD1".to_string(), "1.313222".to_string(), None), 212);
@@ -70,4 +70,20 @@ mod header_tests {
         Ok(())
         
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn typed_getters_errors() {
+        use astrors::io::errors::FitsError;
+
+        let mut header = Header::new();
+        header.add_card(&Card::new("NAXIS".to_string(), "'two'".to_string(), None));
+        header.add_card(&Card::new("BSCALE".to_string(), "1.5".to_string(), None));
+
+        // A present keyword of the wrong type is an invalid value, not a missing keyword.
+        assert!(matches!(header.get_int("NAXIS"), Err(FitsError::InvalidValue(_))));
+        assert!(matches!(header.get_float("NAXIS"), Err(FitsError::InvalidValue(_))));
+        assert!(matches!(header.get_int("BSCALE"), Err(FitsError::InvalidValue(_))));
+        assert!(matches!(header.get_int("BZERO"), Err(FitsError::MissingKeyword(_))));
+        assert_eq!(header.get_float("BSCALE").unwrap(), 1.5);
+    }
+}
@@ -18,7 +18,7 @@ mod image_tests {
         header.read_from_file(&mut f)?;
         //header.pretty_print_advanced();
 
-        let data: ImageData = ImageParser::read_from_buffer(&mut f, &mut header)?;
+        let data: ImageData = ImageParser::read_from_buffer(&mut f, &header)?;
         if let ImageData::F32(array) = data {
             println!("Mean {:?}", array.mean().unwrap());
         } else {
@@ -28,6 +28,19 @@ mod image_tests {
         Ok(())
     }
 
+    #[test]
+    fn image_buffer_shape_mismatch() -> Result<()> {
+        use astrors::io::FitsError;
+
+        // A buffer shorter or longer than the shape is an error, not a panic.
+        let err = ImageParser::image_buffer_to_ndarray(&[0; 10], vec![3, 2], 16).unwrap_err();
+        assert!(matches!(err, FitsError::TruncatedData { expected: 12, found: 10 }));
+        assert!(ImageParser::image_buffer_to_ndarray(&[0; 16], vec![3, 1], -32).is_err());
+        assert!(matches!(ImageParser::image_buffer_to_ndarray(&[0; 12], vec![3, 2], 16)?, ImageData::I16(_)));
+
+        Ok(())
+    }
+
     #[test]
     fn modify_and_write_image() -> Result<()> {
         
@@ -37,7 +50,7 @@ mod image_tests {
         header.read_from_file(&mut f)?;
         //header.pretty_print_advanced();
 
-        let mut data = ImageParser::read_from_buffer(&mut f, &mut header)?;
+        let mut data = ImageParser::read_from_buffer(&mut f, &header)?;
         // println!("Data: {:?}", data);
         
         if let ImageData::F32(ref array) = data {
@@ -76,4 +89,326 @@ mod image_tests {
         Ok(())
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn read_image_mmap() -> Result<()> {
+        use astrors::io::hdus::primaryhdu::PrimaryHDU;
+        use ndarray::s;
+
+        let testfile = common::get_testdata_path("WFPC2u57.fits");
+        let mut f: File = File::open(&testfile)?;
+        let eager = PrimaryHDU::read_from_file(&mut f)?;
+
+        let mut f: File = File::open(&testfile)?;
+        let mapped = PrimaryHDU::read_mmap(&mut f)?;
+        assert_eq!(mapped.data.shape(), eager.data.get_shape().as_slice());
+
+        let array = match eager.data {
+            ImageData::F32(array) => array,
+            _ => panic!("Not implemented for test"),
+        };
+
+        match mapped.data.read_all()? {
+            ImageData::F32(all) => assert_eq!(all, array),
+            _ => panic!("Not implemented for test"),
+        }
+
+        match mapped.data.read_region(&[10, 20, 1], &[5, 7, 2])? {
+            ImageData::F32(region) => assert_eq!(region, array.slice(s![10..15, 20..27, 1..3]).into_dyn()),
+            _ => panic!("Not implemented for test"),
+        }
+
+        match mapped.data.read_plane(&[42])? {
+            ImageData::F32(plane) => assert_eq!(plane, array.slice(s![42, .., ..]).into_dyn()),
+            _ => panic!("Not implemented for test"),
+        }
+
+        match mapped.data.read_row(&[42, 7])? {
+            ImageData::F32(row) => assert_eq!(row, array.slice(s![42, 7, ..]).into_dyn()),
+            _ => panic!("Not implemented for test"),
+        }
+
+        let shape = mapped.data.shape().to_vec();
+        assert!(mapped.data.read_region(&[shape[0], 0, 0], &[1, 1, 1]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scaled_image_round_trip() -> Result<()> {
+        use astrors::io::hdulist::{HDUList, HDU, ReadMode, ReadOptions};
+        use astrors::io::hdus::image::ImageScaling;
+        use astrors::io::hdus::image::imagehdu::ImageHDU;
+        use astrors::io::hdus::primaryhdu::PrimaryHDU;
+        use ndarray::{ArrayD, IxDyn};
+
+        // Integers stored with BSCALE, BZERO and a BLANK pixel.
+        let stored = ArrayD::from_shape_fn(IxDyn(&[6, 5]), |i| if i[0] == 2 && i[1] == 3 { -32768 } else { (i[0] * 5 + i[1]) as i16 - 10 });
+        let header = common::image_header(16, &[("BSCALE", "0.5"), ("BZERO", "100.0"), ("BLANK", "-32768")]);
+        let mut hdu_list = HDUList::new();
+        hdu_list.add_hdu(HDU::Primary(PrimaryHDU::default()));
+        hdu_list.add_hdu(HDU::Image(ImageHDU::new(header, ImageData::I16(stored.clone()))));
+        let bytes = hdu_list.to_bytes()?;
+
+        // Physical values by default, with BLANK as NaN.
+        let physical = HDUList::from_bytes(&bytes)?;
+        match &physical.hdus[1] {
+            HDU::Image(image) => {
+                assert!(!image.header.contains_key("BSCALE"));
+                assert!(!image.header.contains_key("BLANK"));
+                assert_eq!(image.header.get_int("BITPIX")?, -32);
+                match &image.data {
+                    ImageData::F32(array) => {
+                        assert!(array[[2, 3]].is_nan());
+                        assert_eq!(array[[0, 0]], 95.0);
+                        assert_eq!(array[[5, 4]], 109.5);
+                    },
+                    _ => panic!("scaled 16-bit pixels should be read as F32"),
+                }
+            },
+            _ => panic!("expected an image HDU"),
+        }
+
+        // The stored integers in raw mode, with the BLANK pixels as a mask.
+        let (raw, _) = HDUList::from_reader_with_options(&mut std::io::Cursor::new(&bytes), ReadOptions { mode: ReadMode::Strict, raw: true })?;
+        match &raw.hdus[1] {
+            HDU::Image(image) => {
+                assert_eq!(image.header.get_float("BSCALE")?, 0.5);
+                match &image.data {
+                    ImageData::I16(array) => assert_eq!(array, &stored),
+                    _ => panic!("raw pixels should keep their type"),
+                }
+                let mask = image.blank_mask().unwrap();
+                assert_eq!(mask.iter().filter(|&&m| m).count(), 1);
+                assert!(mask[[2, 3]]);
+            },
+            _ => panic!("expected an image HDU"),
+        }
+
+        // Floats written as scaled integers, on request.
+        let values = ArrayD::from_shape_fn(IxDyn(&[6, 5]), |i| if i[0] == 4 && i[1] == 0 { f64::NAN } else { (i[0] as f64 - 2.5) * (i[1] as f64 + 0.25) * 1e3 });
+        let mut primary = PrimaryHDU::new(PrimaryHDU::default().header, ImageData::F64(values.clone()));
+        let scaling = ImageScaling::fit(&primary.data, 16)?;
+        assert_eq!(scaling.blank, Some(-32768));
+        primary.scaling = Some(scaling.clone());
+        let mut hdu_list = HDUList::new();
+        hdu_list.add_hdu(HDU::Primary(primary));
+        let bytes = hdu_list.to_bytes()?;
+
+        let (raw, _) = HDUList::from_reader_with_options(&mut std::io::Cursor::new(&bytes), ReadOptions { mode: ReadMode::Strict, raw: true })?;
+        match &raw.hdus[0] {
+            HDU::Primary(primary) => {
+                assert_eq!(primary.header.get_int("BITPIX")?, 16);
+                assert_eq!(primary.header.get_int("BLANK")?, -32768);
+                assert!(matches!(primary.data, ImageData::I16(_)));
+            },
+            _ => panic!("expected the primary HDU"),
+        }
+        match &HDUList::from_bytes(&bytes)?.hdus[0] {
+            HDU::Primary(primary) => match &primary.data {
+                // 16-bit integers come back as F32.
+                ImageData::F32(array) => {
+                    assert!(array[[4, 0]].is_nan());
+                    for (value, expected) in array.iter().zip(values.iter()).filter(|(_, e)| !e.is_nan()) {
+                        assert!((*value as f64 - expected).abs() <= scaling.bscale);
+                    }
+                },
+                _ => panic!("scaled 16-bit pixels should be read as F32"),
+            },
+            _ => panic!("expected the primary HDU"),
+        }
+
+        // NaN pixels need a BLANK value, and pixels must fit once scaled.
+        let mut image = ImageHDU::new(common::image_header(-64, &[]), ImageData::F64(values));
+        image.scaling = Some(ImageScaling::new(16, 1.0, 0.0));
+        assert!(image.write_to_file(&mut Vec::new()).is_err());
+
+        // Empty images have nothing to scale, in extensions as in the primary HDU.
+        let mut primary = PrimaryHDU { scaling: Some(ImageScaling::new(16, 0.5, 100.0)), ..Default::default() };
+        let mut image = ImageHDU::new(image.header.clone(), ImageData::EMPTY);
+        image.scaling = Some(ImageScaling::new(16, 0.5, 100.0));
+        let mut bytes = Vec::new();
+        primary.write_to_file(&mut bytes)?;
+        image.write_to_file(&mut bytes)?;
+        assert!(!primary.header.contains_key("BSCALE") && !image.header.contains_key("BSCALE"));
+        assert_eq!(HDUList::from_bytes(&bytes)?.hdus.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unsigned_image_round_trip() -> Result<()> {
+        use astrors::io::hdulist::{HDUList, HDU, ReadMode, ReadOptions};
+        use astrors::io::hdus::compressed::{CompressionParams, CompressionType};
+        use astrors::io::hdus::image::imagehdu::ImageHDU;
+        use astrors::io::hdus::primaryhdu::PrimaryHDU;
+        use ndarray::{ArrayD, IxDyn};
+
+        let shape = IxDyn(&[8, 8]);
+        let u16s = ArrayD::from_shape_fn(shape.clone(), |i| (i[0] * 8 + i[1]) as u16 * 1000 + 1);
+        let u32s = ArrayD::from_shape_fn(shape.clone(), |i| u32::MAX - (i[0] * 8 + i[1]) as u32);
+        let u64s = ArrayD::from_shape_fn(shape.clone(), |i| u64::MAX - (i[0] * 8 + i[1]) as u64 * 3);
+        let i64s = ArrayD::from_shape_fn(shape.clone(), |i| i64::MIN + (i[0] * 8 + i[1]) as i64);
+        let i8s = ArrayD::from_shape_fn(shape.clone(), |i| (i[0] * 8 + i[1]) as i8 * 2 - 64);
+
+        let mut hdu_list = HDUList::new();
+        hdu_list.add_hdu(HDU::Primary(PrimaryHDU::new(PrimaryHDU::default().header, ImageData::U16(u16s.clone()))));
+        hdu_list.add_hdu(HDU::Image(ImageHDU::new(common::image_header(8, &[]), ImageData::U32(u32s.clone()))));
+        hdu_list.add_hdu(HDU::Image(ImageHDU::new(common::image_header(8, &[]), ImageData::U64(u64s.clone()))));
+        hdu_list.add_hdu(HDU::Image(ImageHDU::new(common::image_header(8, &[]), ImageData::I64(i64s.clone()))));
+        hdu_list.add_hdu(HDU::Image(ImageHDU::new(common::image_header(8, &[]), ImageData::I8(i8s.clone()))));
+        let mut compressed = ImageHDU::new(common::image_header(8, &[]), ImageData::U16(u16s.clone()));
+        compressed.compression = Some(CompressionParams::new(CompressionType::Rice1, vec![8, 1]));
+        hdu_list.add_hdu(HDU::Image(compressed));
+        let bytes = hdu_list.to_bytes()?;
+
+        // The pixels are stored as signed integers with the standard BZERO offsets.
+        let (raw, _) = HDUList::from_reader_with_options(&mut std::io::Cursor::new(&bytes), ReadOptions { mode: ReadMode::Strict, raw: true })?;
+        let expected = [(16, Some(32768.0)), (32, Some(2147483648.0)), (64, Some(9223372036854775808.0)), (64, None), (8, Some(-128.0))];
+        for (hdu, (bitpix, bzero)) in raw.hdus.iter().zip(expected) {
+            let (header, data) = match hdu {
+                HDU::Primary(primary) => (&primary.header, &primary.data),
+                HDU::Image(image) => (&image.header, &image.data),
+                _ => panic!("expected an image HDU"),
+            };
+            assert_eq!(header.get_int("BITPIX")?, bitpix);
+            assert_eq!(data.get_bitpix(), bitpix as i32);
+            match bzero {
+                Some(bzero) => {
+                    assert_eq!(header.get_float("BZERO")?, bzero);
+                    assert_eq!(header.get_float("BSCALE")?, 1.0);
+                },
+                None => assert!(!header.contains_key("BZERO")),
+            }
+        }
+        match &raw.hdus[0] {
+            HDU::Primary(primary) => match &primary.data {
+                ImageData::I16(array) => assert_eq!(array[[0, 0]], -32767),
+                _ => panic!("raw unsigned 16-bit pixels should be read as I16"),
+            },
+            _ => panic!("expected the primary HDU"),
+        }
+
+        // By default the offsets are removed and the pixels come back with their own type.
+        let physical = HDUList::from_bytes(&bytes)?;
+        let data: Vec<&ImageData> = physical.hdus.iter().map(|hdu| match hdu {
+            HDU::Primary(primary) => &primary.data,
+            HDU::Image(image) => &image.data,
+            _ => panic!("expected an image HDU"),
+        }).collect();
+        assert!(matches!(data[0], ImageData::U16(array) if array == u16s));
+        assert!(matches!(data[1], ImageData::U32(array) if array == u32s));
+        assert!(matches!(data[2], ImageData::U64(array) if array == u64s));
+        assert!(matches!(data[3], ImageData::I64(array) if array == i64s));
+        assert!(matches!(data[4], ImageData::I8(array) if array == i8s));
+        assert!(matches!(data[5], ImageData::U16(array) if array == u16s));
+        assert_eq!(data[0].get_dtype(), "uint16");
+        match &physical.hdus[1] {
+            HDU::Image(image) => {
+                assert_eq!(image.header.get_int("BITPIX")?, 32);
+                assert!(!image.header.contains_key("BZERO"));
+            },
+            _ => panic!("expected an image HDU"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_image_section() -> Result<()> {
+        use astrors::io::hdulist::{HDUList, HDU};
+        use astrors::io::hdus::compressed::{CompressionParams, CompressionType};
+        use astrors::io::hdus::image::AxisSlice;
+        use astrors::io::hdus::image::imagehdu::ImageHDU;
+        use astrors::io::hdus::primaryhdu::PrimaryHDU;
+        use ndarray::{s, ArrayD, IxDyn};
+        use std::io::Cursor;
+
+        let header = common::image_header(8, &[]);
+        let cube = ArrayD::from_shape_fn(IxDyn(&[3, 40, 50]), |i| (i[0] * 2000 + i[1] * 50 + i[2]) as u16 + 30000);
+        let plane = ArrayD::from_shape_fn(IxDyn(&[64, 64]), |i| (i[0] * 64 + i[1]) as i32 - 100);
+        let mut hdu_list = HDUList::new();
+        hdu_list.add_hdu(HDU::Primary(PrimaryHDU::new(PrimaryHDU::default().header, ImageData::U16(cube.clone()))));
+        hdu_list.add_hdu(HDU::Image(ImageHDU::new(header.clone(), ImageData::I32(plane.clone()))));
+        let mut compressed = ImageHDU::new(header, ImageData::I32(plane.clone()));
+        compressed.compression = Some(CompressionParams::new(CompressionType::Rice1, vec![16, 16]));
+        hdu_list.add_hdu(HDU::Image(compressed));
+        let bytes = hdu_list.to_bytes()?;
+
+        // Sections with steps, from the primary HDU and each extension in turn.
+        let mut f = Cursor::new(&bytes);
+        let section = PrimaryHDU::read_section(&mut f, &[AxisSlice::range(1, 3), AxisSlice::new(5, 30, 4), AxisSlice::new(7, 48, 3)])?;
+        match section {
+            ImageData::U16(array) => assert_eq!(array, cube.slice(s![1..3, 5..30;4, 7..48;3]).to_owned().into_dyn()),
+            _ => panic!("the section should keep the physical type"),
+        }
+        let section = ImageHDU::read_section(&mut f, &[AxisSlice::range(10, 12), AxisSlice::range(0, 64)])?;
+        match section {
+            ImageData::I32(array) => assert_eq!(array, plane.slice(s![10..12, ..]).to_owned().into_dyn()),
+            _ => panic!("the section should keep the stored type"),
+        }
+        let section = ImageHDU::read_section(&mut f, &[AxisSlice::new(3, 60, 7), AxisSlice::range(20, 21)])?;
+        match section {
+            ImageData::I32(array) => assert_eq!(array, plane.slice(s![3..60;7, 20..21]).to_owned().into_dyn()),
+            _ => panic!("the section should keep the stored type"),
+        }
+        assert_eq!(f.position() as usize, bytes.len());
+
+        // Sections must match the axes of the image.
+        let mut f = Cursor::new(&bytes);
+        assert!(PrimaryHDU::read_section(&mut f, &[AxisSlice::range(0, 1), AxisSlice::range(0, 41), AxisSlice::range(0, 1)]).is_err());
+        let mut f = Cursor::new(&bytes);
+        assert!(PrimaryHDU::read_section(&mut f, &[AxisSlice::range(0, 1), AxisSlice::range(0, 1)]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn stream_image_writer() -> Result<()> {
+        use astrors::io::hdulist::{HDUList, HDU};
+        use astrors::io::hdus::image::ImageWriter;
+        use astrors::io::hdus::primaryhdu::PrimaryHDU;
+        use ndarray::{s, ArrayD, IxDyn};
+
+        let cube = ArrayD::from_shape_fn(IxDyn(&[4, 30, 20]), |i| (i[0] * 600 + i[1] * 20 + i[2]) as u16 * 20);
+        let strips = ArrayD::from_shape_fn(IxDyn(&[50, 33]), |i| i[0] as f64 * 0.5 - i[1] as f64);
+
+        // The cube plane by plane, then an extension in blocks of rows.
+        let mut writer = ImageWriter::new(Vec::new(), PrimaryHDU::default().header, &[4, 30, 20], "uint16")?;
+        for k in 0..4 {
+            writer.write_block(&ImageData::U16(cube.slice(s![k, .., ..]).to_owned().into_dyn()))?;
+        }
+        assert_eq!(writer.remaining(), 0);
+        let bytes = writer.finish()?;
+        assert_eq!(bytes.len() % 2880, 0);
+
+        let header = common::image_header(8, &[]);
+        let mut writer = ImageWriter::new(bytes, header, &[50, 33], "float64")?;
+        for start in (0..50).step_by(16) {
+            let stop = (start + 16).min(50);
+            writer.write_block(&ImageData::F64(strips.slice(s![start..stop, ..]).to_owned().into_dyn()))?;
+        }
+        let bytes = writer.finish()?;
+
+        let hdu_list = HDUList::from_bytes(&bytes)?;
+        match &hdu_list.hdus[0] {
+            HDU::Primary(primary) => assert!(matches!(&primary.data, ImageData::U16(array) if *array == cube)),
+            _ => panic!("expected the primary HDU"),
+        }
+        match &hdu_list.hdus[1] {
+            HDU::Image(image) => assert!(matches!(&image.data, ImageData::F64(array) if *array == strips)),
+            _ => panic!("expected an image HDU"),
+        }
+
+        // Blocks must match the declared type and axes, and every pixel must be written.
+        let mut writer = ImageWriter::new(Vec::new(), PrimaryHDU::default().header, &[4, 30, 20], "uint16")?;
+        assert!(writer.write_block(&ImageData::I16(ArrayD::zeros(IxDyn(&[30, 20])))).is_err());
+        assert!(writer.write_block(&ImageData::U16(ArrayD::zeros(IxDyn(&[20, 30])))).is_err());
+        assert!(writer.write_block(&ImageData::U16(ArrayD::zeros(IxDyn(&[5, 30, 20])))).is_err());
+        writer.write_block(&ImageData::U16(ArrayD::zeros(IxDyn(&[3, 30, 20]))))?;
+        assert_eq!(writer.written(), 1800);
+        assert!(writer.finish().is_err());
+
+        Ok(())
+    }
+
+}
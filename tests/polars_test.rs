@@ -19,7 +19,7 @@ pub fn bintablehdu_test() -> Result<()> {
     let testfile = common::get_testdata_path("EUVEngc4151imgx.fits");
     let mut f: File = File::open(testfile)?;
 
-    let end_pos = PrimaryHDU::get_end_byte_position(&mut f);
+    let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
     
     //Seek end_pos 
     f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
@@ -98,21 +98,74 @@ pub fn teste_multifield(){
 
     // Iterating over the series and extracting the first and second elements
     if let Ok(list) = series.list() {
-        for opt_s in list.into_iter() {
-            if let Some(s) = opt_s {
-                // Assuming each sublist contains at least 2 elements.
-                if let Ok(sub_series) = s.f64() {
-                    if sub_series.len() >= 2 {
-                        let first = sub_series.get(0);
-                        let second = sub_series.get(1);
-                        println!("First: {:?}, Second: {:?}", first, second);
-                    }
+        for s in list.into_iter().flatten() {
+            // Assuming each sublist contains at least 2 elements.
+            if let Ok(sub_series) = s.f64() {
+                if sub_series.len() >= 2 {
+                    let first = sub_series.get(0);
+                    let second = sub_series.get(1);
+                    println!("First: {:?}, Second: {:?}", first, second);
                 }
             }
         }
     }
 
-    let df = DataFrame::new(vec![series]).unwrap();
+    let _df = DataFrame::new(vec![series]).unwrap();
 
     // println!("{:?}", df);
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn scan_fits_lazy_query() {
+    use astrors::io::scan_fits;
+    use astrors::io::hdulist::{HDUList, HDU};
+
+    // More rows than a scan batch, so that filters and limits span batches.
+    let n = 100_000;
+    let df = df!(
+        "ID" => (0..n as i64).collect::<Vec<_>>(),
+        "RA" => (0..n).map(|i| (i % 360) as f64).collect::<Vec<_>>(),
+        "MAG" => (0..n).map(|i| (i % 25) as f32).collect::<Vec<_>>(),
+        "NAME" => (0..n).map(|i| format!("SRC{:06}", i)).collect::<Vec<_>>()
+    ).unwrap();
+    let path = common::get_temp_path("test_scan_fits.fits");
+    let outfile = path.to_str().unwrap();
+    let mut hdu_list = HDUList::new();
+    hdu_list.add_hdu(HDU::Primary(PrimaryHDU::default()));
+    hdu_list.add_hdu(HDU::BinTable(BinTableHDU::new_data(df.clone())));
+    hdu_list.write_to(outfile).unwrap();
+
+    // Filtering on a column that is not selected.
+    let lazy = scan_fits(outfile, 1).unwrap();
+    assert_eq!(lazy.schema().unwrap().len(), 4);
+    let result = lazy
+        .select([col("ID"), col("RA")])
+        .filter(col("RA").lt(lit(10.0)))
+        .collect()
+        .unwrap();
+    let expected = df.clone().lazy()
+        .filter(col("RA").lt(lit(10.0)))
+        .select([col("ID"), col("RA")])
+        .collect()
+        .unwrap();
+    assert!(result.equals(&expected));
+
+    let result = scan_fits(outfile, 1).unwrap()
+        .filter(col("MAG").eq(lit(3.0f32)).and(col("ID").gt(lit(70_000i64))))
+        .select([col("NAME")])
+        .collect()
+        .unwrap();
+    assert_eq!(result.get_column_names(), vec!["NAME"]);
+    assert_eq!(result.height(), (70_001..n).filter(|i| i % 25 == 3).count());
+
+    // Row limits, with and without a filter.
+    let head = scan_fits(outfile, 1).unwrap().select([col("MAG"), col("ID")]).limit(5).collect().unwrap();
+    assert!(head.equals(&df.select(["MAG", "ID"]).unwrap().head(Some(5))));
+    let head = scan_fits(outfile, 1).unwrap().filter(col("ID").gt_eq(lit(99_990i64))).limit(20).collect().unwrap();
+    assert!(head.equals(&df.slice(99_990, 10)));
+
+    // Only binary tables can be scanned.
+    assert!(scan_fits(outfile, 0).is_err());
+    assert!(scan_fits(outfile, 2).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -24,7 +24,7 @@ mod tablehdu_tests {
         let testfile = common::get_testdata_path("WFPC2u57.fits");
         let mut f: File = File::open(testfile)?;
         
-        let end_pos = PrimaryHDU::get_end_byte_position(&mut f);
+        let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
         
         //Seek end_pos 
         f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
@@ -49,7 +49,7 @@ mod tablehdu_tests {
         let testfile = common::get_testdata_path("WFPC2u57.fits");
         let mut f: File = File::open(testfile)?;
         
-        let end_pos = PrimaryHDU::get_end_byte_position(&mut f);
+        let end_pos = PrimaryHDU::get_end_byte_position(&mut f)?;
         
         //Seek end_pos 
         f.seek(std::io::SeekFrom::Start(end_pos as u64))?;
@@ -73,4 +73,51 @@ mod tablehdu_tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn tablehdu_null_values() -> Result<()> {
+        let df = DataFrame::new(vec![
+            Series::new("ID", [Some(1i32), None, Some(3)]),
+            Series::new("MAG", [Some(1.5f64), Some(-2.0), None]),
+            Series::new("NAME", [Some("a"), None, Some("ccc")]),
+        ]).unwrap();
+
+        let mut bytes = Vec::new();
+        PrimaryHDU::default().write_to_file(&mut bytes)?;
+        let table_start = bytes.len() as u64;
+        TableHDU::new_data(df).write_to_file(&mut bytes)?;
+
+        // Null values are blank fields, read back as nulls except for strings.
+        let mut cursor = std::io::Cursor::new(&bytes);
+        cursor.set_position(table_start);
+        let tablehdu = TableHDU::read_from_file(&mut cursor)?;
+        let expected = DataFrame::new(vec![
+            Series::new("ID", [Some(1i32), None, Some(3)]),
+            Series::new("MAG", [Some(1.5f64), Some(-2.0), None]),
+            Series::new("NAME", ["a", "", "ccc"]),
+        ]).unwrap();
+        assert!(tablehdu.data.equals_missing(&expected));
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_mismatched_columns() -> Result<()> {
+        use astrors::io::hdus::table::table::{df_to_buffer, polars_to_columns};
+
+        // Columns that do not describe the DataFrame are reported instead of panicking.
+        let df = df!("ID" => [1i32, 2], "MAG" => [1.5f64, 2.0], "NAME" => ["a", "b"]).unwrap();
+        let others = [
+            df!("ID" => [1i32, 2], "MAG" => [1.5f64, 2.0]).unwrap(),
+            df!("ID" => [1.0f64, 2.0], "MAG" => [1.5f64, 2.0], "NAME" => ["a", "b"]).unwrap(),
+            df!("ID" => [1i32, 2], "MAG" => [true, false], "NAME" => ["a", "b"]).unwrap(),
+            df!("ID" => [1i32, 2], "MAG" => [1.5f64, 2.0], "NAME" => ["too long", "b"]).unwrap(),
+        ];
+        for other in &others {
+            assert!(df_to_buffer(polars_to_columns(&df).unwrap(), other, &mut Vec::new()).is_err());
+        }
+        df_to_buffer(polars_to_columns(&df).unwrap(), &df, &mut Vec::new())?;
+
+        Ok(())
+    }
+}